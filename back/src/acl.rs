@@ -0,0 +1,355 @@
+mod consts;
+pub mod err;
+
+use std::net::IpAddr;
+
+use actix_web::http::header::HeaderMap;
+use anyhow::{Error, Result};
+use ipnet::IpNet;
+
+use self::consts::{
+    ALLOW, ANY_KEYWORD, DENY, FORWARDED_HEADER, PRIVATE_KEYWORD, PRIVATE_NETWORKS,
+    X_FORWARDED_FOR_HEADER,
+};
+use self::err::AclErr;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Allow,
+    Deny,
+}
+
+/// Forwarding header set by the trusted reverse proxies
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TrustedHeader {
+    Forwarded,
+    XForwardedFor,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    action: Action,
+    networks: Vec<IpNet>,
+}
+
+/// Ordered list of allow/deny rules, the first one matching the client
+/// decides. Clients not matching any rule are denied
+#[derive(Clone, Debug)]
+pub struct Acl {
+    rules: Vec<Rule>,
+    trusted_proxies: Vec<IpNet>,
+    trusted_header: TrustedHeader,
+}
+
+// IPv4 clients of dual stack listeners show up as IPv4-mapped IPv6 addresses
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_canonical(),
+        v4 => v4,
+    }
+}
+
+/// Parses an address, a CIDR block, or one of the `private` and `any`
+/// keywords
+pub fn parse_networks(network: &str) -> Result<Vec<IpNet>> {
+    let network = network.trim();
+
+    match network {
+        PRIVATE_KEYWORD => Ok(PRIVATE_NETWORKS
+            .iter()
+            .map(|n| n.parse::<IpNet>().unwrap())
+            .collect()),
+        ANY_KEYWORD => Ok(vec![
+            "0.0.0.0/0".parse::<IpNet>().unwrap(),
+            "::/0".parse::<IpNet>().unwrap(),
+        ]),
+        _ => {
+            if let Ok(net) = network.parse::<IpNet>() {
+                return Ok(vec![net.trunc()]);
+            }
+            if let Ok(ip) = network.parse::<IpAddr>() {
+                return Ok(vec![IpNet::from(ip)]);
+            }
+
+            Err(Error::new(AclErr::MalformedNetwork(network.to_string())))
+        }
+    }
+}
+
+fn parse_rule(rule: &str) -> Result<Rule> {
+    let Some((action, network)) = rule.trim().split_once(char::is_whitespace) else {
+        return Err(Error::new(AclErr::MalformedRule(rule.to_string())));
+    };
+
+    let action = match action {
+        ALLOW => Action::Allow,
+        DENY => Action::Deny,
+        _ => return Err(Error::new(AclErr::MalformedRule(rule.to_string()))),
+    };
+
+    Ok(Rule {
+        action,
+        networks: parse_networks(network)?,
+    })
+}
+
+// Extracts the addresses from a `Forwarded` header (RFC 7239), e.g.
+// `for=192.0.2.60;proto=http, for="[2001:db8::1]:4711"`
+fn forwarded_addresses(header: &str) -> Vec<Option<IpAddr>> {
+    header
+        .split(',')
+        .map(|element| {
+            let node = element.split(';').find_map(|pair| {
+                let (key, value) = pair.trim().split_once('=')?;
+                key.eq_ignore_ascii_case("for").then_some(value)
+            })?;
+            let node = node.trim().trim_matches('"');
+
+            if let Some(bracketed) = node.strip_prefix('[') {
+                return bracketed.split(']').next()?.parse::<IpAddr>().ok();
+            }
+            if let Ok(ip) = node.parse::<IpAddr>() {
+                return Some(ip);
+            }
+
+            // IPv4 address with a port
+            node.rsplit_once(':')?.0.parse::<IpAddr>().ok()
+        })
+        .collect()
+}
+
+fn x_forwarded_for_addresses(header: &str) -> Vec<Option<IpAddr>> {
+    header
+        .split(',')
+        .map(|a| a.trim().parse::<IpAddr>().ok())
+        .collect()
+}
+
+impl TrustedHeader {
+    pub fn parse(header: &str) -> Result<Self> {
+        match header.trim().to_ascii_lowercase().as_str() {
+            FORWARDED_HEADER => Ok(TrustedHeader::Forwarded),
+            X_FORWARDED_FOR_HEADER => Ok(TrustedHeader::XForwardedFor),
+            _ => Err(Error::new(AclErr::UnknownHeader(header.to_string()))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            TrustedHeader::Forwarded => FORWARDED_HEADER,
+            TrustedHeader::XForwardedFor => X_FORWARDED_FOR_HEADER,
+        }
+    }
+
+    fn addresses(self, header: &str) -> Vec<Option<IpAddr>> {
+        match self {
+            TrustedHeader::Forwarded => forwarded_addresses(header),
+            TrustedHeader::XForwardedFor => x_forwarded_for_addresses(header),
+        }
+    }
+}
+
+impl Acl {
+    pub fn new(rules: &[String], trusted_proxies: &[String], trusted_header: &str) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|r| parse_rule(r))
+            .collect::<Result<Vec<Rule>>>()?;
+
+        let mut proxies = Vec::new();
+        for proxy in trusted_proxies {
+            proxies.extend(parse_networks(proxy)?);
+        }
+
+        Ok(Acl {
+            rules,
+            trusted_proxies: proxies,
+            trusted_header: TrustedHeader::parse(trusted_header)?,
+        })
+    }
+
+    pub fn check(&self, ip: IpAddr) -> Action {
+        let ip = canonical(ip);
+
+        self.rules
+            .iter()
+            .find(|r| r.networks.iter().any(|n| n.contains(&ip)))
+            .map(|r| r.action)
+            .unwrap_or(Action::Deny)
+    }
+
+    fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        let ip = canonical(ip);
+        self.trusted_proxies.iter().any(|n| n.contains(&ip))
+    }
+
+    /// Address of the client, taken from the trusted forwarding header when
+    /// the connection comes from a trusted reverse proxy. The other header
+    /// is ignored, as proxies only overwrite or append to the one they set.
+    /// The chain, over every instance of the header, is walked from the
+    /// closest hop, stopping at the first address which is not a trusted
+    /// proxy, so that clients can't spoof their address
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted_proxy(peer) {
+            return canonical(peer);
+        }
+
+        let mut chain = Vec::new();
+        for value in headers.get_all(self.trusted_header.name()) {
+            // A header which isn't valid text can't be walked past
+            let Ok(value) = value.to_str() else {
+                chain.push(None);
+                continue;
+            };
+            chain.extend(self.trusted_header.addresses(value));
+        }
+
+        let mut client = peer;
+        for hop in chain.iter().rev() {
+            // An unparsable hop (e.g. an obfuscated identifier) can't be
+            // trusted, so the last known address is used
+            let Some(hop) = hop else {
+                break;
+            };
+
+            client = *hop;
+            if !self.is_trusted_proxy(client) {
+                break;
+            }
+        }
+
+        canonical(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{HeaderName, HeaderValue};
+
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn acl(rules: &[&str], trusted_proxies: &[&str], trusted_header: &str) -> Acl {
+        let rules: Vec<String> = rules.iter().map(|r| r.to_string()).collect();
+        let proxies: Vec<String> = trusted_proxies.iter().map(|p| p.to_string()).collect();
+        Acl::new(&rules, &proxies, trusted_header).unwrap()
+    }
+
+    fn header_map(headers: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(
+                HeaderName::from_static(name),
+                HeaderValue::from_static(value),
+            );
+        }
+        map
+    }
+
+    #[test]
+    fn first_matching_rule_decides() {
+        let acl = acl(
+            &[
+                "deny 192.168.1.66",
+                "allow 192.168.1.0/24",
+                "allow fd00::/8",
+            ],
+            &[],
+            "x-forwarded-for",
+        );
+
+        assert_eq!(acl.check(ip("192.168.1.10")), Action::Allow);
+        assert_eq!(acl.check(ip("192.168.1.66")), Action::Deny);
+        assert_eq!(acl.check(ip("192.168.2.10")), Action::Deny);
+        assert_eq!(acl.check(ip("fd12::1")), Action::Allow);
+        // IPv4 clients of dual stack listeners
+        assert_eq!(acl.check(ip("::ffff:192.168.1.10")), Action::Allow);
+    }
+
+    #[test]
+    fn cidr_blocks_are_truncated() {
+        assert_eq!(
+            parse_networks("192.168.1.42/24").unwrap(),
+            vec!["192.168.1.0/24".parse::<IpNet>().unwrap()]
+        );
+        assert_eq!(
+            parse_networks("private").unwrap().len(),
+            PRIVATE_NETWORKS.len()
+        );
+        assert!(parse_networks("192.168.1.0/33").is_err());
+        assert!(parse_rule("permit any").is_err());
+        assert!(Acl::new(&[], &[], "x-real-ip").is_err());
+    }
+
+    #[test]
+    fn headers_are_ignored_from_untrusted_peers() {
+        let acl = acl(&["allow any"], &["127.0.0.1"], "x-forwarded-for");
+        let headers = header_map(&[("x-forwarded-for", "10.0.0.1")]);
+
+        assert_eq!(acl.client_ip(ip("192.0.2.1"), &headers), ip("192.0.2.1"));
+        assert_eq!(acl.client_ip(ip("127.0.0.1"), &headers), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn chain_is_walked_from_the_closest_hop() {
+        let acl = acl(
+            &["allow any"],
+            &["127.0.0.1", "10.0.0.0/8"],
+            "x-forwarded-for",
+        );
+
+        // The client prepended a private address, the proxies appended theirs
+        let headers = header_map(&[("x-forwarded-for", "192.168.1.1, 203.0.113.7, 10.0.0.2")]);
+        assert_eq!(acl.client_ip(ip("127.0.0.1"), &headers), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn every_instance_of_the_header_is_walked() {
+        let acl = acl(&["allow any"], &["127.0.0.1"], "x-forwarded-for");
+
+        // The client sent its own header, the proxy added another one
+        let headers = header_map(&[
+            ("x-forwarded-for", "192.168.1.1"),
+            ("x-forwarded-for", "203.0.113.7"),
+        ]);
+        assert_eq!(acl.client_ip(ip("127.0.0.1"), &headers), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn spoofed_header_of_the_other_kind_is_ignored() {
+        let acl = acl(&["allow any"], &["127.0.0.1"], "x-forwarded-for");
+
+        // The proxy only sets `X-Forwarded-For`, so `Forwarded` comes from
+        // the client
+        let headers = header_map(&[
+            ("forwarded", "for=192.168.1.1"),
+            ("x-forwarded-for", "203.0.113.7"),
+        ]);
+        assert_eq!(acl.client_ip(ip("127.0.0.1"), &headers), ip("203.0.113.7"));
+
+        let headers = header_map(&[("forwarded", "for=192.168.1.1")]);
+        assert_eq!(acl.client_ip(ip("127.0.0.1"), &headers), ip("127.0.0.1"));
+    }
+
+    #[test]
+    fn forwarded_header() {
+        let acl = acl(&["allow any"], &["127.0.0.1"], "forwarded");
+
+        let headers = header_map(&[
+            ("forwarded", "for=192.168.1.1"),
+            ("forwarded", r#"for="[2001:db8::1]:4711";proto=https"#),
+            ("x-forwarded-for", "192.168.1.2"),
+        ]);
+        assert_eq!(acl.client_ip(ip("127.0.0.1"), &headers), ip("2001:db8::1"));
+
+        let headers = header_map(&[("forwarded", "for=192.0.2.60:8080, for=127.0.0.1")]);
+        assert_eq!(acl.client_ip(ip("127.0.0.1"), &headers), ip("192.0.2.60"));
+
+        // Obfuscated identifiers can't be walked past
+        let headers = header_map(&[("forwarded", "for=192.0.2.60, for=_hidden")]);
+        assert_eq!(acl.client_ip(ip("127.0.0.1"), &headers), ip("127.0.0.1"));
+    }
+}
@@ -0,0 +1,20 @@
+// Loopback, RFC 1918, unique local and link-local addresses
+pub const PRIVATE_NETWORKS: &[&str; 8] = &[
+    "127.0.0.0/8",
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "::1/128",
+    "fc00::/7",
+    "fe80::/10",
+    "169.254.0.0/16",
+];
+
+pub const PRIVATE_KEYWORD: &str = "private";
+pub const ANY_KEYWORD: &str = "any";
+
+pub const ALLOW: &str = "allow";
+pub const DENY: &str = "deny";
+
+pub const FORWARDED_HEADER: &str = "forwarded";
+pub const X_FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
@@ -0,0 +1,32 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum AclErr {
+    MalformedRule(String),
+    MalformedNetwork(String),
+    UnknownHeader(String),
+}
+
+impl std::error::Error for AclErr {}
+
+impl fmt::Display for AclErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AclErr::MalformedRule(r) => write!(
+                f,
+                "Malformed rule `{}`, expected `allow <network>` or `deny <network>`",
+                r
+            ),
+            AclErr::MalformedNetwork(n) => write!(
+                f,
+                "Malformed network `{}`, expected an IP address, a CIDR block, `private` or `any`",
+                n
+            ),
+            AclErr::UnknownHeader(h) => write!(
+                f,
+                "Unknown forwarding header `{}`, expected `forwarded` or `x-forwarded-for`",
+                h
+            ),
+        }
+    }
+}
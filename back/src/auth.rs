@@ -0,0 +1,452 @@
+mod consts;
+pub mod err;
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Error, Result};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHashString, SaltString};
+use argon2::{Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier};
+use serde::{Deserialize, Serialize};
+
+pub use self::consts::{
+    BEARER_PREFIX, DEFAULT_SESSION_TTL, LOGIN_FAILED_MESSAGE, LOGIN_PAGE, LOGIN_PATH, LOGOUT_PATH,
+    SESSION_COOKIE, TOKEN_QUERY_PARAM, TOO_MANY_LOGINS_MESSAGE,
+};
+use self::consts::{
+    DUMMY_PASSWORD, FAILED_LOGINS_WINDOW, MAX_FAILED_LOGINS, MAX_SESSIONS, SESSION_ID_BYTES,
+};
+use self::err::AuthErr;
+
+/// What an authenticated client can see. Scopes are ordered, each one
+/// includes everything the previous ones can access
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    // Everything except processes data
+    Summary,
+    #[default]
+    Full,
+    Admin,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TokenConfig {
+    pub name: String,
+    pub token: String,
+    #[serde(default)]
+    pub scope: Scope,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    pub username: String,
+    pub password_hash: String,
+    #[serde(default)]
+    pub scope: Scope,
+}
+
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub name: String,
+    pub scope: Scope,
+}
+
+struct Session {
+    identity: Identity,
+    expires: Instant,
+}
+
+struct User {
+    password_hash: PasswordHashString,
+    scope: Scope,
+}
+
+/// Outcome of a login attempt
+pub enum Login {
+    // Id of the new session
+    Session(String),
+    Failed,
+    // The client failed too many times recently, nothing was verified
+    Refused,
+}
+
+// Failed login attempts of a client since the first one of the window
+struct FailedLogins {
+    count: u32,
+    since: Instant,
+}
+
+pub struct Auth {
+    tokens: Vec<(String, Identity)>,
+    users: HashMap<String, User>,
+    // Verified when the user doesn't exist, so that unknown usernames take
+    // as long as wrong passwords
+    dummy_hash: Option<PasswordHashString>,
+    sessions: Mutex<HashMap<String, Session>>,
+    failed_logins: Mutex<HashMap<IpAddr, FailedLogins>>,
+    session_ttl: Duration,
+}
+
+// Comparison time only depends on the length, to not leak how much of a
+// token was guessed right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    match Argon2::default().hash_password(password.as_bytes(), &salt) {
+        Ok(h) => Ok(h.to_string()),
+        Err(e) => Err(Error::new(AuthErr::HashingFailed(e.to_string()))),
+    }
+}
+
+// Hash of a password nobody knows, with the same cost as the given one
+fn dummy_hash(like: &PasswordHash) -> Result<PasswordHashString> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Params::try_from(like).and_then(|params| {
+        Argon2::default().hash_password_customized(
+            DUMMY_PASSWORD.as_bytes(),
+            Some(like.algorithm),
+            like.version,
+            params,
+            &salt,
+        )
+    });
+
+    match hash {
+        Ok(h) => Ok(h.serialize()),
+        Err(e) => Err(Error::new(AuthErr::HashingFailed(e.to_string()))),
+    }
+}
+
+impl Identity {
+    // Used for every client when authentication is not configured
+    pub fn anonymous() -> Self {
+        Identity {
+            name: String::from("anonymous"),
+            scope: Scope::Admin,
+        }
+    }
+}
+
+impl Auth {
+    pub fn new(
+        tokens: &[TokenConfig],
+        users: &[UserConfig],
+        session_ttl: Duration,
+    ) -> Result<Self> {
+        let mut parsed_tokens = Vec::new();
+        for t in tokens {
+            if t.token.is_empty() {
+                return Err(Error::new(AuthErr::EmptyToken(t.name.clone())));
+            }
+
+            parsed_tokens.push((
+                t.token.clone(),
+                Identity {
+                    name: t.name.clone(),
+                    scope: t.scope,
+                },
+            ));
+        }
+
+        let mut parsed_users = HashMap::new();
+        let mut dummy = None;
+        for u in users {
+            let Ok(hash) = PasswordHash::new(&u.password_hash) else {
+                return Err(Error::new(AuthErr::MalformedPasswordHash(
+                    u.username.clone(),
+                )));
+            };
+
+            if dummy.is_none() {
+                dummy = Some(dummy_hash(&hash)?);
+            }
+
+            let user = User {
+                password_hash: hash.serialize(),
+                scope: u.scope,
+            };
+            if parsed_users.insert(u.username.clone(), user).is_some() {
+                return Err(Error::new(AuthErr::DuplicateUser(u.username.clone())));
+            }
+        }
+
+        Ok(Auth {
+            tokens: parsed_tokens,
+            users: parsed_users,
+            dummy_hash: dummy,
+            sessions: Mutex::new(HashMap::new()),
+            failed_logins: Mutex::new(HashMap::new()),
+            session_ttl,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty() || !self.users.is_empty()
+    }
+
+    pub fn session_ttl(&self) -> Duration {
+        self.session_ttl
+    }
+
+    pub fn authenticate_token(&self, token: &str) -> Option<Identity> {
+        self.tokens
+            .iter()
+            .find(|(t, _)| constant_time_eq(t.as_bytes(), token.as_bytes()))
+            .map(|(_, i)| i.clone())
+    }
+
+    pub fn authenticate_session(&self, session_id: &str) -> Option<Identity> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, s| s.expires > now);
+
+        sessions.get(session_id).map(|s| s.identity.clone())
+    }
+
+    // Counts the attempt as failed until it succeeds, so that concurrent
+    // attempts can't get past the limit, unless the client failed too many
+    // times recently
+    fn reserve_attempt(&self, client: IpAddr) -> bool {
+        let mut failed_logins = self.failed_logins.lock().unwrap();
+        failed_logins.retain(|_, f| f.since.elapsed() < FAILED_LOGINS_WINDOW);
+
+        let failed = failed_logins.entry(client).or_insert_with(|| FailedLogins {
+            count: 0,
+            since: Instant::now(),
+        });
+        if failed.count >= MAX_FAILED_LOGINS {
+            return false;
+        }
+        failed.count += 1;
+        true
+    }
+
+    /// Verifies the credentials and opens a new session. Password
+    /// verification is slow by design, so this should not be called from
+    /// async code
+    pub fn login(&self, client: IpAddr, username: &str, password: &str) -> Login {
+        let user = self.users.get(username);
+        let hash = match (user, &self.dummy_hash) {
+            (Some(user), _) => user.password_hash.password_hash(),
+            (None, Some(dummy)) => dummy.password_hash(),
+            (None, None) => return Login::Failed,
+        };
+
+        if !self.reserve_attempt(client) {
+            return Login::Refused;
+        }
+        let verified = Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok();
+        let (true, Some(user)) = (verified, user) else {
+            return Login::Failed;
+        };
+        self.failed_logins.lock().unwrap().remove(&client);
+
+        let mut id_bytes = [0u8; SESSION_ID_BYTES];
+        OsRng.fill_bytes(&mut id_bytes);
+        let session_id = id_bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, s| s.expires > now);
+        if sessions.len() >= MAX_SESSIONS {
+            let expiring = sessions
+                .iter()
+                .min_by_key(|(_, s)| s.expires)
+                .map(|(id, _)| id.clone());
+            if let Some(id) = expiring {
+                sessions.remove(&id);
+            }
+        }
+        sessions.insert(
+            session_id.clone(),
+            Session {
+                identity: Identity {
+                    name: username.to_string(),
+                    scope: user.scope,
+                },
+                expires: now + self.session_ttl,
+            },
+        );
+
+        Login::Session(session_id)
+    }
+
+    pub fn logout(&self, session_id: &str) {
+        self.sessions.lock().unwrap().remove(session_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> IpAddr {
+        IpAddr::from([192, 168, 1, 10])
+    }
+
+    // Cheap to verify, unlike the default parameters
+    fn weak_hash(password: &str) -> String {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password_customized(
+                password.as_bytes(),
+                None,
+                None,
+                Params::new(8, 1, 1, None).unwrap(),
+                &salt,
+            )
+            .unwrap()
+            .to_string()
+    }
+
+    fn auth() -> Auth {
+        let tokens = [
+            TokenConfig {
+                name: String::from("grafana"),
+                token: String::from("s3cr3t"),
+                scope: Scope::Summary,
+            },
+            TokenConfig {
+                name: String::from("admin"),
+                token: String::from("adm1n"),
+                scope: Scope::Admin,
+            },
+        ];
+        let users = [UserConfig {
+            username: String::from("pi"),
+            password_hash: weak_hash("raspberry"),
+            scope: Scope::Full,
+        }];
+
+        Auth::new(&tokens, &users, Duration::from_secs(60)).unwrap()
+    }
+
+    #[test]
+    fn tokens_carry_their_scope() {
+        let auth = auth();
+
+        let identity = auth.authenticate_token("s3cr3t").unwrap();
+        assert_eq!(identity.name, "grafana");
+        assert_eq!(identity.scope, Scope::Summary);
+        assert_eq!(
+            auth.authenticate_token("adm1n").unwrap().scope,
+            Scope::Admin
+        );
+        assert!(auth.authenticate_token("s3cr3").is_none());
+        assert!(auth.authenticate_token("").is_none());
+    }
+
+    #[test]
+    fn sessions_carry_the_user_scope() {
+        let auth = auth();
+
+        let Login::Session(session) = auth.login(client(), "pi", "raspberry") else {
+            panic!("the login failed");
+        };
+        let identity = auth.authenticate_session(&session).unwrap();
+        assert_eq!(identity.name, "pi");
+        assert_eq!(identity.scope, Scope::Full);
+
+        auth.logout(&session);
+        assert!(auth.authenticate_session(&session).is_none());
+    }
+
+    #[test]
+    fn wrong_credentials_are_rejected() {
+        let auth = auth();
+
+        assert!(matches!(
+            auth.login(client(), "pi", "raspberri"),
+            Login::Failed
+        ));
+        // Verified against the dummy hash, even with its password
+        assert!(matches!(
+            auth.login(client(), "root", "raspberry"),
+            Login::Failed
+        ));
+        assert!(matches!(
+            auth.login(client(), "root", DUMMY_PASSWORD),
+            Login::Failed
+        ));
+    }
+
+    #[test]
+    fn clients_failing_too_often_are_refused() {
+        let auth = auth();
+        let other = IpAddr::from([192, 168, 1, 11]);
+
+        for _ in 0..MAX_FAILED_LOGINS {
+            assert!(matches!(
+                auth.login(client(), "pi", "123456"),
+                Login::Failed
+            ));
+        }
+        // Even with the right password
+        assert!(matches!(
+            auth.login(client(), "pi", "raspberry"),
+            Login::Refused
+        ));
+
+        // A successful login forgets the failures
+        auth.login(other, "pi", "123456");
+        assert!(matches!(
+            auth.login(other, "pi", "raspberry"),
+            Login::Session(_)
+        ));
+        assert!(auth.failed_logins.lock().unwrap().get(&other).is_none());
+    }
+
+    #[test]
+    fn attempts_are_counted_before_being_verified() {
+        let auth = auth();
+
+        // As many attempts in flight as allowed failures
+        for _ in 0..MAX_FAILED_LOGINS {
+            assert!(auth.reserve_attempt(client()));
+        }
+        assert!(!auth.reserve_attempt(client()));
+    }
+
+    #[test]
+    fn sessions_are_capped() {
+        let auth = auth();
+
+        let sessions = (0..=MAX_SESSIONS)
+            .map(|_| match auth.login(client(), "pi", "raspberry") {
+                Login::Session(id) => id,
+                _ => panic!("the login failed"),
+            })
+            .collect::<Vec<String>>();
+
+        assert_eq!(auth.sessions.lock().unwrap().len(), MAX_SESSIONS);
+        // The oldest one made room for the last one
+        assert!(auth.authenticate_session(&sessions[0]).is_none());
+        assert!(auth.authenticate_session(&sessions[MAX_SESSIONS]).is_some());
+    }
+
+    #[test]
+    fn scopes_are_ordered() {
+        assert!(Scope::Summary < Scope::Full && Scope::Full < Scope::Admin);
+        assert_eq!(Scope::default(), Scope::Full);
+        assert!(Auth::new(&[], &[], Duration::from_secs(60)).is_ok_and(|a| !a.is_enabled()));
+    }
+}
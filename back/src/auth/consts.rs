@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+pub const SESSION_COOKIE: &str = "pst_session";
+pub const TOKEN_QUERY_PARAM: &str = "token";
+pub const BEARER_PREFIX: &str = "Bearer ";
+
+pub const LOGIN_PATH: &str = "/login";
+pub const LOGOUT_PATH: &str = "/logout";
+
+pub const DEFAULT_SESSION_TTL: &str = "7d";
+pub const SESSION_ID_BYTES: usize = 32;
+// Sessions kept at most, the ones expiring first making room for new ones
+pub const MAX_SESSIONS: usize = 1000;
+
+// Clients failing to log in that many times are refused until the window
+// since their first failure is over
+pub const MAX_FAILED_LOGINS: u32 = 5;
+pub const FAILED_LOGINS_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+pub const DUMMY_PASSWORD: &str = "pi-status dummy password";
+
+pub const LOGIN_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1.0" />
+    <title>pi-status</title>
+    <style>
+        body { font-family: sans-serif; background: #18181b; color: #e4e4e7; display: flex; justify-content: center; align-items: center; min-height: 100vh; margin: 0; }
+        form { display: flex; flex-direction: column; gap: 0.75rem; width: 16rem; }
+        input, button { padding: 0.5rem; border-radius: 0.375rem; border: 1px solid #3f3f46; background: #27272a; color: inherit; }
+        button { cursor: pointer; }
+        p { color: #f87171; margin: 0; }
+    </style>
+</head>
+<body>
+    <form method="post" action="/login">
+        <h1>pi-status</h1>
+        {error}
+        <input name="username" placeholder="Username" autocomplete="username" required />
+        <input name="password" type="password" placeholder="Password" autocomplete="current-password" required />
+        <button type="submit">Log in</button>
+    </form>
+</body>
+</html>
+"#;
+pub const LOGIN_FAILED_MESSAGE: &str = "<p>Wrong username or password</p>";
+pub const TOO_MANY_LOGINS_MESSAGE: &str = "<p>Too many failed attempts, try again later</p>";
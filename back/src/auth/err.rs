@@ -0,0 +1,26 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum AuthErr {
+    MalformedPasswordHash(String),
+    DuplicateUser(String),
+    EmptyToken(String),
+    HashingFailed(String),
+}
+
+impl std::error::Error for AuthErr {}
+
+impl fmt::Display for AuthErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthErr::MalformedPasswordHash(u) => write!(
+                f,
+                "The password hash of user `{}` is not a valid PHC string, generate it with --hash-password",
+                u
+            ),
+            AuthErr::DuplicateUser(u) => write!(f, "User `{}` is configured more than once", u),
+            AuthErr::EmptyToken(n) => write!(f, "Token `{}` is empty", n),
+            AuthErr::HashingFailed(e) => write!(f, "Could not hash the password: {}", e),
+        }
+    }
+}
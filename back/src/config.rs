@@ -0,0 +1,557 @@
+mod consts;
+pub mod err;
+
+use crate::acl::Acl;
+use crate::auth::{self, Auth, TokenConfig, UserConfig};
+use crate::demo::{self, DemoConfig};
+use crate::history::{self, TierSpec};
+use crate::record::{self, ReplayConfig};
+use crate::status::source::{self, Source};
+use crate::status::{self, CollectorSettings, ScheduledCollector, UpdateSettings};
+use crate::store::{self, StoreConfig};
+use crate::tls::{self, TlsConfig};
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+
+use self::consts::{
+    DEFAULT_ACL_RULE, DEFAULT_ADDRESS, DEFAULT_COLLECTOR_TIMEOUT, DEFAULT_PORT,
+    DEFAULT_SERVE_DIRECTORY, DEFAULT_TRUSTED_HEADER, DEFAULT_UPDATE_INTERVAL,
+    MAX_SCHEDULE_DURATION, PUBLIC_ACL_RULE,
+};
+use self::err::ConfigErr;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub web: WebConfig,
+    pub collectors: CollectorsConfig,
+    pub metrics: MetricsFileConfig,
+    pub history: HistoryConfig,
+    pub store: StoreFileConfig,
+    pub record: RecordFileConfig,
+    pub replay: ReplayFileConfig,
+    pub demo: DemoFileConfig,
+    pub auth: AuthFileConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebConfig {
+    pub serve_directory: String,
+    pub listeners: Vec<ListenerConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenerConfig {
+    pub address: String,
+    pub port: u16,
+    pub public: bool,
+    pub acl: Vec<String>,
+    pub trusted_proxies: Vec<String>,
+    pub trusted_header: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+}
+
+/// Every other key is a collector section, e.g. `[collectors.disk]`.
+/// Unknown collector names are rejected by validation
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CollectorsConfig {
+    pub update_interval: String,
+    #[serde(flatten)]
+    pub collectors: BTreeMap<String, CollectorConfig>,
+}
+
+/// Options shared by every collector. The remaining ones are handed to the
+/// collector, which rejects those it doesn't know
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CollectorConfig {
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+    pub timeout: String,
+    pub suppress_errors: bool,
+    #[serde(flatten)]
+    pub options: toml::Table,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsFileConfig {
+    pub processes: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub enabled: bool,
+    pub tiers: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreFileConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    pub commit_interval: String,
+    pub raw_retention: String,
+    pub rollup_retention: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecordFileConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+/// Serves a recording instead of the data of this machine
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplayFileConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    pub speed: f64,
+}
+
+/// Serves a simulated machine instead of this one
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DemoFileConfig {
+    pub enabled: bool,
+    pub seed: u64,
+}
+
+/// Authentication is enabled as soon as a token or a user is configured
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthFileConfig {
+    pub session_ttl: String,
+    pub tokens: Vec<TokenConfig>,
+    pub users: Vec<UserConfig>,
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        WebConfig {
+            serve_directory: String::from(DEFAULT_SERVE_DIRECTORY),
+            listeners: vec![ListenerConfig::default()],
+        }
+    }
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        ListenerConfig {
+            address: String::from(DEFAULT_ADDRESS),
+            port: DEFAULT_PORT,
+            public: false,
+            acl: vec![String::from(DEFAULT_ACL_RULE)],
+            trusted_proxies: Vec::new(),
+            trusted_header: String::from(DEFAULT_TRUSTED_HEADER),
+            tls: None,
+        }
+    }
+}
+
+impl Default for CollectorsConfig {
+    fn default() -> Self {
+        CollectorsConfig {
+            update_interval: String::from(DEFAULT_UPDATE_INTERVAL),
+            collectors: status::builtin_collectors(source::host())
+                .iter()
+                .map(|c| (c.name().to_string(), CollectorConfig::default()))
+                .collect(),
+        }
+    }
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        CollectorConfig {
+            enabled: true,
+            interval: None,
+            timeout: String::from(DEFAULT_COLLECTOR_TIMEOUT),
+            suppress_errors: false,
+            options: toml::Table::new(),
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            enabled: false,
+            tiers: String::from(history::DEFAULT_TIERS),
+        }
+    }
+}
+
+impl Default for StoreFileConfig {
+    fn default() -> Self {
+        StoreFileConfig {
+            path: None,
+            commit_interval: String::from(store::DEFAULT_COMMIT_INTERVAL),
+            raw_retention: String::from(store::DEFAULT_RAW_RETENTION),
+            rollup_retention: String::from(store::DEFAULT_ROLLUP_RETENTION),
+        }
+    }
+}
+
+impl Default for ReplayFileConfig {
+    fn default() -> Self {
+        ReplayFileConfig {
+            path: None,
+            speed: record::DEFAULT_REPLAY_SPEED,
+        }
+    }
+}
+
+impl Default for DemoFileConfig {
+    fn default() -> Self {
+        DemoFileConfig {
+            enabled: false,
+            seed: demo::DEFAULT_SEED,
+        }
+    }
+}
+
+impl Default for AuthFileConfig {
+    fn default() -> Self {
+        AuthFileConfig {
+            session_ttl: String::from(auth::DEFAULT_SESSION_TTL),
+            tokens: Vec::new(),
+            users: Vec::new(),
+        }
+    }
+}
+
+/// Parses durations like `500ms`, `30s`, `15m`, `24h` or `7d`
+pub fn parse_duration(duration: &str) -> Result<Duration> {
+    let malformed = || Error::new(ConfigErr::MalformedDuration(duration.to_string()));
+
+    let trimmed = duration.trim();
+    let unit_start = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(malformed)?;
+    let (amount, unit) = trimmed.split_at(unit_start);
+    let amount = amount.parse::<u64>().map_err(|_| malformed())?;
+
+    // Overflowing amounts are as malformed as unknown units
+    let duration = match unit {
+        "ms" => Some(Duration::from_millis(amount)),
+        "s" => Some(Duration::from_secs(amount)),
+        "m" => amount.checked_mul(60).map(Duration::from_secs),
+        "h" => amount.checked_mul(60 * 60).map(Duration::from_secs),
+        "d" => amount.checked_mul(24 * 60 * 60).map(Duration::from_secs),
+        _ => None,
+    }
+    .ok_or_else(malformed)?;
+
+    if duration.is_zero() {
+        return Err(malformed());
+    }
+
+    Ok(duration)
+}
+
+// Like parse_duration, but for the places where only whole seconds make sense
+pub fn parse_duration_secs(duration: &str) -> Result<u64> {
+    let parsed = parse_duration(duration)?;
+    if parsed.subsec_nanos() != 0 {
+        return Err(Error::new(ConfigErr::MalformedDuration(
+            duration.to_string(),
+        )));
+    }
+
+    Ok(parsed.as_secs())
+}
+
+fn field_duration(field: &str, value: &str) -> Result<Duration> {
+    parse_duration(value).with_context(|| format!("Invalid value for `{}`", field))
+}
+
+// Intervals and timeouts are added to instants, which must not overflow
+fn schedule_duration(field: &str, value: &str) -> Result<Duration> {
+    let duration = field_duration(field, value)?;
+    if duration > MAX_SCHEDULE_DURATION {
+        return Err(Error::new(ConfigErr::InvalidValue {
+            field: field.to_string(),
+            reason: String::from("it can't be longer than 1d"),
+        }));
+    }
+
+    Ok(duration)
+}
+
+fn field_duration_secs(field: &str, value: &str) -> Result<u64> {
+    parse_duration_secs(value).with_context(|| format!("Invalid value for `{}`", field))
+}
+
+impl ListenerConfig {
+    /// Being public appends a catch-all allow rule, so explicit deny rules
+    /// still apply
+    pub fn acl(&self) -> Result<Acl> {
+        let mut rules = self.acl.clone();
+        if self.public {
+            rules.push(String::from(PUBLIC_ACL_RULE));
+        }
+
+        Acl::new(&rules, &self.trusted_proxies, &self.trusted_header)
+    }
+}
+
+impl CollectorsConfig {
+    /// Section of a collector, created when the configuration has none
+    pub fn collector_mut(&mut self, name: &str) -> &mut CollectorConfig {
+        self.collectors.entry(name.to_string()).or_default()
+    }
+}
+
+impl CollectorConfig {
+    fn settings(&self, name: &str) -> Result<CollectorSettings> {
+        let interval = match &self.interval {
+            Some(i) => Some(schedule_duration(
+                &format!("collectors.{}.interval", name),
+                i,
+            )?),
+            None => None,
+        };
+
+        Ok(CollectorSettings {
+            enabled: self.enabled,
+            interval,
+            timeout: schedule_duration(&format!("collectors.{}.timeout", name), &self.timeout)?,
+            suppress_errors: self.suppress_errors,
+        })
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read configuration file {}", path.display()))?;
+
+        toml::from_str(&content)
+            .with_context(|| format!("Could not parse configuration file {}", path.display()))
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Checks everything that can be checked before starting, so that
+    /// mistakes are reported all at once at startup rather than later on
+    pub fn validate(&self) -> Result<()> {
+        self.update_settings(source::host())?;
+
+        if self.web.listeners.is_empty() {
+            return Err(Error::new(ConfigErr::NoListeners));
+        }
+
+        if self.record.path.is_some() && self.replay.path.is_some() {
+            return Err(Error::new(ConfigErr::RecordAndReplay));
+        }
+        if self.demo.enabled && self.replay.path.is_some() {
+            return Err(Error::new(ConfigErr::DemoAndReplay));
+        }
+        if !(self.replay.speed.is_finite() && self.replay.speed > 0.0) {
+            return Err(Error::new(ConfigErr::InvalidValue {
+                field: String::from("replay.speed"),
+                reason: String::from("it must be a positive number"),
+            }));
+        }
+
+        let mut seen = HashSet::new();
+        for (i, listener) in self.web.listeners.iter().enumerate() {
+            if listener.address.parse::<IpAddr>().is_err() {
+                return Err(Error::new(ConfigErr::InvalidValue {
+                    field: format!("web.listeners[{}].address", i),
+                    reason: format!("`{}` is not an IP address", listener.address),
+                }));
+            }
+
+            listener
+                .acl()
+                .with_context(|| format!("Invalid access control of `web.listeners[{}]`", i))?;
+
+            let mut ports = vec![listener.port];
+            if let Some(tls) = &listener.tls {
+                tls::validate(tls).with_context(|| {
+                    format!("Invalid TLS configuration of `web.listeners[{}]`", i)
+                })?;
+                ports.extend(tls.redirect_port);
+            }
+
+            for port in ports {
+                let key = format!("{}:{}", listener.address, port);
+                if !seen.insert(key.clone()) {
+                    return Err(Error::new(ConfigErr::DuplicateListener(key)));
+                }
+            }
+        }
+
+        self.history_tiers()
+            .context("Invalid value for `history.tiers`")?;
+        self.store_config()?;
+        self.auth()?;
+
+        Ok(())
+    }
+
+    pub fn auth(&self) -> Result<Auth> {
+        let session_ttl = field_duration("auth.session_ttl", &self.auth.session_ttl)?;
+
+        Auth::new(&self.auth.tokens, &self.auth.users, session_ttl)
+            .context("Invalid authentication configuration")
+    }
+
+    /// Configures every collector, the ones missing from the configuration
+    /// with their defaults, reading their data from `source`
+    pub fn update_settings(&self, source: Arc<dyn Source>) -> Result<UpdateSettings> {
+        let c = &self.collectors;
+
+        let builtins = status::builtin_collectors(source);
+        if let Some(name) = c
+            .collectors
+            .keys()
+            .find(|n| !builtins.iter().any(|b| b.name() == n.as_str()))
+        {
+            return Err(Error::new(ConfigErr::UnknownCollector(name.clone())));
+        }
+
+        let mut collectors = Vec::new();
+        for mut collector in builtins {
+            let name = collector.name();
+            let config = c.collectors.get(name).cloned().unwrap_or_default();
+
+            collector
+                .configure(&config.options)
+                .with_context(|| format!("Invalid configuration of `collectors.{}`", name))?;
+            collectors.push(ScheduledCollector::new(collector, config.settings(name)?));
+        }
+
+        Ok(UpdateSettings {
+            interval: schedule_duration("collectors.update_interval", &c.update_interval)?,
+            collectors,
+        })
+    }
+
+    pub fn history_tiers(&self) -> Result<Option<Vec<TierSpec>>> {
+        if !self.history.enabled {
+            return Ok(None);
+        }
+
+        Ok(Some(history::parse_tiers(&self.history.tiers)?))
+    }
+
+    pub fn record_path(&self) -> Option<&Path> {
+        self.record.path.as_deref()
+    }
+
+    pub fn replay_config(&self) -> Option<ReplayConfig> {
+        Some(ReplayConfig {
+            path: self.replay.path.clone()?,
+            speed: self.replay.speed,
+        })
+    }
+
+    pub fn demo_config(&self) -> Option<DemoConfig> {
+        self.demo.enabled.then_some(DemoConfig {
+            seed: self.demo.seed,
+        })
+    }
+
+    pub fn store_config(&self) -> Result<Option<StoreConfig>> {
+        let Some(path) = &self.store.path else {
+            return Ok(None);
+        };
+
+        Ok(Some(StoreConfig {
+            path: path.clone(),
+            commit_interval: field_duration_secs(
+                "store.commit_interval",
+                &self.store.commit_interval,
+            )?,
+            raw_retention: field_duration_secs("store.raw_retention", &self.store.raw_retention)?,
+            rollup_retention: field_duration_secs(
+                "store.rollup_retention",
+                &self.store.rollup_retention,
+            )?,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration(" 30s ").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("24h").unwrap(), Duration::from_secs(86400));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604800));
+    }
+
+    #[test]
+    fn rejects_malformed_durations() {
+        for duration in ["", "30", "s", "0s", "-1s", "1.5h", "10 s", "3w", "1sm"] {
+            let e = parse_duration(duration).unwrap_err();
+            assert!(
+                matches!(e.downcast_ref(), Some(ConfigErr::MalformedDuration(_))),
+                "{}",
+                duration
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_overflowing_durations() {
+        for duration in [
+            "400000000000000000d",
+            "18446744073709551615m",
+            "99999999999999999999s",
+        ] {
+            let e = parse_duration(duration).unwrap_err();
+            assert!(matches!(
+                e.downcast_ref(),
+                Some(ConfigErr::MalformedDuration(_))
+            ));
+        }
+        assert!(parse_duration("18446744073709551615s").is_ok());
+    }
+
+    #[test]
+    fn bounds_schedules() {
+        let mut config = Config::default();
+        config.collectors.update_interval = String::from("1d");
+        assert!(config.update_settings(source::host()).is_ok());
+
+        config.collectors.update_interval = String::from("18446744073709551615s");
+        assert!(config.update_settings(source::host()).is_err());
+
+        config.collectors.update_interval = String::from("1s");
+        config.collectors.collector_mut("cpu").timeout = String::from("25h");
+        assert!(config.update_settings(source::host()).is_err());
+    }
+
+    #[test]
+    fn whole_seconds() {
+        assert_eq!(parse_duration_secs("2m").unwrap(), 120);
+        assert!(parse_duration_secs("1500ms").is_err());
+        assert!(parse_duration_secs("400000000000000000d").is_err());
+    }
+}
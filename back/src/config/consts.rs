@@ -0,0 +1,14 @@
+use std::time::Duration;
+
+pub const DEFAULT_ADDRESS: &str = "0.0.0.0";
+pub const DEFAULT_PORT: u16 = 8080;
+pub const DEFAULT_SERVE_DIRECTORY: &str = "./front/dist";
+pub const DEFAULT_UPDATE_INTERVAL: &str = "1s";
+pub const DEFAULT_COLLECTOR_TIMEOUT: &str = "2s";
+// Longest update interval or collector timeout, which schedules are computed
+// with
+pub const MAX_SCHEDULE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub const DEFAULT_ACL_RULE: &str = "allow private";
+pub const PUBLIC_ACL_RULE: &str = "allow any";
+pub const DEFAULT_TRUSTED_HEADER: &str = "x-forwarded-for";
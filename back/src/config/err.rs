@@ -0,0 +1,44 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ConfigErr {
+    MalformedDuration(String),
+    InvalidValue { field: String, reason: String },
+    UnknownCollector(String),
+    NoListeners,
+    DuplicateListener(String),
+    RecordAndReplay,
+    DemoAndReplay,
+}
+
+impl std::error::Error for ConfigErr {}
+
+impl fmt::Display for ConfigErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigErr::MalformedDuration(d) => write!(
+                f,
+                "Malformed duration `{}`, expected a number followed by one of ms, s, m, h, d",
+                d
+            ),
+            ConfigErr::InvalidValue { field, reason } => {
+                write!(f, "Invalid value for `{}`: {}", field, reason)
+            }
+            ConfigErr::UnknownCollector(c) => {
+                write!(f, "There is no collector named `{}` (collectors.{})", c, c)
+            }
+            ConfigErr::NoListeners => {
+                write!(f, "At least one listener is needed in `web.listeners`")
+            }
+            ConfigErr::DuplicateListener(l) => {
+                write!(f, "Listener `{}` is configured more than once", l)
+            }
+            ConfigErr::RecordAndReplay => {
+                write!(f, "`record.path` and `replay.path` can't be set together")
+            }
+            ConfigErr::DemoAndReplay => {
+                write!(f, "`demo.enabled` and `replay.path` can't be set together")
+            }
+        }
+    }
+}
@@ -0,0 +1,833 @@
+mod consts;
+
+use crate::status::source::{FsStats, Source};
+
+use std::f64::consts::TAU;
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use anyhow::Result;
+use log::info;
+
+pub use self::consts::DEFAULT_SEED;
+use self::consts::{
+    AMBIENT_SWING, AMBIENT_TEMP, APT_IO_STALL, APT_RATE, BACKUP_DISK, BACKUP_IO_STALL, BACKUP_JOB,
+    BACKUP_PRUNE_ABOVE, BACKUP_PRUNE_TO, BACKUP_RATE, BASE_LOAD, BOOT_FREQUENCY_SHARES,
+    BOOT_STALLS, BURST_CHANCE, BURST_DURATION, BURST_INTERFACE, BURST_RATE, BUSY_SPLIT,
+    CACHED_WRITES, CORES, CPU_CONTENTION, CPU_DIR, CPU_PRESENT, CTXT_PER_LOAD, CTXT_PER_SECOND,
+    DAEMONS, DISKS, FREQUENCIES, GOVERNOR, GOVERNORS, HOSTNAME, HOST_PATH, IDLE_IO_STALL,
+    INTERFACES, JOBS, JOB_CHANCE, KERNEL_TASKS, LEAKING_DAEMON, LEAK_LIMIT, LEAK_RATE,
+    LOADAVG_PERIODS, LOAD_HEAT, LOAD_NOISE, LOAD_WAVES, LOG_RATE, MAX_BOOT_UPTIME, MAX_JOBS,
+    MEMORY_CONTENTION, MEM_BASE, MEM_BUFFERS, MEM_MIN_FREE, MEM_TOTAL, MIN_BOOT_UPTIME, NET_DIR,
+    PAGE_SIZE, PRESSURE_DIR, PRESSURE_PERIODS, PROC_DIR, PROC_LOADAVG, PROC_MEMINFO, PROC_MOUNTS,
+    PROC_STAT, PROC_UPTIME, PSEUDO_MOUNTS, ROOT_DISK, SINCE_BOOT_SHIFT, STOPPED_EPOCH,
+    TEMP_LIMIT_FLAGS, TEMP_NOISE, TEMP_PATH, THERMAL_INERTIA, THROTTLED_FREQUENCY, THROTTLED_PATH,
+    THROTTLE_TEMP, TICK, UNDER_VOLTAGE_CHANCE, UNDER_VOLTAGE_DURATION, UNDER_VOLTAGE_FLAGS,
+    UP_THRESHOLD, USER_HZ,
+};
+
+pub struct DemoConfig {
+    // The same seed always simulates the same machine
+    pub seed: u64,
+}
+
+// SplitMix64, giving the same numbers on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // In [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn range(&mut self, (min, max): (f64, f64)) -> f64 {
+        min + self.unit() * (max - min)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        self.unit() < probability
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[(self.next() % items.len() as u64) as usize]
+    }
+}
+
+// Times in jiffies, in the order of /proc/stat: user, nice, system, idle,
+// iowait, irq, softirq
+struct Core {
+    phase: f64,
+    times: [f64; 7],
+    // Index in FREQUENCIES
+    frequency: usize,
+    // Jiffies spent at each frequency
+    time_in_state: [f64; 5],
+}
+
+// Pressure stall information of a resource, averages being in percents and
+// totals in microseconds
+#[derive(Default)]
+struct Stalls {
+    some: [f64; 3],
+    full: [f64; 3],
+    some_total: f64,
+    full_total: f64,
+}
+
+impl Stalls {
+    fn since_boot(uptime: f64, share: f64, full: f64) -> Self {
+        Stalls {
+            some_total: uptime * 1e6 * share,
+            full_total: uptime * 1e6 * share * full,
+            ..Default::default()
+        }
+    }
+
+    // Shares of the last `dt` seconds during which some and all tasks were
+    // stalled
+    fn step(&mut self, dt: f64, some: f64, full: f64) {
+        for (i, period) in PRESSURE_PERIODS.iter().enumerate() {
+            let decay = (-dt / period).exp();
+            self.some[i] = self.some[i] * decay + some * 100.0 * (1.0 - decay);
+            self.full[i] = self.full[i] * decay + full * 100.0 * (1.0 - decay);
+        }
+        self.some_total += some * dt * 1e6;
+        self.full_total += full * dt * 1e6;
+    }
+
+    fn file(&self) -> String {
+        let line = |kind: &str, avgs: &[f64; 3], total: f64| {
+            format!(
+                "{} avg10={:.2} avg60={:.2} avg300={:.2} total={}\n",
+                kind, avgs[0], avgs[1], avgs[2], total as u64
+            )
+        };
+
+        line("some", &self.some, self.some_total) + &line("full", &self.full, self.full_total)
+    }
+}
+
+struct Burst {
+    until: u64,
+    rx: f64,
+    tx: f64,
+}
+
+struct Interface {
+    name: &'static str,
+    // (download, upload), in bytes per second
+    base: (f64, f64),
+    rx: f64,
+    tx: f64,
+    burst: Option<Burst>,
+}
+
+struct Disk {
+    device: &'static str,
+    mountpoint: &'static str,
+    fs_type: &'static str,
+    total: u64,
+    used: f64,
+}
+
+struct Process {
+    pid: u64,
+    name: &'static str,
+    threads: u16,
+    // In pages
+    rss: f64,
+    // Share of a core
+    load: f64,
+    utime: f64,
+    stime: f64,
+    // In jiffies since boot
+    start_time: u64,
+    // Step at which jobs exit, daemons running forever
+    exits_at: Option<u64>,
+}
+
+/// A simulated Raspberry Pi, stepped every TICK. Everything random comes
+/// from the seeded generator, in the same order on every run
+struct Machine {
+    rng: Rng,
+    tick: u64,
+    // Unix timestamp of the start of the simulation, in milliseconds
+    epoch: u128,
+    // Uptime at the start of the simulation, in seconds
+    boot_uptime: f64,
+    cores: Vec<Core>,
+    // In kB
+    cached: f64,
+    interfaces: Vec<Interface>,
+    disks: Vec<Disk>,
+    processes: Vec<Process>,
+    next_pid: u64,
+    // In °C
+    temp: f64,
+    // Firmware throttling flags, the ones since boot included
+    throttled: u32,
+    under_voltage_until: Option<u64>,
+    // Over the last LOADAVG_PERIODS
+    loadavg: [f64; 3],
+    // CPU, memory and I/O
+    stalls: [Stalls; 3],
+    ctxt: f64,
+    forks: u64,
+    running: u32,
+    blocked: u32,
+}
+
+impl Machine {
+    fn new(seed: u64, epoch: u128) -> Self {
+        let mut rng = Rng(seed);
+        let boot_uptime = rng.range((MIN_BOOT_UPTIME, MAX_BOOT_UPTIME));
+        let jiffies = boot_uptime * USER_HZ;
+
+        let cores = (0..CORES)
+            .map(|_| {
+                let busy = jiffies * rng.range((0.1, 0.3));
+                let mut times = [0.0; 7];
+                for (i, share) in [0, 1, 2, 5, 6].into_iter().zip(BUSY_SPLIT) {
+                    times[i] = busy * share;
+                }
+                times[3] = (jiffies - busy) * 0.99;
+                times[4] = (jiffies - busy) * 0.01;
+
+                Core {
+                    phase: rng.range((0.0, TAU)),
+                    times,
+                    frequency: 0,
+                    time_in_state: BOOT_FREQUENCY_SHARES.map(|share| jiffies * share),
+                }
+            })
+            .collect();
+
+        let interfaces = INTERFACES
+            .iter()
+            .map(|&(name, rx, tx)| Interface {
+                name,
+                base: (rx, tx),
+                rx: rx * boot_uptime * rng.range((0.5, 2.0)),
+                tx: tx * boot_uptime * rng.range((0.5, 2.0)),
+                burst: None,
+            })
+            .collect();
+
+        let disks = DISKS
+            .iter()
+            .map(|&(device, mountpoint, fs_type, total, used)| Disk {
+                device,
+                mountpoint,
+                fs_type,
+                total,
+                used: total as f64 * used,
+            })
+            .collect();
+
+        let mut machine = Machine {
+            rng,
+            tick: 0,
+            epoch,
+            boot_uptime,
+            cores,
+            cached: MEM_TOTAL * 0.25,
+            interfaces,
+            disks,
+            processes: Vec::new(),
+            next_pid: 1,
+            temp: AMBIENT_TEMP + LOAD_HEAT * BASE_LOAD,
+            throttled: 0,
+            under_voltage_until: None,
+            loadavg: [BASE_LOAD * CORES as f64; 3],
+            stalls: [
+                Stalls::since_boot(boot_uptime, BOOT_STALLS[0], 0.0),
+                Stalls::since_boot(boot_uptime, BOOT_STALLS[1], 0.5),
+                Stalls::since_boot(boot_uptime, BOOT_STALLS[2], 0.7),
+            ],
+            ctxt: jiffies * CTXT_PER_SECOND / USER_HZ,
+            forks: 0,
+            running: 1,
+            blocked: 0,
+        };
+
+        // Daemons started with the system, the first one being init
+        for (name, threads, rss, load) in DAEMONS {
+            let start_time = match machine.next_pid {
+                1 => 5.0,
+                _ => machine.rng.range((500.0, 3000.0)),
+            };
+            machine.spawn(name, threads, rss, load, start_time as u64, None);
+
+            let process = machine.processes.last_mut().unwrap();
+            let run_time = jiffies - start_time;
+            process.utime = run_time * load * 0.85;
+            process.stime = run_time * load * 0.15;
+        }
+        machine.next_pid = machine.rng.range((2000.0, 30000.0)) as u64;
+        machine.forks = machine.next_pid + machine.rng.next() % 100000;
+
+        machine
+    }
+
+    // Seconds since the start of the simulation
+    fn elapsed(&self) -> f64 {
+        self.tick as f64 * TICK.as_secs_f64()
+    }
+
+    fn uptime(&self) -> f64 {
+        self.boot_uptime + self.elapsed()
+    }
+
+    fn now_millis(&self) -> u128 {
+        self.epoch + self.tick as u128 * TICK.as_millis()
+    }
+
+    fn is_running(&self, name: &str) -> bool {
+        self.processes.iter().any(|p| p.name == name)
+    }
+
+    fn spawn(
+        &mut self,
+        name: &'static str,
+        threads: u16,
+        rss: f64,
+        load: f64,
+        start_time: u64,
+        exits_at: Option<u64>,
+    ) {
+        self.processes.push(Process {
+            pid: self.next_pid,
+            name,
+            threads,
+            rss,
+            load,
+            utime: 0.0,
+            stime: 0.0,
+            start_time,
+            exits_at,
+        });
+
+        // Short-lived processes which are not simulated take pids too
+        let skipped = 1 + self.rng.next() % 40;
+        self.next_pid += skipped;
+        self.forks += skipped;
+    }
+
+    fn start_job(&mut self, start_time: u64) {
+        let (name, threads, rss, load, lifetime) = self.rng.pick(&JOBS);
+
+        if name == BACKUP_JOB {
+            let backup = &mut self.disks[BACKUP_DISK];
+            if backup.used > backup.total as f64 * BACKUP_PRUNE_ABOVE {
+                backup.used = backup.total as f64 * BACKUP_PRUNE_TO;
+            }
+        }
+
+        let lifetime = self.rng.range(lifetime) / TICK.as_secs_f64();
+        let rss = rss * self.rng.range((0.8, 1.2));
+        let exits_at = Some(self.tick + lifetime as u64);
+        self.spawn(name, threads, rss, load, start_time, exits_at);
+    }
+
+    fn step(&mut self) {
+        let dt = TICK.as_secs_f64();
+        self.tick += 1;
+        let t = self.elapsed();
+        let now = (self.uptime() * USER_HZ) as u64;
+
+        // Processes starting and exiting
+        let tick = self.tick;
+        self.processes
+            .retain(|p| p.exits_at.is_none_or(|e| e > tick));
+        let jobs = self
+            .processes
+            .iter()
+            .filter(|p| p.exits_at.is_some())
+            .count();
+        if jobs < MAX_JOBS && self.rng.chance(JOB_CHANCE * dt) {
+            self.start_job(now);
+        }
+
+        // Memory pressure, from a daemon leaking until it is restarted
+        if let Some(i) = self.processes.iter().position(|p| p.name == LEAKING_DAEMON) {
+            self.processes[i].rss += LEAK_RATE * dt * self.rng.range((0.5, 1.5));
+            if self.processes[i].rss > LEAK_LIMIT {
+                let leaking = self.processes.remove(i);
+                let (_, _, rss, _) = DAEMONS.iter().find(|d| d.0 == LEAKING_DAEMON).unwrap();
+                self.spawn(leaking.name, leaking.threads, *rss, leaking.load, now, None);
+            }
+        }
+
+        let mut processes_load = 0.0;
+        let mut running = 1;
+        for p in self.processes.iter_mut() {
+            let load = p.load * self.rng.range((0.8, 1.2));
+            p.utime += load * 0.85 * USER_HZ * dt;
+            p.stime += load * 0.15 * USER_HZ * dt;
+            processes_load += load;
+            if load > 0.5 {
+                running += 1;
+            }
+        }
+        self.running = running;
+
+        // CPU load waves, with the processes on top
+        let backup = self.is_running(BACKUP_JOB);
+        let highest = match self.throttled & 0xf {
+            0 => FREQUENCIES.len() - 1,
+            _ => FREQUENCIES.partition_point(|f| *f <= THROTTLED_FREQUENCY) - 1,
+        };
+        let mut total_load = 0.0;
+        for core in self.cores.iter_mut() {
+            let mut load = BASE_LOAD + processes_load / CORES as f64;
+            for (period, amplitude) in LOAD_WAVES {
+                load += amplitude * (TAU * t / period + core.phase).sin();
+            }
+            load += self.rng.range((-LOAD_NOISE, LOAD_NOISE));
+            let load = load.clamp(0.01, 1.0);
+            total_load += load;
+
+            // The governor scales the frequency with the load
+            let wanted = FREQUENCIES[0] as f64
+                + (load / UP_THRESHOLD).min(1.0)
+                    * (FREQUENCIES[FREQUENCIES.len() - 1] - FREQUENCIES[0]) as f64;
+            core.frequency = FREQUENCIES
+                .partition_point(|f| (*f as f64) < wanted)
+                .min(highest);
+            core.time_in_state[core.frequency] += USER_HZ * dt;
+
+            let busy = load * USER_HZ * dt;
+            let idle = USER_HZ * dt - busy;
+            for (i, share) in [0, 1, 2, 5, 6].into_iter().zip(BUSY_SPLIT) {
+                core.times[i] += busy * share;
+            }
+            // Copying to the backup disk waits on it
+            let iowait = if backup { 0.1 } else { 0.005 };
+            core.times[3] += idle * (1.0 - iowait);
+            core.times[4] += idle * iowait;
+        }
+        let load = total_load / CORES as f64;
+
+        self.ctxt += (CTXT_PER_SECOND + CTXT_PER_LOAD * load) * dt * self.rng.range((0.9, 1.1));
+        self.blocked = (backup && self.rng.chance(0.5)) as u32;
+
+        // Filling disks
+        let mut written = LOG_RATE * dt;
+        if self.is_running("apt-get") {
+            written += APT_RATE * dt;
+        }
+        let root = &mut self.disks[ROOT_DISK];
+        root.used = (root.used + written).min(root.total as f64);
+        if backup {
+            let disk = &mut self.disks[BACKUP_DISK];
+            disk.used = (disk.used + BACKUP_RATE * dt).min(disk.total as f64);
+            written += BACKUP_RATE * dt;
+        }
+
+        // The page cache grows with the writes, and is evicted when the
+        // processes need the memory
+        let anon = self.anon_memory();
+        self.cached = (self.cached + written / 1024.0 * CACHED_WRITES)
+            .min(MEM_TOTAL - anon - MEM_BUFFERS - MEM_MIN_FREE)
+            .max(0.0);
+
+        // Traffic, with bursts on the main interface
+        for (i, interface) in self.interfaces.iter_mut().enumerate() {
+            let (rx, tx) = interface.base;
+            interface.rx += rx * dt * self.rng.range((0.5, 1.5));
+            interface.tx += tx * dt * self.rng.range((0.5, 1.5));
+            if i != BURST_INTERFACE {
+                continue;
+            }
+
+            if interface.burst.as_ref().is_some_and(|b| b.until <= tick) {
+                interface.burst = None;
+            }
+            if interface.burst.is_none() && self.rng.chance(BURST_CHANCE * dt) {
+                let duration = self.rng.range(BURST_DURATION) / dt;
+                let rate = self.rng.range(BURST_RATE);
+                let download = self.rng.chance(0.7);
+                interface.burst = Some(Burst {
+                    until: tick + duration as u64,
+                    rx: if download { rate } else { rate / 40.0 },
+                    tx: if download { rate / 40.0 } else { rate },
+                });
+            }
+            if let Some(burst) = &interface.burst {
+                interface.rx += burst.rx * dt;
+                interface.tx += burst.tx * dt;
+            }
+            // The backups are pulled from the network
+            if backup {
+                interface.rx += BACKUP_RATE * dt;
+            }
+        }
+
+        // Temperature following the load and the room's
+        let (period, amplitude) = AMBIENT_SWING;
+        let target = AMBIENT_TEMP + amplitude * (TAU * t / period).sin() + LOAD_HEAT * load;
+        self.temp += (target - self.temp) * dt / THERMAL_INERTIA;
+        self.temp += self.rng.range((-TEMP_NOISE, TEMP_NOISE));
+
+        // Load averages of the busy cores and the tasks waiting on the disks
+        let runnable = total_load + self.blocked as f64;
+        for (avg, period) in self.loadavg.iter_mut().zip(LOADAVG_PERIODS) {
+            let decay = (-dt / period).exp();
+            *avg = *avg * decay + runnable * (1.0 - decay);
+        }
+
+        // Tasks stall on the CPU when the cores are busy, on memory when the
+        // page cache has to be evicted, and on the disks while writing
+        let cpu = ((load - CPU_CONTENTION) / (1.0 - CPU_CONTENTION)).clamp(0.0, 1.0) * 0.5;
+        self.stalls[0].step(dt, cpu, 0.0);
+        let used = self.anon_memory() / MEM_TOTAL;
+        let memory = ((used - MEMORY_CONTENTION) / (1.0 - MEMORY_CONTENTION)).clamp(0.0, 1.0) * 0.4;
+        self.stalls[1].step(dt, memory, memory * 0.5);
+        let io = if backup {
+            BACKUP_IO_STALL
+        } else if self.is_running("apt-get") {
+            APT_IO_STALL
+        } else {
+            IDLE_IO_STALL
+        };
+        let io = io * self.rng.range((0.7, 1.3));
+        self.stalls[2].step(dt, io, io * 0.7);
+
+        // The firmware throttles on under-voltage and when too hot,
+        // remembering it until the next boot
+        if self.under_voltage_until.is_some_and(|u| u <= tick) {
+            self.under_voltage_until = None;
+        }
+        if self.under_voltage_until.is_none() && self.rng.chance(UNDER_VOLTAGE_CHANCE * dt) {
+            let duration = self.rng.range(UNDER_VOLTAGE_DURATION) / dt;
+            self.under_voltage_until = Some(tick + duration as u64);
+        }
+        let mut now = 0;
+        if self.under_voltage_until.is_some() {
+            now |= UNDER_VOLTAGE_FLAGS;
+        }
+        if self.temp > THROTTLE_TEMP {
+            now |= TEMP_LIMIT_FLAGS;
+        }
+        self.throttled = (self.throttled & !0xf) | now | (now << SINCE_BOOT_SHIFT);
+    }
+
+    // Memory which can't be evicted, in kB
+    fn anon_memory(&self) -> f64 {
+        let processes: f64 = self.processes.iter().map(|p| p.rss).sum();
+        MEM_BASE + processes * (PAGE_SIZE / 1024) as f64
+    }
+
+    fn stat(&self) -> String {
+        let mut total = [0.0; 7];
+        let mut cores = String::new();
+        for (i, core) in self.cores.iter().enumerate() {
+            for (t, c) in total.iter_mut().zip(core.times) {
+                *t += c;
+            }
+            cores.push_str(&format!("cpu{} {} 0 0 0\n", i, times_line(&core.times)));
+        }
+
+        format!(
+            "cpu  {} 0 0 0\n{}intr {}\nctxt {}\nbtime {}\nprocesses {}\nprocs_running {}\nprocs_blocked {}\n",
+            times_line(&total),
+            cores,
+            (self.ctxt * 0.6) as u64,
+            self.ctxt as u64,
+            (self.epoch / 1000) as u64 - self.boot_uptime as u64,
+            self.forks,
+            self.running,
+            self.blocked,
+        )
+    }
+
+    fn meminfo(&self) -> String {
+        let anon = self.anon_memory();
+        let free = MEM_TOTAL - anon - self.cached - MEM_BUFFERS;
+        let available = free + self.cached * 0.9 + MEM_BUFFERS;
+
+        [
+            ("MemTotal:", MEM_TOTAL),
+            ("MemFree:", free),
+            ("MemAvailable:", available),
+            ("Buffers:", MEM_BUFFERS),
+            ("Cached:", self.cached),
+            ("SwapCached:", 0.0),
+            ("SwapTotal:", 102396.0),
+            ("SwapFree:", 102396.0),
+            ("AnonPages:", anon - MEM_BASE * 0.5),
+        ]
+        .iter()
+        .map(|(label, kb)| format!("{:<16}{:>8} kB\n", label, *kb as u64))
+        .collect()
+    }
+
+    fn uptime_file(&self) -> String {
+        let idle: f64 = self.cores.iter().map(|c| c.times[3]).sum();
+        format!("{:.2} {:.2}\n", self.uptime(), idle / USER_HZ)
+    }
+
+    fn loadavg_file(&self) -> String {
+        let threads: u64 = self.processes.iter().map(|p| p.threads as u64).sum();
+        format!(
+            "{:.2} {:.2} {:.2} {}/{} {}\n",
+            self.loadavg[0],
+            self.loadavg[1],
+            self.loadavg[2],
+            self.running,
+            KERNEL_TASKS + threads,
+            self.next_pid - 1,
+        )
+    }
+
+    fn mounts(&self) -> String {
+        let mut mounts = self
+            .disks
+            .iter()
+            .map(|d| {
+                format!(
+                    "{} {} {} rw,noatime 0 0\n",
+                    d.device, d.mountpoint, d.fs_type
+                )
+            })
+            .collect::<String>();
+        mounts.push_str(PSEUDO_MOUNTS);
+        mounts
+    }
+
+    fn process_stat(&self, process: &Process) -> String {
+        let state = if process.load > 0.5 { "R" } else { "S" };
+        format!(
+            "{} ({}) {} 1 {} {} 0 -1 4194560 1520 0 12 0 {} {} 0 0 20 0 {} 0 {} {} {} 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 {} 0 0 0 0 0\n",
+            process.pid,
+            process.name,
+            state,
+            process.pid,
+            process.pid,
+            process.utime as u64,
+            process.stime as u64,
+            process.threads,
+            process.start_time,
+            process.rss as u64 * PAGE_SIZE * 3,
+            process.rss as u64,
+            process.pid % CORES as u64,
+        )
+    }
+
+    fn read(&self, path: &str) -> Option<String> {
+        match path {
+            PROC_STAT => return Some(self.stat()),
+            PROC_MEMINFO => return Some(self.meminfo()),
+            PROC_UPTIME => return Some(self.uptime_file()),
+            PROC_LOADAVG => return Some(self.loadavg_file()),
+            PROC_MOUNTS => return Some(self.mounts()),
+            HOST_PATH => return Some(format!("{}\n", HOSTNAME)),
+            CPU_PRESENT => return Some(format!("0-{}\n", CORES - 1)),
+            TEMP_PATH => return Some(format!("{}\n", (self.temp * 1000.0) as i64)),
+            THROTTLED_PATH => return Some(format!("{:x}\n", self.throttled)),
+            _ => {}
+        }
+
+        if let Some(resource) = path.strip_prefix(PRESSURE_DIR) {
+            let i = ["/cpu", "/memory", "/io"]
+                .iter()
+                .position(|r| *r == resource)?;
+            return Some(self.stalls[i].file());
+        }
+
+        if let Some(rest) = path.strip_prefix(CPU_DIR) {
+            return self.read_cpu(rest.trim_start_matches('/'));
+        }
+
+        if let Some(rest) = path.strip_prefix(NET_DIR) {
+            let (name, counter) = rest.trim_start_matches('/').split_once('/')?;
+            let interface = self.interfaces.iter().find(|i| i.name == name)?;
+            return match counter {
+                "statistics/rx_bytes" => Some(format!("{}\n", interface.rx as u64)),
+                "statistics/tx_bytes" => Some(format!("{}\n", interface.tx as u64)),
+                "operstate" => Some(String::from("up\n")),
+                _ => None,
+            };
+        }
+
+        let (pid, file) = path
+            .strip_prefix(PROC_DIR)?
+            .strip_prefix('/')?
+            .split_once('/')?;
+        let process = self.processes.iter().find(|p| p.pid.to_string() == pid)?;
+        match file {
+            "stat" => Some(self.process_stat(process)),
+            _ => None,
+        }
+    }
+
+    // Files under CPU_DIR
+    fn read_cpu(&self, path: &str) -> Option<String> {
+        let (core, file) = path.strip_prefix("cpu")?.split_once('/')?;
+        let core = self.cores.get(core.parse::<usize>().ok()?)?;
+        match file {
+            "cpufreq/scaling_cur_freq" => Some(format!("{}\n", FREQUENCIES[core.frequency])),
+            "cpufreq/scaling_min_freq" => Some(format!("{}\n", FREQUENCIES[0])),
+            "cpufreq/scaling_max_freq" => Some(format!("{}\n", FREQUENCIES[FREQUENCIES.len() - 1])),
+            "cpufreq/scaling_governor" => Some(format!("{}\n", GOVERNOR)),
+            "cpufreq/scaling_available_governors" => Some(format!("{} \n", GOVERNORS)),
+            "cpufreq/stats/time_in_state" => Some(
+                FREQUENCIES
+                    .iter()
+                    .zip(core.time_in_state)
+                    .map(|(f, t)| format!("{} {}\n", f, t as u64))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    fn read_dir(&self, path: &str) -> Option<Vec<String>> {
+        match path {
+            PROC_DIR => {
+                let mut entries = ["loadavg", "meminfo", "mounts", "pressure", "stat", "uptime"]
+                    .map(String::from)
+                    .to_vec();
+                entries.extend(self.processes.iter().map(|p| p.pid.to_string()));
+                Some(entries)
+            }
+            NET_DIR => Some(self.interfaces.iter().map(|i| i.name.to_string()).collect()),
+            CPU_DIR => {
+                let mut entries = (0..CORES).map(|i| format!("cpu{}", i)).collect::<Vec<_>>();
+                entries.push(String::from("present"));
+                Some(entries)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn times_line(times: &[f64; 7]) -> String {
+    times
+        .iter()
+        .map(|t| (*t as u64).to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn not_simulated(path: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} is not simulated by the demo", path),
+    )
+}
+
+struct DemoState {
+    machine: Machine,
+    // Steps made by `advance`, ahead of the clock
+    skipped: u64,
+}
+
+/// Source serving a simulated machine instead of the host, so that every
+/// collector, and so every endpoint, works anywhere with plausible data
+pub struct DemoSource {
+    state: Mutex<DemoState>,
+    // None when the simulation only moves with `advance`
+    started: Option<Instant>,
+}
+
+impl DemoSource {
+    pub fn new(config: DemoConfig) -> Self {
+        info!("Serving simulated data (seed {})", config.seed);
+        let epoch = UNIX_EPOCH.elapsed().unwrap_or_default().as_millis();
+
+        Self::with_clock(config, epoch, Some(Instant::now()))
+    }
+
+    /// A demo whose time only moves with `advance`, e.g. for tests
+    pub fn stopped(config: DemoConfig) -> Self {
+        Self::with_clock(config, STOPPED_EPOCH, None)
+    }
+
+    fn with_clock(config: DemoConfig, epoch: u128, started: Option<Instant>) -> Self {
+        DemoSource {
+            state: Mutex::new(DemoState {
+                machine: Machine::new(config.seed, epoch),
+                skipped: 0,
+            }),
+            started,
+        }
+    }
+
+    /// Moves the simulation `duration` ahead at once
+    pub fn advance(&self, duration: Duration) {
+        let steps = (duration.as_millis() / TICK.as_millis()) as u64;
+
+        let mut state = self.state.lock().unwrap();
+        state.skipped += steps;
+        for _ in 0..steps {
+            state.machine.step();
+        }
+    }
+
+    // Steps the simulation up to the clock
+    fn machine(&self) -> MutexGuard<'_, DemoState> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(started) = self.started {
+            let due = (started.elapsed().as_millis() / TICK.as_millis()) as u64 + state.skipped;
+            while state.machine.tick < due {
+                state.machine.step();
+            }
+        }
+
+        state
+    }
+}
+
+impl Source for DemoSource {
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        self.machine()
+            .machine
+            .read(path)
+            .ok_or_else(|| not_simulated(path))
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.machine()
+            .machine
+            .read_dir(path)
+            .ok_or_else(|| not_simulated(path))
+    }
+
+    fn statvfs(&self, mountpoint: &str) -> io::Result<FsStats> {
+        let state = self.machine();
+        let disk = state
+            .machine
+            .disks
+            .iter()
+            .find(|d| d.mountpoint == mountpoint)
+            .ok_or_else(|| not_simulated(mountpoint))?;
+
+        Ok(FsStats {
+            total: disk.total,
+            available: disk.total - disk.used as u64,
+        })
+    }
+
+    fn page_size(&self) -> Result<u64> {
+        Ok(PAGE_SIZE)
+    }
+
+    fn now_millis(&self) -> u128 {
+        self.machine().machine.now_millis()
+    }
+
+    fn monotonic(&self) -> Duration {
+        Duration::from_millis(self.machine().machine.tick * TICK.as_millis() as u64)
+    }
+}
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+pub const DEFAULT_SEED: u64 = 1;
+
+// Time between two steps of the simulation
+pub const TICK: Duration = Duration::from_millis(100);
+// Jiffies per second in /proc
+pub const USER_HZ: f64 = 100.0;
+pub const PAGE_SIZE: u64 = 4096;
+
+pub const HOSTNAME: &str = "pi-status-demo";
+pub const CORES: usize = 4;
+// Uptime when the demo starts, in seconds
+pub const MIN_BOOT_UPTIME: f64 = 3600.0;
+pub const MAX_BOOT_UPTIME: f64 = 20.0 * 86400.0;
+
+pub const PROC_STAT: &str = "/proc/stat";
+pub const PROC_MEMINFO: &str = "/proc/meminfo";
+pub const PROC_UPTIME: &str = "/proc/uptime";
+pub const PROC_LOADAVG: &str = "/proc/loadavg";
+pub const PRESSURE_DIR: &str = "/proc/pressure";
+pub const PROC_MOUNTS: &str = "/proc/mounts";
+pub const PROC_DIR: &str = "/proc";
+pub const HOST_PATH: &str = "/etc/hostname";
+pub const NET_DIR: &str = "/sys/class/net";
+pub const TEMP_PATH: &str = "/sys/class/thermal/thermal_zone0/temp";
+pub const CPU_DIR: &str = "/sys/devices/system/cpu";
+pub const CPU_PRESENT: &str = "/sys/devices/system/cpu/present";
+pub const THROTTLED_PATH: &str = "/sys/devices/platform/soc/soc:firmware/get_throttled";
+
+// Every core follows a slow and a fast wave: (period in seconds, amplitude)
+pub const LOAD_WAVES: [(f64, f64); 2] = [(180.0, 0.25), (23.0, 0.1)];
+pub const BASE_LOAD: f64 = 0.2;
+pub const LOAD_NOISE: f64 = 0.05;
+// Share of the busy time spent in (user, nice, system, irq, softirq)
+pub const BUSY_SPLIT: [f64; 5] = [0.72, 0.03, 0.21, 0.01, 0.03];
+
+// In kB
+pub const MEM_TOTAL: f64 = 3884852.0;
+// Used by the kernel and everything which is not a simulated process
+pub const MEM_BASE: f64 = 420000.0;
+pub const MEM_BUFFERS: f64 = 81224.0;
+// Kept free by the kernel, evicting the page cache when needed
+pub const MEM_MIN_FREE: f64 = 65536.0;
+// Share of the written data staying in the page cache
+pub const CACHED_WRITES: f64 = 0.5;
+
+// (name, threads, resident pages, share of a core)
+pub const DAEMONS: [(&str, u16, f64, f64); 7] = [
+    ("systemd", 1, 2950.0, 0.002),
+    ("sshd", 1, 1820.0, 0.0),
+    ("dockerd", 14, 18200.0, 0.01),
+    ("nginx", 2, 2210.0, 0.004),
+    ("tmux: server", 1, 980.0, 0.001),
+    ("pi-status", 9, 2410.0, 0.015),
+    ("node-red", 11, 24000.0, 0.03),
+];
+// node-red leaks memory until it is restarted, in pages per second
+pub const LEAKING_DAEMON: &str = "node-red";
+pub const LEAK_RATE: f64 = 600.0;
+pub const LEAK_LIMIT: f64 = 420000.0;
+
+// (min, max), in seconds
+pub type Lifetime = (f64, f64);
+// (name, threads, resident pages, share of a core, lifetime)
+pub const JOBS: [(&str, u16, f64, f64, Lifetime); 6] = [
+    ("make", 1, 900.0, 0.05, (20.0, 90.0)),
+    ("cc1", 1, 38000.0, 0.95, (3.0, 25.0)),
+    ("python3", 2, 9000.0, 0.4, (5.0, 60.0)),
+    ("ffmpeg", 6, 52000.0, 0.9, (30.0, 180.0)),
+    ("apt-get", 1, 14000.0, 0.2, (15.0, 60.0)),
+    ("rsync", 1, 3100.0, 0.25, (60.0, 240.0)),
+];
+// Chance per second of a job starting
+pub const JOB_CHANCE: f64 = 0.04;
+pub const MAX_JOBS: usize = 6;
+// The job copying to the backup disk
+pub const BACKUP_JOB: &str = "rsync";
+// In bytes per second
+pub const BACKUP_RATE: f64 = 38e6;
+pub const APT_RATE: f64 = 4e6;
+pub const LOG_RATE: f64 = 2e3;
+// Old backups are pruned once the disk is that full
+pub const BACKUP_PRUNE_ABOVE: f64 = 0.9;
+pub const BACKUP_PRUNE_TO: f64 = 0.55;
+
+// (device, mount point, type, size in bytes, used share)
+pub const DISKS: [(&str, &str, &str, u64, f64); 3] = [
+    ("/dev/mmcblk0p2", "/", "ext4", 62109302784, 0.31),
+    ("/dev/mmcblk0p1", "/boot/firmware", "vfat", 535805952, 0.12),
+    ("/dev/sda1", "/mnt/backup", "ext4", 64023257088, 0.62),
+];
+pub const ROOT_DISK: usize = 0;
+pub const BACKUP_DISK: usize = 2;
+// Listed in /proc/mounts but excluded by default
+pub const PSEUDO_MOUNTS: &str = "proc /proc proc rw,relatime 0 0
+sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+devtmpfs /dev devtmpfs rw,relatime,mode=755 0 0
+tmpfs /run tmpfs rw,nosuid,nodev,mode=755 0 0
+";
+
+// (name, base download rate, base upload rate), in bytes per second
+pub const INTERFACES: [(&str, f64, f64); 3] = [
+    ("eth0", 24e3, 6e3),
+    ("lo", 2e3, 2e3),
+    ("wlan0", 300.0, 150.0),
+];
+// Interface the bursts and backups go through
+pub const BURST_INTERFACE: usize = 0;
+// Chance per second of a burst starting
+pub const BURST_CHANCE: f64 = 0.02;
+pub const BURST_DURATION: (f64, f64) = (3.0, 25.0);
+pub const BURST_RATE: (f64, f64) = (1e6, 11e6);
+
+// In °C
+pub const AMBIENT_TEMP: f64 = 38.0;
+// Daily-like swing of the room temperature, sped up to be seen in a demo
+pub const AMBIENT_SWING: (f64, f64) = (900.0, 3.0);
+// Added at full load
+pub const LOAD_HEAT: f64 = 32.0;
+// Time constant of the temperature following the load, in seconds
+pub const THERMAL_INERTIA: f64 = 25.0;
+// Largest random change per step
+pub const TEMP_NOISE: f64 = 0.05;
+
+// Frequencies of the cores, in kHz
+pub const FREQUENCIES: [u64; 5] = [600000, 750000, 1000000, 1500000, 1800000];
+// Share of the time spent at each frequency before the demo starts
+pub const BOOT_FREQUENCY_SHARES: [f64; 5] = [0.7, 0.05, 0.05, 0.05, 0.15];
+pub const GOVERNOR: &str = "ondemand";
+pub const GOVERNORS: &str = "conservative ondemand userspace powersave performance schedutil";
+// Load above which the governor picks the highest frequency
+pub const UP_THRESHOLD: f64 = 0.8;
+
+// Firmware throttling flags, see `vcgencmd get_throttled`
+pub const UNDER_VOLTAGE_FLAGS: u32 = 0x5;
+pub const TEMP_LIMIT_FLAGS: u32 = 0x6;
+pub const SINCE_BOOT_SHIFT: u32 = 16;
+// Highest frequency while throttled, in kHz
+pub const THROTTLED_FREQUENCY: u64 = 1000000;
+// In °C
+pub const THROTTLE_TEMP: f64 = 80.0;
+// A weak power supply sagging now and then, chance per second
+pub const UNDER_VOLTAGE_CHANCE: f64 = 0.002;
+pub const UNDER_VOLTAGE_DURATION: (f64, f64) = (2.0, 10.0);
+
+// Periods of the load averages, in seconds
+pub const LOADAVG_PERIODS: [f64; 3] = [60.0, 300.0, 900.0];
+// Tasks which are not simulated processes
+pub const KERNEL_TASKS: u64 = 96;
+// Periods of the pressure stall averages, in seconds
+pub const PRESSURE_PERIODS: [f64; 3] = [10.0, 60.0, 300.0];
+// Share of the time stalled before the demo starts: (cpu, memory, io)
+pub const BOOT_STALLS: [f64; 3] = [0.02, 0.001, 0.01];
+// Average core load above which tasks wait for the CPU
+pub const CPU_CONTENTION: f64 = 0.7;
+// Share of the memory in use above which tasks wait for the page cache to be
+// evicted
+pub const MEMORY_CONTENTION: f64 = 0.75;
+// Share of the time stalled on I/O while copying to the backup disk, and
+// while installing packages to the SD card
+pub const BACKUP_IO_STALL: f64 = 0.3;
+pub const APT_IO_STALL: f64 = 0.08;
+pub const IDLE_IO_STALL: f64 = 0.003;
+
+pub const CTXT_PER_SECOND: f64 = 900.0;
+// Added at full load of every core
+pub const CTXT_PER_LOAD: f64 = 12000.0;
+
+// Unix timestamp, in milliseconds, at which stopped demos start, so that
+// they are reproducible
+pub const STOPPED_EPOCH: u128 = 1700000000000;
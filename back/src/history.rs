@@ -0,0 +1,370 @@
+mod consts;
+pub mod err;
+
+use crate::config::parse_duration_secs;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::RwLock;
+
+use anyhow::{Error, Result};
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+pub use self::consts::{DEFAULT_QUERY_RANGE_SECS, DEFAULT_TIERS, MAX_QUERY_POINTS};
+use self::err::HistoryErr;
+
+lazy_static! {
+    pub static ref HISTORY: RwLock<Option<History>> = RwLock::new(None);
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct TierSpec {
+    pub resolution: u64,
+    pub window: u64,
+}
+
+impl TierSpec {
+    fn capacity(&self) -> usize {
+        (self.window / self.resolution).max(1) as usize
+    }
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub struct Point {
+    pub timestamp: u64,
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    start: u64,
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+}
+
+impl Bucket {
+    fn new(start: u64) -> Self {
+        Bucket {
+            start,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn add(&mut self, min: f64, max: f64, sum: f64, count: u64) {
+        self.min = self.min.min(min);
+        self.max = self.max.max(max);
+        self.sum += sum;
+        self.count += count;
+    }
+
+    fn point(&self) -> Point {
+        Point {
+            timestamp: self.start,
+            min: self.min,
+            avg: self.sum / self.count as f64,
+            max: self.max,
+        }
+    }
+}
+
+struct Tier {
+    spec: TierSpec,
+    buckets: VecDeque<Bucket>,
+}
+
+impl Tier {
+    fn new(spec: TierSpec) -> Self {
+        Tier {
+            spec,
+            buckets: VecDeque::with_capacity(spec.capacity()),
+        }
+    }
+
+    fn record(&mut self, timestamp: u64, value: f64) {
+        let start = timestamp - timestamp % self.spec.resolution;
+
+        match self.buckets.back_mut() {
+            Some(b) if b.start == start => b.add(value, value, value, 1),
+            // Clock went backwards, drop the sample instead of breaking the ordering
+            Some(b) if b.start > start => (),
+            _ => {
+                if self.buckets.len() >= self.spec.capacity() {
+                    self.buckets.pop_front();
+                }
+
+                let mut bucket = Bucket::new(start);
+                bucket.add(value, value, value, 1);
+                self.buckets.push_back(bucket);
+            }
+        }
+    }
+
+    fn oldest(&self) -> Option<u64> {
+        self.buckets.front().map(|b| b.start)
+    }
+
+    // A tier that has not wrapped around yet holds everything ever recorded
+    fn covers(&self, timestamp: u64) -> bool {
+        self.buckets.len() < self.spec.capacity() || self.oldest().is_some_and(|o| o <= timestamp)
+    }
+}
+
+struct Series {
+    tiers: Vec<Tier>,
+    last_update: u64,
+}
+
+pub struct History {
+    specs: Vec<TierSpec>,
+    series: HashMap<String, Series>,
+}
+
+/// Parses a comma separated list of `<resolution>:<window>` tiers, e.g.
+/// `1s:15m,1m:24h`
+pub fn parse_tiers(tiers: &str) -> Result<Vec<TierSpec>> {
+    let mut specs = Vec::new();
+
+    for tier in tiers.split(',').filter(|t| !t.trim().is_empty()) {
+        let Some((resolution, window)) = tier.split_once(':') else {
+            return Err(Error::new(HistoryErr::MalformedTier(tier.to_string())));
+        };
+
+        let spec = TierSpec {
+            resolution: parse_duration_secs(resolution)?,
+            window: parse_duration_secs(window)?,
+        };
+        if spec.window < spec.resolution {
+            return Err(Error::new(HistoryErr::MalformedTier(tier.to_string())));
+        }
+
+        specs.push(spec);
+    }
+
+    if specs.is_empty() {
+        return Err(Error::new(HistoryErr::NoTiers));
+    }
+
+    specs.sort_by_key(|s| s.resolution);
+    Ok(specs)
+}
+
+impl History {
+    pub fn new(specs: Vec<TierSpec>) -> Self {
+        History {
+            specs,
+            series: HashMap::new(),
+        }
+    }
+
+    pub fn tiers(&self) -> &[TierSpec] {
+        &self.specs
+    }
+
+    fn max_window(&self) -> u64 {
+        self.specs.iter().map(|s| s.window).max().unwrap_or(0)
+    }
+
+    pub fn record(&mut self, timestamp: u64, samples: &[(String, f64)]) {
+        for (name, value) in samples {
+            if !value.is_finite() {
+                continue;
+            }
+
+            let series = self.series.entry(name.clone()).or_insert_with(|| Series {
+                tiers: self.specs.iter().map(|s| Tier::new(*s)).collect(),
+                last_update: timestamp,
+            });
+
+            series.last_update = timestamp;
+            for tier in &mut series.tiers {
+                tier.record(timestamp, *value);
+            }
+        }
+
+        // Forget series which disappeared (e.g. an interface that went down)
+        // once they fall outside of every window
+        let max_window = self.max_window();
+        self.series
+            .retain(|_, s| timestamp.saturating_sub(s.last_update) <= max_window);
+    }
+
+    pub fn metrics(&self) -> Vec<String> {
+        let mut metrics = self.series.keys().cloned().collect::<Vec<String>>();
+        metrics.sort();
+        metrics
+    }
+
+    /// Oldest timestamp for which in-memory data is available for the metric
+    pub fn oldest(&self, metric: &str) -> Option<u64> {
+        self.series
+            .get(metric)?
+            .tiers
+            .iter()
+            .filter_map(|t| t.oldest())
+            .min()
+    }
+
+    /// Returns the points of the metric in `[from, to]`, aggregated with the
+    /// given step. The finest tier covering `from` is used as source, and the
+    /// step is never finer than the resolution of that tier
+    pub fn query(&self, metric: &str, from: u64, to: u64, step: Option<u64>) -> Result<Vec<Point>> {
+        if from > to {
+            return Err(Error::new(HistoryErr::InvalidRange));
+        }
+
+        let Some(series) = self.series.get(metric) else {
+            return Err(Error::new(HistoryErr::UnknownMetric(metric.to_string())));
+        };
+
+        let requested_step = step.unwrap_or(0);
+        let covering = series
+            .tiers
+            .iter()
+            .filter(|t| t.covers(from))
+            .collect::<Vec<&Tier>>();
+        let tier = covering
+            .iter()
+            .rev()
+            .find(|t| t.spec.resolution <= requested_step)
+            .or(covering.first())
+            .copied()
+            .or(series.tiers.last());
+        let Some(tier) = tier else {
+            return Ok(Vec::new());
+        };
+
+        let step = requested_step
+            .max(tier.spec.resolution)
+            .max((to - from) / MAX_QUERY_POINTS);
+
+        let mut points: Vec<Bucket> = Vec::new();
+        for bucket in tier
+            .buckets
+            .iter()
+            .filter(|b| b.start >= from - from % tier.spec.resolution && b.start <= to)
+        {
+            let start = bucket.start - bucket.start % step;
+            match points.last_mut() {
+                Some(p) if p.start == start => {
+                    p.add(bucket.min, bucket.max, bucket.sum, bucket.count)
+                }
+                _ => {
+                    let mut p = Bucket::new(start);
+                    p.add(bucket.min, bucket.max, bucket.sum, bucket.count);
+                    points.push(p);
+                }
+            }
+        }
+
+        Ok(points.iter().map(|b| b.point()).collect())
+    }
+
+    /// Recent history of every metric at the finest resolution, used to fill
+    /// the charts of newly connected clients
+    pub fn backfill(&self, now: u64, seconds: u64) -> HashMap<String, Vec<Point>> {
+        let from = now.saturating_sub(seconds);
+
+        self.series
+            .keys()
+            .filter_map(|m| {
+                let points = self.query(m, from, now, None).ok()?;
+                Some((m.clone(), points))
+            })
+            .collect()
+    }
+}
+
+pub fn is_enabled() -> bool {
+    HISTORY.read().unwrap().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> History {
+        History::new(parse_tiers("1s:1m,10s:10m").unwrap())
+    }
+
+    fn record(history: &mut History, from: u64, to: u64) {
+        for t in from..=to {
+            history.record(t, &[(String::from("temp"), t as f64)]);
+        }
+    }
+
+    #[test]
+    fn tiers_are_sorted_by_resolution() {
+        let tiers = parse_tiers("1m:24h, 1s:15m").unwrap();
+        assert_eq!(
+            tiers
+                .iter()
+                .map(|t| (t.resolution, t.window))
+                .collect::<Vec<_>>(),
+            [(1, 900), (60, 86400)]
+        );
+        assert!(parse_tiers("1m:1s").is_err());
+        assert!(parse_tiers("1s").is_err());
+        assert!(parse_tiers("").is_err());
+    }
+
+    #[test]
+    fn coarser_tiers_roll_samples_up() {
+        let mut history = history();
+        record(&mut history, 1000, 1019);
+
+        let points = history.query("temp", 1000, 1019, Some(10)).unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].timestamp, 1000);
+        assert_eq!((points[0].min, points[0].max), (1000.0, 1009.0));
+        assert_eq!(points[0].avg, 1004.5);
+        assert_eq!(points[1].timestamp, 1010);
+    }
+
+    #[test]
+    fn ranges_use_the_finest_covering_tier() {
+        let mut history = history();
+        record(&mut history, 1000, 1299);
+
+        // The last minute is kept every second
+        let recent = history.query("temp", 1250, 1259, None).unwrap();
+        assert_eq!(recent.len(), 10);
+        assert_eq!(recent[0].timestamp, 1250);
+        assert_eq!(recent[9].avg, 1259.0);
+
+        // Older samples were only kept by the 10 seconds tier
+        let older = history.query("temp", 1000, 1059, None).unwrap();
+        assert_eq!(
+            older.iter().map(|p| p.timestamp).collect::<Vec<u64>>(),
+            [1000, 1010, 1020, 1030, 1040, 1050]
+        );
+        assert_eq!(history.oldest("temp"), Some(1000));
+    }
+
+    #[test]
+    fn invalid_queries_are_rejected() {
+        let mut history = history();
+        record(&mut history, 1000, 1001);
+
+        assert!(history.query("temp", 1001, 1000, None).is_err());
+        assert!(history.query("humidity", 1000, 1001, None).is_err());
+        assert_eq!(history.metrics(), ["temp"]);
+    }
+
+    #[test]
+    fn series_are_forgotten_once_out_of_every_window() {
+        let mut history = history();
+        record(&mut history, 1000, 1000);
+        history.record(1600, &[(String::from("load"), 0.5)]);
+        assert_eq!(history.metrics(), ["load", "temp"]);
+
+        history.record(1601, &[(String::from("load"), 0.5)]);
+        assert_eq!(history.metrics(), ["load"]);
+    }
+}
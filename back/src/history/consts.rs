@@ -0,0 +1,4 @@
+pub const DEFAULT_TIERS: &str = "1s:15m,1m:24h";
+
+pub const DEFAULT_QUERY_RANGE_SECS: u64 = 15 * 60;
+pub const MAX_QUERY_POINTS: u64 = 10_000;
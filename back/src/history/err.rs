@@ -0,0 +1,26 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum HistoryErr {
+    MalformedTier(String),
+    NoTiers,
+    UnknownMetric(String),
+    InvalidRange,
+}
+
+impl std::error::Error for HistoryErr {}
+
+impl fmt::Display for HistoryErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistoryErr::MalformedTier(t) => write!(
+                f,
+                "Malformed history tier `{}`, expected `<resolution>:<window>` (e.g. `1s:15m`)",
+                t
+            ),
+            HistoryErr::NoTiers => write!(f, "At least one history tier is needed"),
+            HistoryErr::UnknownMetric(m) => write!(f, "No history for metric `{}`", m),
+            HistoryErr::InvalidRange => write!(f, "The requested range is not valid"),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+pub mod acl;
+pub mod auth;
+pub mod config;
+pub mod demo;
+pub mod history;
+pub mod metrics;
+pub mod record;
+pub mod status;
+pub mod store;
+pub mod tls;
+pub mod web;
@@ -1,75 +1,369 @@
-pub mod status;
-pub mod web;
-
-use actix_ip_filter::IPFilter;
 use actix_web::{web as ActixWeb, App, HttpServer};
 use clap::Parser;
-use log::warn;
+use log::{error, warn};
+use pi_status::config::Config;
+use pi_status::status::source;
+use pi_status::{auth, demo, history, metrics, record, status, store, tls, web};
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
 use std::thread;
 
 #[derive(Parser)]
 #[command(about, long_about = None)]
 struct Args {
-    #[arg(short, long, default_value_t = 8080)]
-    port: u16,
-    #[arg(short, long, default_value_t = false)]
+    #[arg(short = 'C', long, env = "PST_CONFIG")]
+    config: Option<PathBuf>,
+    #[arg(long, default_value_t = false)]
+    print_config: bool,
+    #[arg(long, default_value_t = false)]
+    hash_password: bool,
+    #[arg(short, long, env = "PST_PORT")]
+    port: Option<u16>,
+    #[arg(short, long, env = "PST_FORCE_PUBLIC")]
     force_public: bool,
-    #[arg(short = 'c', long, default_value_t = false)]
+    #[arg(long, env = "PST_TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    #[arg(long, env = "PST_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    #[arg(short = 'c', long, env = "PST_SUPPRESS_CPU_ERRORS")]
     suppress_cpu_errors: bool,
-    #[arg(short = 'd', long, default_value_t = false)]
+    #[arg(short = 'd', long, env = "PST_SUPPRESS_DISK_ERRORS")]
     suppress_disk_errors: bool,
-    #[arg(short = 'o', long, default_value_t = false)]
+    #[arg(short = 'o', long, env = "PST_SUPPRESS_HOST_ERRORS")]
     suppress_host_errors: bool,
-    #[arg(short = 'n', long, default_value_t = false)]
+    #[arg(short = 'n', long, env = "PST_SUPPRESS_NET_ERRORS")]
     suppress_net_errors: bool,
-    #[arg(short = 'P', long, default_value_t = false)]
+    #[arg(short = 'P', long, env = "PST_SUPPRESS_PROC_ERRORS")]
     suppress_proc_errors: bool,
-    #[arg(short = 'r', long, default_value_t = false)]
+    #[arg(short = 'r', long, env = "PST_SUPPRESS_RAM_ERRORS")]
     suppress_ram_errors: bool,
-    #[arg(short = 't', long, default_value_t = false)]
+    #[arg(short = 't', long, env = "PST_SUPPRESS_TEMPERATURE_ERRORS")]
     suppress_temperature_errors: bool,
-    #[arg(short = 's', long, default_value_t = String::from("./front/dist"))]
-    serve_directory: String,
+    #[arg(short = 's', long, env = "PST_SERVE_DIRECTORY")]
+    serve_directory: Option<String>,
+    #[arg(short = 'u', long, env = "PST_UPDATE_INTERVAL")]
+    update_interval: Option<String>,
+    #[arg(short = 'm', long, env = "PST_METRICS_PROCESSES")]
+    metrics_processes: bool,
+    #[arg(short = 'H', long, env = "PST_HISTORY")]
+    history: bool,
+    #[arg(long, env = "PST_HISTORY_TIERS")]
+    history_tiers: Option<String>,
+    #[arg(short = 'S', long, env = "PST_STORE")]
+    store: Option<PathBuf>,
+    #[arg(long, env = "PST_STORE_COMMIT_INTERVAL")]
+    store_commit_interval: Option<String>,
+    #[arg(long, env = "PST_STORE_RAW_RETENTION")]
+    store_raw_retention: Option<String>,
+    #[arg(long, env = "PST_STORE_ROLLUP_RETENTION")]
+    store_rollup_retention: Option<String>,
+    #[arg(long, env = "PST_RECORD")]
+    record: Option<PathBuf>,
+    #[arg(long, env = "PST_REPLAY")]
+    replay: Option<PathBuf>,
+    #[arg(long, env = "PST_REPLAY_SPEED")]
+    replay_speed: Option<f64>,
+    #[arg(long, env = "PST_DEMO")]
+    demo: bool,
+    #[arg(long, env = "PST_DEMO_SEED")]
+    demo_seed: Option<u64>,
+}
+
+// Flags and environment variables take precedence over the configuration
+// file. Listener options only apply to the first listener
+fn apply_args(config: &mut Config, args: Args) {
+    if let Some(listener) = config.web.listeners.first_mut() {
+        if let Some(port) = args.port {
+            listener.port = port;
+        }
+        if args.force_public {
+            listener.public = true;
+        }
+        if let (Some(cert), Some(key)) = (args.tls_cert, args.tls_key) {
+            match &mut listener.tls {
+                Some(tls) => {
+                    tls.cert = cert;
+                    tls.key = key;
+                }
+                None => {
+                    listener.tls = Some(tls::TlsConfig {
+                        cert,
+                        key,
+                        client_ca: None,
+                        redirect_port: None,
+                    })
+                }
+            }
+        }
+    }
+    if let Some(serve_directory) = args.serve_directory {
+        config.web.serve_directory = serve_directory;
+    }
+
+    for (name, suppress) in [
+        ("cpu", args.suppress_cpu_errors),
+        ("disk", args.suppress_disk_errors),
+        ("host", args.suppress_host_errors),
+        ("net", args.suppress_net_errors),
+        ("proc", args.suppress_proc_errors),
+        ("ram", args.suppress_ram_errors),
+        ("temp", args.suppress_temperature_errors),
+    ] {
+        if suppress {
+            config.collectors.collector_mut(name).suppress_errors = true;
+        }
+    }
+    if let Some(update_interval) = args.update_interval {
+        config.collectors.update_interval = update_interval;
+    }
+
+    if args.metrics_processes {
+        config.metrics.processes = true;
+    }
+
+    if args.history {
+        config.history.enabled = true;
+    }
+    if let Some(tiers) = args.history_tiers {
+        config.history.tiers = tiers;
+    }
+
+    if let Some(path) = args.store {
+        config.store.path = Some(path);
+    }
+    if let Some(commit_interval) = args.store_commit_interval {
+        config.store.commit_interval = commit_interval;
+    }
+    if let Some(raw_retention) = args.store_raw_retention {
+        config.store.raw_retention = raw_retention;
+    }
+    if let Some(rollup_retention) = args.store_rollup_retention {
+        config.store.rollup_retention = rollup_retention;
+    }
+
+    if let Some(path) = args.record {
+        config.record.path = Some(path);
+    }
+    if let Some(path) = args.replay {
+        config.replay.path = Some(path);
+    }
+    if let Some(speed) = args.replay_speed {
+        config.replay.speed = speed;
+    }
+
+    if args.demo {
+        config.demo.enabled = true;
+    }
+    if let Some(seed) = args.demo_seed {
+        config.demo.seed = seed;
+    }
+}
+
+fn load_config(args: Args) -> anyhow::Result<Config> {
+    let mut config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+
+    apply_args(&mut config, args);
+    config.validate()?;
+
+    Ok(config)
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let args = Args::parse();
+    let print_config = args.print_config;
+
+    if args.hash_password {
+        let mut password = String::new();
+        if let Err(e) = io::stdin().read_line(&mut password) {
+            eprintln!("Could not read the password: {}", e);
+            process::exit(1);
+        }
+
+        match auth::hash_password(password.trim_end_matches(['\r', '\n'])) {
+            Ok(h) => println!("{}", h),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
     std::env::set_var("RUST_LOG", "info");
     env_logger::init();
 
-    let mut allowed_subnets = vec![
-        "127.0.0.1",
-        "10.*.*.*",
-        "172.{16,17,18,19,20,21,22,23,24,25,26,27,28,29,30,31}.*.*",
-        "192.168.1.*",
-    ];
-
-    if args.force_public {
-        allowed_subnets.push("*.*.*.*");
-        warn!("The monitored resources data is now accessible to anyone, including processes data");
-    }
-
-    thread::spawn(move || {
-        status::continous_update(status::ErrorSuppressions {
-            cpu: args.suppress_cpu_errors,
-            disk: args.suppress_disk_errors,
-            host: args.suppress_host_errors,
-            temp: args.suppress_temperature_errors,
-            net: args.suppress_net_errors,
-            proc: args.suppress_proc_errors,
-            ram: args.suppress_ram_errors,
-        })
+    let config = match load_config(args) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Invalid configuration: {:#}", e);
+            process::exit(1);
+        }
+    };
+
+    if print_config {
+        match config.to_toml() {
+            Ok(c) => print!("{}", c),
+            Err(e) => {
+                error!("Could not serialize the configuration: {}", e);
+                process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    let mut source = source::host();
+    if let Some(demo_config) = config.demo_config() {
+        source = Arc::new(demo::DemoSource::new(demo_config));
+    }
+    if let Some(replay_config) = config.replay_config() {
+        match record::Replay::open(replay_config) {
+            Ok(r) => source = Arc::new(r),
+            Err(e) => {
+                error!("Could not replay the recording: {:#}", e);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = config.record_path() {
+        match record::Recorder::start(path, source) {
+            Ok(r) => {
+                *record::RECORDER.write().unwrap() = Some(r.clone());
+                source = r;
+            }
+            Err(e) => {
+                error!("Could not start recording: {:#}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // Validation already checked that every section can be converted
+    let update_settings = config.update_settings(source).unwrap();
+    // Clients can't get fresher data than the most frequent collector's
+    let update_interval = update_settings.shortest_interval();
+
+    if let Some(tiers) = config.history_tiers().unwrap() {
+        *history::HISTORY.write().unwrap() = Some(history::History::new(tiers));
+    }
+
+    if let Some(store_config) = config.store_config().unwrap() {
+        match store::Store::start(store_config) {
+            Ok(s) => *store::STORE.write().unwrap() = Some(s),
+            Err(e) => {
+                error!("Could not open the metrics store: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    status::register_collectors(&update_settings);
+    thread::spawn(move || status::continous_update(update_settings));
+
+    let metrics_config = ActixWeb::Data::new(metrics::MetricsConfig {
+        processes: config.metrics.processes,
     });
+    let data_config = ActixWeb::Data::new(web::ws::DataConfig { update_interval });
+    let auth = ActixWeb::Data::new(config.auth().unwrap());
+
+    let mut servers = Vec::new();
+    for listener in &config.web.listeners {
+        if listener.public && !auth.is_enabled() {
+            warn!(
+                "The monitored resources data is now accessible to anyone on {}:{}, including processes data",
+                listener.address, listener.port
+            );
+        }
+
+        // Validation already checked that the rules can be parsed
+        let acl = Arc::new(listener.acl().unwrap());
+        let serve_directory = config.web.serve_directory.clone();
+        let metrics_config = metrics_config.clone();
+        let data_config = data_config.clone();
+        let auth = auth.clone();
+        let address = listener.address.as_str();
+
+        let server = HttpServer::new(move || {
+            // Middlewares run in reverse order of registration, so clients
+            // are checked against the ACL before authenticating
+            App::new()
+                .wrap_fn({
+                    let auth = auth.clone();
+                    move |req, srv| web::login::authenticate(&auth, req, srv)
+                })
+                .wrap_fn({
+                    let acl = acl.clone();
+                    move |req, srv| web::filter_client(&acl, req, srv)
+                })
+                .app_data(metrics_config.clone())
+                .app_data(data_config.clone())
+                .app_data(auth.clone())
+                .service(
+                    ActixWeb::resource(auth::LOGIN_PATH)
+                        .route(ActixWeb::get().to(web::login::serve_login_page))
+                        .route(ActixWeb::post().to(web::login::login)),
+                )
+                .service(ActixWeb::resource(auth::LOGOUT_PATH).to(web::login::logout))
+                .service(ActixWeb::resource("/ws_data").to(web::ws::serve_data))
+                .service(ActixWeb::resource("/metrics").to(web::serve_metrics))
+                .service(ActixWeb::resource("/api/history").to(web::serve_history))
+                .service(ActixWeb::resource("/api/admin/sessions").to(web::serve_sessions))
+                .service(
+                    ActixWeb::scope("/api/v1")
+                        .route("/status", ActixWeb::get().to(web::api::serve_status))
+                        .route(
+                            "/net/{iface}",
+                            ActixWeb::get().to(web::api::serve_interface),
+                        )
+                        .route("/processes", ActixWeb::get().to(web::api::serve_processes))
+                        // Same as /processes, rather than another unsorted copy
+                        .route("/proc", ActixWeb::get().to(web::api::serve_processes))
+                        .route("/stream", ActixWeb::get().to(web::sse::serve_stream))
+                        .route("/openapi.json", ActixWeb::get().to(web::api::serve_openapi))
+                        // One endpoint per collector, e.g. /api/v1/ram
+                        .route("/{section}", ActixWeb::get().to(web::api::serve_section)),
+                )
+                .service(actix_files::Files::new("/", &serve_directory).index_file("index.html"))
+        });
+
+        let server = match &listener.tls {
+            Some(tls_config) => {
+                let server_config = match tls::server_config(tls_config) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        error!("Could not set up TLS: {:#}", e);
+                        process::exit(1);
+                    }
+                };
+
+                if let Some(redirect_port) = tls_config.redirect_port {
+                    let redirect_config = ActixWeb::Data::new(web::RedirectConfig {
+                        https_port: listener.port,
+                    });
+                    let redirect = HttpServer::new(move || {
+                        App::new()
+                            .app_data(redirect_config.clone())
+                            .default_service(ActixWeb::to(web::redirect_to_https))
+                    })
+                    .bind((address, redirect_port))?
+                    .run();
+
+                    servers.push(redirect);
+                }
+
+                server.bind_rustls((address, listener.port), server_config)?
+            }
+            None => server.bind((address, listener.port))?,
+        };
+
+        servers.push(server.run());
+    }
 
-    HttpServer::new(move || {
-        App::new()
-            .wrap(IPFilter::new().allow(allowed_subnets.iter().map(|x| *x).collect()))
-            .service(ActixWeb::resource("/ws_data").to(web::serve_data))
-            .service(actix_files::Files::new("/", &args.serve_directory).index_file("index.html"))
-    })
-    .bind(("0.0.0.0", args.port))?
-    .run()
-    .await
+    futures_util::future::try_join_all(servers).await?;
+    Ok(())
 }
@@ -0,0 +1,468 @@
+use crate::status::cpu::{self, CpuData};
+use crate::status::cpufreq::{self, CpuFreqData};
+use crate::status::disk::{self, FsData};
+use crate::status::host::{self, HostData};
+use crate::status::load::{self, LoadData};
+use crate::status::net::{self, IfaStats};
+use crate::status::proc::{self, Process};
+use crate::status::ram::{self, RamData};
+use crate::status::{temp, Status};
+
+use std::fmt::Write;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+const PREFIX: &str = "pistatus";
+
+pub struct MetricsConfig {
+    pub processes: bool,
+}
+
+struct MetricsWriter {
+    out: String,
+}
+
+impl MetricsWriter {
+    fn new() -> Self {
+        MetricsWriter { out: String::new() }
+    }
+
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {}_{} {}", PREFIX, name, help);
+        let _ = writeln!(self.out, "# TYPE {}_{} {}", PREFIX, name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let _ = write!(self.out, "{}_{}", PREFIX, name);
+
+        if !labels.is_empty() {
+            let labels = labels
+                .iter()
+                .map(|(k, v)| format!("{}=\"{}\"", k, escape_label_value(v)))
+                .collect::<Vec<String>>()
+                .join(",");
+            let _ = write!(self.out, "{{{}}}", labels);
+        }
+
+        let _ = writeln!(self.out, " {}", format_value(value));
+    }
+}
+
+// Rust prints `inf` and `NaN`, while the exposition format wants `+Inf`,
+// `-Inf` and `NaN`
+fn format_value(value: f64) -> String {
+    match value {
+        v if v.is_nan() => String::from("NaN"),
+        f64::INFINITY => String::from("+Inf"),
+        f64::NEG_INFINITY => String::from("-Inf"),
+        v => v.to_string(),
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Keys of the sections `render` exports, which a scrape needs fresh
+pub fn sections(config: &MetricsConfig) -> Vec<&'static str> {
+    let mut keys = vec![
+        host::SECTION_KEY,
+        temp::SECTION_KEY,
+        cpu::SECTION_KEY,
+        cpufreq::SECTION_KEY,
+        load::SECTION_KEY,
+        ram::SECTION_KEY,
+        disk::SECTION_KEY,
+        net::SECTION_KEY,
+    ];
+    if config.processes {
+        keys.push(proc::SECTION_KEY);
+    }
+    keys
+}
+
+/// Renders the status in the Prometheus text exposition format
+pub fn render(status: &Status, config: &MetricsConfig) -> String {
+    let mut w = MetricsWriter::new();
+
+    if let Some(host) = status.section::<HostData>(host::SECTION_KEY) {
+        w.family("host_info", "gauge", "Host information");
+        w.sample("host_info", &[("hostname", &host.hostname)], 1.0);
+
+        w.family("uptime_seconds", "gauge", "Time since boot");
+        w.sample("uptime_seconds", &[], host.uptime as f64);
+    }
+
+    if let Some(temp) = status.section::<f64>(temp::SECTION_KEY) {
+        w.family("temperature_celsius", "gauge", "CPU temperature");
+        w.sample("temperature_celsius", &[], temp);
+    }
+
+    if let Some(cpu) = status.section::<CpuData>(cpu::SECTION_KEY) {
+        // The aggregate of all the cores, then each core by id
+        let mut loads = vec![(String::from("all"), &cpu.total)];
+        loads.extend(cpu.cores.iter().map(|(id, load)| (id.to_string(), load)));
+
+        w.family(
+            "cpu_usage_ratio",
+            "gauge",
+            "Fraction of time spent not idle during the last update interval",
+        );
+        for (core, load) in &loads {
+            w.sample(
+                "cpu_usage_ratio",
+                &[("core", core)],
+                load.percent.busy / 100.0,
+            );
+        }
+
+        w.family(
+            "cpu_jiffies",
+            "gauge",
+            "Jiffies spent in each mode during the last update interval",
+        );
+        for (core, load) in &loads {
+            let jiffies = &load.jiffies;
+            for (mode, value) in [
+                ("user", jiffies.user),
+                ("nice", jiffies.nice),
+                ("system", jiffies.system),
+                ("idle", jiffies.idle),
+                ("iowait", jiffies.iowait),
+                ("irq", jiffies.irq),
+                ("softirq", jiffies.softirq),
+                ("steal", jiffies.steal),
+                ("guest", jiffies.guest),
+                ("guest_nice", jiffies.guest_nice),
+            ] {
+                w.sample(
+                    "cpu_jiffies",
+                    &[("core", core), ("mode", mode)],
+                    value as f64,
+                );
+            }
+        }
+
+        for (name, help, value) in [
+            (
+                "context_switches_per_second",
+                "Context switches per second during the last update interval",
+                cpu.context_switches_per_sec,
+            ),
+            (
+                "forks_per_second",
+                "Processes created per second during the last update interval",
+                cpu.forks_per_sec,
+            ),
+        ] {
+            if let Some(value) = value {
+                w.family(name, "gauge", help);
+                w.sample(name, &[], value);
+            }
+        }
+
+        for (name, help, value) in [
+            (
+                "procs_running",
+                "Processes runnable",
+                cpu.procs_running as f64,
+            ),
+            (
+                "procs_blocked",
+                "Processes blocked waiting for I/O",
+                cpu.procs_blocked as f64,
+            ),
+            (
+                "boot_time_seconds",
+                "Unix time of the boot",
+                cpu.boot_time as f64,
+            ),
+        ] {
+            w.family(name, "gauge", help);
+            w.sample(name, &[], value);
+        }
+    }
+
+    if let Some(cpufreq) = status.section::<CpuFreqData>(cpufreq::SECTION_KEY) {
+        if !cpufreq.cores.is_empty() {
+            w.family(
+                "cpu_frequency_hertz",
+                "gauge",
+                "Current frequency of the core",
+            );
+            for (id, core) in &cpufreq.cores {
+                w.sample(
+                    "cpu_frequency_hertz",
+                    &[("core", &id.to_string())],
+                    core.cur_khz as f64 * 1e3,
+                );
+            }
+
+            w.family(
+                "cpu_frequency_min_hertz",
+                "gauge",
+                "Lowest frequency the governor may pick",
+            );
+            for (id, core) in &cpufreq.cores {
+                w.sample(
+                    "cpu_frequency_min_hertz",
+                    &[("core", &id.to_string())],
+                    core.min_khz as f64 * 1e3,
+                );
+            }
+
+            w.family(
+                "cpu_frequency_max_hertz",
+                "gauge",
+                "Highest frequency the governor may pick",
+            );
+            for (id, core) in &cpufreq.cores {
+                w.sample(
+                    "cpu_frequency_max_hertz",
+                    &[("core", &id.to_string())],
+                    core.max_khz as f64 * 1e3,
+                );
+            }
+        }
+
+        if let Some(throttling) = cpufreq.throttling {
+            w.family(
+                "throttling",
+                "gauge",
+                "Raspberry Pi firmware throttling flags, now and since boot",
+            );
+            for (when, flags) in [
+                ("now", throttling.now),
+                ("since_boot", throttling.since_boot),
+            ] {
+                for (flag, set) in [
+                    ("under_voltage", flags.under_voltage),
+                    ("frequency_capped", flags.frequency_capped),
+                    ("throttled", flags.throttled),
+                    ("soft_temp_limit", flags.soft_temp_limit),
+                ] {
+                    w.sample(
+                        "throttling",
+                        &[("flag", flag), ("when", when)],
+                        set as u8 as f64,
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(load) = status.section::<LoadData>(load::SECTION_KEY) {
+        w.family("load_average", "gauge", "Load average");
+        for (period, value) in [("1m", load.load1), ("5m", load.load5), ("15m", load.load15)] {
+            w.sample("load_average", &[("period", period)], value);
+        }
+
+        for (name, help, value) in [
+            ("tasks_runnable", "Tasks runnable", load.runnable),
+            ("tasks", "Tasks, threads included", load.tasks),
+        ] {
+            w.family(name, "gauge", help);
+            w.sample(name, &[], value as f64);
+        }
+
+        if let Some(pressure) = load.pressure {
+            w.family(
+                "pressure_ratio",
+                "gauge",
+                "Share of the time tasks were stalled waiting for the resource, over the window",
+            );
+            for (resource, stalls) in pressure.resources() {
+                for (kind, stall) in stalls.stalls() {
+                    for (window, value) in [
+                        ("10s", stall.avg10),
+                        ("60s", stall.avg60),
+                        ("300s", stall.avg300),
+                    ] {
+                        w.sample(
+                            "pressure_ratio",
+                            &[("resource", resource), ("kind", kind), ("window", window)],
+                            value / 100.0,
+                        );
+                    }
+                }
+            }
+
+            w.family(
+                "pressure_stalled_seconds_total",
+                "counter",
+                "Time tasks were stalled waiting for the resource since boot",
+            );
+            for (resource, stalls) in pressure.resources() {
+                for (kind, stall) in stalls.stalls() {
+                    w.sample(
+                        "pressure_stalled_seconds_total",
+                        &[("resource", resource), ("kind", kind)],
+                        stall.total_us as f64 / 1e6,
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(ram) = status.section::<RamData>(ram::SECTION_KEY) {
+        for (name, help, value) in [
+            ("memory_total_bytes", "Total memory", ram.total),
+            ("memory_used_bytes", "Used memory", ram.used),
+            ("memory_available_bytes", "Available memory", ram.available),
+            ("memory_free_bytes", "Free memory", ram.free),
+            ("memory_cached_bytes", "Memory used as cache", ram.cached),
+        ] {
+            w.family(name, "gauge", help);
+            w.sample(name, &[], value as f64);
+        }
+    }
+
+    if let Some(filesystems) = status.section::<Vec<FsData>>(disk::SECTION_KEY) {
+        w.family("filesystem_size_bytes", "gauge", "Filesystem size");
+        for fs in &filesystems {
+            w.sample(
+                "filesystem_size_bytes",
+                &[
+                    ("mountpoint", &fs.mountpoint),
+                    ("filesystem", &fs.filesystem),
+                ],
+                fs.total as f64,
+            );
+        }
+
+        w.family(
+            "filesystem_available_bytes",
+            "gauge",
+            "Filesystem space available",
+        );
+        for fs in &filesystems {
+            w.sample(
+                "filesystem_available_bytes",
+                &[
+                    ("mountpoint", &fs.mountpoint),
+                    ("filesystem", &fs.filesystem),
+                ],
+                fs.available as f64,
+            );
+        }
+    }
+
+    if let Some(interfaces) = status.section::<Vec<IfaStats>>(net::SECTION_KEY) {
+        w.family(
+            "network_transmit_bytes_total",
+            "counter",
+            "Bytes transmitted by the interface",
+        );
+        for ifa in &interfaces {
+            w.sample(
+                "network_transmit_bytes_total",
+                &[("interface", &ifa.interface.interface_name)],
+                ifa.upload_total as f64,
+            );
+        }
+
+        w.family(
+            "network_receive_bytes_total",
+            "counter",
+            "Bytes received by the interface",
+        );
+        for ifa in &interfaces {
+            w.sample(
+                "network_receive_bytes_total",
+                &[("interface", &ifa.interface.interface_name)],
+                ifa.download_total as f64,
+            );
+        }
+
+        // The dashboard speeds are in bytes per millisecond times 1024
+        w.family(
+            "network_transmit_bytes_per_second",
+            "gauge",
+            "Bytes transmitted by the interface per second, over the last update",
+        );
+        for ifa in &interfaces {
+            w.sample(
+                "network_transmit_bytes_per_second",
+                &[("interface", &ifa.interface.interface_name)],
+                ifa.upload_speed * 1000.0 / 1024.0,
+            );
+        }
+
+        w.family(
+            "network_receive_bytes_per_second",
+            "gauge",
+            "Bytes received by the interface per second, over the last update",
+        );
+        for ifa in &interfaces {
+            w.sample(
+                "network_receive_bytes_per_second",
+                &[("interface", &ifa.interface.interface_name)],
+                ifa.download_speed * 1000.0 / 1024.0,
+            );
+        }
+    }
+
+    if config.processes {
+        if let Some(processes) = status.section::<Vec<Process>>(proc::SECTION_KEY) {
+            w.family(
+                "process_cpu_usage_ratio",
+                "gauge",
+                "Fraction of a CPU used by the process during the last update interval",
+            );
+            for p in &processes {
+                if let Some(cpu_usage) = p.cpu_usage {
+                    w.sample(
+                        "process_cpu_usage_ratio",
+                        &[("pid", &p.pid.to_string()), ("name", &p.name)],
+                        cpu_usage / 100.0,
+                    );
+                }
+            }
+
+            w.family("process_memory_bytes", "gauge", "Process resident memory");
+            for p in &processes {
+                w.sample(
+                    "process_memory_bytes",
+                    &[("pid", &p.pid.to_string()), ("name", &p.name)],
+                    p.mem as f64,
+                );
+            }
+
+            w.family("process_threads", "gauge", "Process threads");
+            for p in &processes {
+                w.sample(
+                    "process_threads",
+                    &[("pid", &p.pid.to_string()), ("name", &p.name)],
+                    p.threads as f64,
+                );
+            }
+        }
+    }
+
+    w.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_follow_the_exposition_format() {
+        let mut w = MetricsWriter::new();
+        w.sample("temperature_celsius", &[], 42.5);
+        w.sample("ratio", &[("cpu", "a\"b")], f64::NAN);
+        w.sample("ratio", &[], f64::INFINITY);
+        w.sample("ratio", &[], f64::NEG_INFINITY);
+
+        assert_eq!(
+            w.out,
+            "pistatus_temperature_celsius 42.5\n\
+             pistatus_ratio{cpu=\"a\\\"b\"} NaN\n\
+             pistatus_ratio +Inf\n\
+             pistatus_ratio -Inf\n"
+        );
+    }
+}
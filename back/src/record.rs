@@ -0,0 +1,426 @@
+mod consts;
+pub mod err;
+
+use crate::status::source::{FsStats, Source};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Error, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use lazy_static::lazy_static;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+pub use self::consts::DEFAULT_REPLAY_SPEED;
+use self::consts::{FORMAT, VERSION};
+use self::err::RecordErr;
+
+lazy_static! {
+    pub static ref RECORDER: RwLock<Option<Arc<Recorder>>> = RwLock::new(None);
+}
+
+pub struct ReplayConfig {
+    pub path: PathBuf,
+    // 2.0 replays twice as fast as recorded
+    pub speed: f64,
+}
+
+// First line of a recording
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+    page_size: u64,
+}
+
+/// What the collectors read during an update. Only what changed since it
+/// was last recorded is kept, so that replaying the frames in order rebuilds
+/// what was read at the time
+#[derive(Serialize, Deserialize, Default)]
+struct Frame {
+    // Unix timestamp, in milliseconds
+    time: u128,
+    // Monotonic time, in milliseconds
+    #[serde(default)]
+    elapsed: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    files: BTreeMap<String, String>,
+    // Files which couldn't be read
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    missing: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    dirs: BTreeMap<String, Vec<String>>,
+    // Mount point: (total, available)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    filesystems: BTreeMap<String, (u64, u64)>,
+}
+
+impl Frame {
+    fn is_empty(&self) -> bool {
+        self.files.is_empty()
+            && self.missing.is_empty()
+            && self.dirs.is_empty()
+            && self.filesystems.is_empty()
+    }
+}
+
+// Everything read so far, as rebuilt from the frames
+#[derive(Default)]
+struct Snapshot {
+    files: HashMap<String, String>,
+    missing: HashSet<String>,
+    dirs: HashMap<String, Vec<String>>,
+    filesystems: HashMap<String, (u64, u64)>,
+}
+
+impl Snapshot {
+    fn apply(&mut self, frame: Frame) {
+        for (dir, entries) in &frame.dirs {
+            self.prune(dir, entries);
+        }
+
+        for path in frame.missing {
+            self.files.remove(&path);
+            self.missing.insert(path);
+        }
+        for path in frame.files.keys() {
+            self.missing.remove(path);
+        }
+        self.files.extend(frame.files);
+        self.dirs.extend(frame.dirs);
+        self.filesystems.extend(frame.filesystems);
+    }
+
+    // Forgets what was read below the entries gone from a directory (e.g.
+    // `/proc/<pid>/stat` of the processes which exited), so that it doesn't
+    // grow for as long as pids keep changing
+    fn prune(&mut self, dir: &str, entries: &[String]) {
+        let listed = entries
+            .iter()
+            .map(String::as_str)
+            .collect::<HashSet<&str>>();
+        let is_gone = |path: &str| {
+            path.strip_prefix(dir)
+                .and_then(|p| p.strip_prefix('/'))
+                .and_then(|p| p.split('/').next())
+                .is_some_and(|entry| !listed.contains(entry))
+        };
+
+        self.files.retain(|p, _| !is_gone(p));
+        self.missing.retain(|p| !is_gone(p));
+        self.dirs.retain(|p, _| !is_gone(p));
+    }
+
+    // Leaves in the frame only what differs from the snapshot
+    fn changes(&self, frame: &mut Frame) {
+        frame
+            .files
+            .retain(|p, content| self.files.get(p) != Some(content));
+        frame.missing.retain(|p| !self.missing.contains(p));
+        frame
+            .dirs
+            .retain(|p, entries| self.dirs.get(p) != Some(entries));
+        frame
+            .filesystems
+            .retain(|m, sizes| self.filesystems.get(m) != Some(sizes));
+    }
+}
+
+struct RecorderState {
+    // Everything read since the last commit
+    frame: Frame,
+    // Last recorded state, to only write what changed
+    recorded: Snapshot,
+    writer: GzEncoder<File>,
+}
+
+/// Source recording everything read through it, one frame per update, in a
+/// gzipped file of JSON lines. Frames are flushed as they are written, so a
+/// recording stays readable when pi-status is stopped abruptly
+pub struct Recorder {
+    inner: Arc<dyn Source>,
+    state: Mutex<RecorderState>,
+    // Held for reading by the collector runs, so that frames are only cut
+    // between runs
+    runs: RwLock<()>,
+}
+
+impl Recorder {
+    pub fn start(path: &Path, inner: Arc<dyn Source>) -> Result<Arc<Self>> {
+        let file = File::create(path)
+            .with_context(|| format!("Could not create the recording {}", path.display()))?;
+        let mut writer = GzEncoder::new(file, Compression::best());
+
+        let header = Header {
+            format: String::from(FORMAT),
+            version: VERSION,
+            page_size: inner.page_size()?,
+        };
+        writeln!(writer, "{}", serde_json::to_string(&header)?)?;
+        writer.flush()?;
+
+        Ok(Arc::new(Recorder {
+            inner,
+            state: Mutex::new(RecorderState {
+                frame: Frame::default(),
+                recorded: Snapshot::default(),
+                writer,
+            }),
+            runs: RwLock::new(()),
+        }))
+    }
+
+    /// Marks a collector run, until the guard is dropped
+    pub fn run(&self) -> RwLockReadGuard<'_, ()> {
+        self.runs.read().unwrap()
+    }
+
+    /// Writes what was read since the last commit. Runs still going on (e.g.
+    /// a collector hanging on a stale mount) would be split across frames,
+    /// so their reads are left for a later commit
+    pub fn commit(&self) -> Result<()> {
+        let Ok(_runs) = self.runs.try_write() else {
+            return Ok(());
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let mut frame = std::mem::take(&mut state.frame);
+        state.recorded.changes(&mut frame);
+        if frame.is_empty() {
+            return Ok(());
+        }
+
+        frame.time = self.inner.now_millis();
+        frame.elapsed = self.inner.monotonic().as_millis() as u64;
+
+        writeln!(state.writer, "{}", serde_json::to_string(&frame)?)?;
+        state.writer.flush()?;
+
+        state.recorded.apply(frame);
+        Ok(())
+    }
+}
+
+impl Source for Recorder {
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        let read = self.inner.read_to_string(path);
+
+        let mut state = self.state.lock().unwrap();
+        let frame = &mut state.frame;
+        match &read {
+            Ok(content) => {
+                frame.files.insert(path.to_string(), content.clone());
+                frame.missing.remove(path);
+            }
+            Err(_) => {
+                frame.files.remove(path);
+                frame.missing.insert(path.to_string());
+            }
+        }
+
+        read
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        let entries = self.inner.read_dir(path)?;
+
+        let mut state = self.state.lock().unwrap();
+        state.frame.dirs.insert(path.to_string(), entries.clone());
+
+        Ok(entries)
+    }
+
+    fn statvfs(&self, mountpoint: &str) -> io::Result<FsStats> {
+        let stats = self.inner.statvfs(mountpoint)?;
+
+        let mut state = self.state.lock().unwrap();
+        state
+            .frame
+            .filesystems
+            .insert(mountpoint.to_string(), (stats.total, stats.available));
+
+        Ok(stats)
+    }
+
+    fn page_size(&self) -> Result<u64> {
+        self.inner.page_size()
+    }
+
+    fn now_millis(&self) -> u128 {
+        self.inner.now_millis()
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.inner.monotonic()
+    }
+}
+
+struct ReplayState {
+    snapshot: Snapshot,
+    // Frames not applied yet, the next one first
+    frames: Vec<Frame>,
+    time: u128,
+    elapsed: u64,
+    // Where the replay clock was last set
+    base: (Instant, u128),
+    finished: bool,
+}
+
+/// Source serving a recording, its frames being applied as the replay clock
+/// reaches their time
+pub struct Replay {
+    page_size: u64,
+    speed: f64,
+    state: Mutex<ReplayState>,
+}
+
+impl Replay {
+    pub fn open(config: ReplayConfig) -> Result<Self> {
+        let file = File::open(&config.path)
+            .with_context(|| format!("Could not open the recording {}", config.path.display()))?;
+        let mut lines = BufReader::new(GzDecoder::new(file)).lines();
+
+        let header = lines
+            .next()
+            .and_then(|l| l.ok())
+            .and_then(|l| serde_json::from_str::<Header>(&l).ok())
+            .filter(|h| h.format == FORMAT)
+            .ok_or_else(|| Error::new(RecordErr::NotAnArchive))?;
+        if header.version != VERSION {
+            return Err(Error::new(RecordErr::UnsupportedVersion(header.version)));
+        }
+
+        let mut frames = Vec::new();
+        for line in lines {
+            // The end of recordings which were not closed is cut off
+            let Ok(Ok(frame)) = line.map(|l| serde_json::from_str::<Frame>(&l)) else {
+                break;
+            };
+            frames.push(frame);
+        }
+        if frames.is_empty() {
+            return Err(Error::new(RecordErr::NoFrames));
+        }
+        frames.reverse();
+
+        let start = frames[frames.len() - 1].time;
+        info!(
+            "Replaying {} frames from {}",
+            frames.len(),
+            config.path.display()
+        );
+
+        let replay = Replay {
+            page_size: header.page_size,
+            speed: config.speed,
+            state: Mutex::new(ReplayState {
+                snapshot: Snapshot::default(),
+                frames,
+                time: start,
+                elapsed: 0,
+                base: (Instant::now(), start),
+                finished: false,
+            }),
+        };
+        replay.advance(&mut replay.state.lock().unwrap());
+
+        Ok(replay)
+    }
+
+    /// Applies the next `frames` frames at once, the replay going on from
+    /// there
+    pub fn skip(&self, frames: usize) {
+        let mut state = self.state.lock().unwrap();
+
+        for _ in 0..frames {
+            let Some(frame) = state.frames.pop() else {
+                break;
+            };
+            state.time = frame.time;
+            state.elapsed = frame.elapsed;
+            state.snapshot.apply(frame);
+        }
+        state.base = (Instant::now(), state.time);
+    }
+
+    // Applies the frames the replay clock went past
+    fn advance(&self, state: &mut ReplayState) {
+        let (started, time) = state.base;
+        let elapsed = started.elapsed().as_secs_f64() * self.speed * 1e3;
+        let now = time + elapsed as u128;
+
+        while state.frames.last().is_some_and(|f| f.time <= now) {
+            let frame = state.frames.pop().unwrap();
+            state.time = frame.time;
+            state.elapsed = frame.elapsed;
+            state.snapshot.apply(frame);
+        }
+
+        if state.frames.is_empty() && !state.finished {
+            state.finished = true;
+            info!("The replay reached the end of the recording");
+        }
+    }
+
+    fn with_snapshot<T>(&self, f: impl FnOnce(&Snapshot) -> T) -> T {
+        let mut state = self.state.lock().unwrap();
+        self.advance(&mut state);
+        f(&state.snapshot)
+    }
+}
+
+fn not_recorded(path: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} is not in the recording", path),
+    )
+}
+
+impl Source for Replay {
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        self.with_snapshot(|s| s.files.get(path).cloned())
+            .ok_or_else(|| not_recorded(path))
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.with_snapshot(|s| s.dirs.get(path).cloned())
+            .ok_or_else(|| not_recorded(path))
+    }
+
+    fn statvfs(&self, mountpoint: &str) -> io::Result<FsStats> {
+        let (total, available) = self
+            .with_snapshot(|s| s.filesystems.get(mountpoint).copied())
+            .ok_or_else(|| not_recorded(mountpoint))?;
+
+        Ok(FsStats { total, available })
+    }
+
+    fn page_size(&self) -> Result<u64> {
+        Ok(self.page_size)
+    }
+
+    // Times of the frame being served, so that rates are the recorded ones
+    // whatever the replay speed
+    fn now_millis(&self) -> u128 {
+        let mut state = self.state.lock().unwrap();
+        self.advance(&mut state);
+        state.time
+    }
+
+    fn monotonic(&self) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        self.advance(&mut state);
+        Duration::from_millis(state.elapsed)
+    }
+}
+
+pub fn is_enabled() -> bool {
+    RECORDER.read().unwrap().is_some()
+}
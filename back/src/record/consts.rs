@@ -0,0 +1,4 @@
+pub const FORMAT: &str = "pi-status-record";
+pub const VERSION: u32 = 1;
+
+pub const DEFAULT_REPLAY_SPEED: f64 = 1.0;
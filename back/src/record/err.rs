@@ -0,0 +1,22 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum RecordErr {
+    NotAnArchive,
+    UnsupportedVersion(u32),
+    NoFrames,
+}
+
+impl std::error::Error for RecordErr {}
+
+impl fmt::Display for RecordErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordErr::NotAnArchive => write!(f, "The file is not a pi-status recording"),
+            RecordErr::UnsupportedVersion(v) => {
+                write!(f, "Recordings of version {} are not supported", v)
+            }
+            RecordErr::NoFrames => write!(f, "The recording is empty"),
+        }
+    }
+}
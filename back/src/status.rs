@@ -1,178 +1,481 @@
+pub mod collector;
 pub mod cpu;
+pub mod cpufreq;
 pub mod disk;
+mod errors;
 pub mod host;
+pub mod load;
 pub mod net;
 pub mod proc;
 pub mod ram;
+pub mod source;
 pub mod temp;
+mod worker;
 
+use crate::history::{self, HISTORY};
+use crate::record::{self, RECORDER};
+use crate::store::{self, STORE};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, RwLock};
-use std::thread;
-use std::time::Duration;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
 use lazy_static::lazy_static;
-use log::{error, warn};
-use serde::Serialize;
+use log::error;
+use serde::de::DeserializeOwned;
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use self::collector::err::CollectorErr;
+use self::collector::{Collector, CollectorInfo};
+use self::cpu::CpuCollector;
+use self::cpufreq::CpuFreqCollector;
+use self::disk::DiskCollector;
+pub use self::errors::CollectorError;
+use self::host::HostCollector;
+use self::load::LoadCollector;
+use self::net::NetCollector;
+use self::proc::ProcCollector;
+use self::ram::RamCollector;
+use self::source::Source;
+use self::temp::TempCollector;
+pub use self::worker::Timing;
+use self::worker::{Poll, Worker};
+
+// WebSocket sessions and event streams
+pub static ACTIVE_CLIENTS: AtomicU64 = AtomicU64::new(0);
+
+// Incremented after every update of the status, so that consumers can tell
+// whether data they derived from it is outdated
+pub static STATUS_GENERATION: AtomicU64 = AtomicU64::new(0);
 
-use self::cpu::{CoreUsage, CpuUsage};
-use self::disk::{DiskData, FsData};
-use self::host::HostData;
-use self::net::{IfaStats, NetData};
-use self::proc::{Process, ProcessData};
-use self::ram::RamData;
-use self::temp::TempData;
+// Unix timestamp, in seconds, of the last request for data not coming from a
+// WebSocket connection (e.g. a Prometheus scrape)
+static LAST_DEMAND: AtomicU64 = AtomicU64::new(0);
+const DEMAND_WINDOW_SECS: u64 = 120;
 
-pub static ACTIVE_WS_CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+// Longest pause of a collector which keeps timing out
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
 
-pub const DOCKER_PROC_DIR_ENV: &str = "PST_PROC_DIR";
-pub const DOCKER_MOUNTS_FILE_ENV: &str = "PST_MOUNTS_FILE";
-pub const DOCKER_HOST_FILE_ENV: &str = "PST_HOST_FILE";
-pub const DOCKER_NET_DIR_ENV: &str = "PST_NET_DIR";
-pub const DOCKER_THERMAL_DIR_ENV: &str = "PST_THERMAL_DIR";
+pub const TIMED_OUT_KEY: &str = "timed_out";
+pub const ERRORS_KEY: &str = "errors";
+pub const TIMING_KEY: &str = "timing";
 
 lazy_static! {
-    pub static ref STATUS: RwLock<Status> = RwLock::new(Status {
-        host: None,
-        temp: None,
-        net_stats: None,
-        cpu_usage: None,
-        disk: None,
-        ram: None,
-        proc: None,
-    });
+    pub static ref STATUS: RwLock<Status> = RwLock::new(Status::default());
     pub static ref STATUS_STR: Arc<RwLock<String>> = Arc::new(RwLock::new(String::new()));
+    // Same as STATUS_STR, without sensitive sections
+    pub static ref STATUS_SUMMARY_STR: Arc<RwLock<String>> = Arc::new(RwLock::new(String::new()));
+    // When the last update started, every section due then having run
+    static ref LAST_UPDATE: RwLock<Option<Instant>> = RwLock::new(None);
+    // Wakes the update loop up while it's idle
+    static ref WAKE_UP: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+    // Notified after every update
+    static ref UPDATED: (Mutex<()>, Condvar) = (Mutex::new(()), Condvar::new());
+    // Every collector known to the update loop, enabled or not
+    static ref COLLECTORS: RwLock<Arc<Vec<CollectorInfo>>> = RwLock::new(Arc::new(Vec::new()));
+    // Number of WebSocket sessions and event streams subscribed to each
+    // section, by collector name
+    static ref SECTION_SUBSCRIBERS: Mutex<HashMap<String, u64>> = Mutex::new(HashMap::new());
+}
+
+/// Every collector shipped with pi-status, in the order of the status
+/// sections. Collectors added here get a configuration section, a REST
+/// endpoint and a WebSocket section without any other change
+pub fn builtin_collectors(source: Arc<dyn Source>) -> Vec<Box<dyn Collector>> {
+    vec![
+        Box::new(HostCollector::new(source.clone())),
+        Box::new(TempCollector::new(source.clone())),
+        Box::new(NetCollector::new(source.clone())),
+        Box::new(CpuCollector::new(source.clone())),
+        Box::new(CpuFreqCollector::new(source.clone())),
+        Box::new(LoadCollector::new(source.clone())),
+        Box::new(RamCollector::new(source.clone())),
+        Box::new(DiskCollector::new(source.clone())),
+        Box::new(ProcCollector::new(source)),
+    ]
+}
+
+pub struct CollectorSettings {
+    pub enabled: bool,
+    // None means the collector's own default
+    pub interval: Option<Duration>,
+    // Longest wait for a run, the update goes on without its data after it
+    pub timeout: Duration,
+    pub suppress_errors: bool,
 }
 
-pub struct ErrorSuppressions {
-    pub host: bool,
-    pub temp: bool,
-    pub net: bool,
-    pub cpu: bool,
-    pub ram: bool,
-    pub disk: bool,
-    pub proc: bool,
+/// A configured collector, as run by the update loop
+pub struct ScheduledCollector {
+    collector: Box<dyn Collector>,
+    settings: CollectorSettings,
 }
 
-#[derive(Serialize)]
+pub struct UpdateSettings {
+    // Interval of the collectors which don't have their own
+    pub interval: Duration,
+    pub collectors: Vec<ScheduledCollector>,
+}
+
+impl ScheduledCollector {
+    pub fn new(collector: Box<dyn Collector>, settings: CollectorSettings) -> Self {
+        ScheduledCollector {
+            collector,
+            settings,
+        }
+    }
+
+    /// The configured interval, or else the collector's own default
+    pub fn interval(&self, default: Duration) -> Duration {
+        self.settings
+            .interval
+            .or(self.collector.interval())
+            .unwrap_or(default)
+    }
+}
+
+impl UpdateSettings {
+    /// Interval of the enabled collector running the most often, i.e. how
+    /// often the status can change
+    pub fn shortest_interval(&self) -> Duration {
+        self.collectors
+            .iter()
+            .filter(|c| c.settings.enabled)
+            .map(|c| c.interval(self.interval))
+            .min()
+            .unwrap_or(self.interval)
+    }
+}
+
+/// Latest data of every collector, by section key. Sections of disabled or
+/// failing collectors are null, while those of collectors which are late
+/// keep their last data and are listed under `timed_out`. The current error
+/// of every failing collector is kept under `errors`, and when every section
+/// was collected under `timing`
+#[derive(Default)]
 pub struct Status {
-    host: Option<HostData>,
-    temp: Option<f32>,
-    net_stats: Option<Vec<IfaStats>>,
-    cpu_usage: Option<Vec<CoreUsage>>,
-    ram: Option<RamData>,
-    disk: Option<Vec<FsData>>,
-    proc: Option<Vec<Process>>,
+    sections: Map<String, Value>,
+    timed_out: BTreeSet<&'static str>,
+    errors: BTreeMap<&'static str, CollectorError>,
+    timing: BTreeMap<&'static str, Timing>,
 }
 
-pub fn continous_update(error_suppressions: ErrorSuppressions) {
-    let mut just_run;
-    let mut cpu_usage: CpuUsage = CpuUsage::new();
-    let mut procs: Option<ProcessData> = match ProcessData::new() {
-        Ok(p) => Some(p),
-        Err(e) => {
-            warn!("Could not start getting process data: {}. For this run process data will not be retrieved", e);
-            None
+impl Status {
+    /// Data of a section, None when there's none
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.sections.get(key).filter(|v| !v.is_null())
+    }
+
+    /// Typed data of a section, for consumers knowing what the collector
+    /// produces
+    pub fn section<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        T::deserialize(self.get(key)?).ok()
+    }
+
+    fn set(&mut self, key: &str, data: Option<Value>) {
+        self.sections
+            .insert(key.to_string(), data.unwrap_or(Value::Null));
+    }
+
+    /// Names of the collectors which didn't answer in time
+    pub fn timed_out(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.timed_out.iter().copied()
+    }
+
+    /// Current errors, by collector name
+    pub fn errors(&self) -> impl Iterator<Item = (&'static str, &CollectorError)> + '_ {
+        self.errors.iter().map(|(n, e)| (*n, e))
+    }
+
+    /// When the sections were collected, by collector name
+    pub fn timing(&self) -> impl Iterator<Item = (&'static str, &Timing)> + '_ {
+        self.timing.iter().map(|(n, t)| (*n, t))
+    }
+
+    /// The serialized status, without sensitive sections for summaries
+    pub fn to_value(&self, summary: bool) -> Value {
+        let mut sections = self.sections.clone();
+
+        if summary {
+            for info in COLLECTORS.read().unwrap().iter().filter(|c| c.sensitive) {
+                sections.insert(info.key.to_string(), Value::Null);
+            }
         }
-    };
-    let mut net_data: Option<NetData> = Some(NetData::new());
+        sections.insert(String::from(TIMED_OUT_KEY), self.timed_out().collect());
+        sections.insert(
+            String::from(ERRORS_KEY),
+            serde_json::to_value(&self.errors).unwrap_or_default(),
+        );
+        sections.insert(
+            String::from(TIMING_KEY),
+            serde_json::to_value(&self.timing).unwrap_or_default(),
+        );
+
+        Value::Object(sections)
+    }
+}
+
+impl Serialize for Status {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value(false).serialize(serializer)
+    }
+}
+
+/// Makes the collectors known to the rest of the program, and their
+/// sections part of the status even before they run
+pub fn register_collectors(settings: &UpdateSettings) {
+    let mut status_ref = STATUS.write().unwrap();
+    let mut collectors = Vec::new();
+
+    for scheduled in &settings.collectors {
+        let info = CollectorInfo::of(scheduled.collector.as_ref());
+        status_ref.set(info.key, None);
+        collectors.push(info);
+    }
+
+    *COLLECTORS.write().unwrap() = Arc::new(collectors);
+}
+
+pub fn collectors() -> Arc<Vec<CollectorInfo>> {
+    COLLECTORS.read().unwrap().clone()
+}
+
+pub fn collector(name: &str) -> Option<CollectorInfo> {
+    COLLECTORS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|c| c.name == name)
+        .cloned()
+}
+
+pub fn continous_update(settings: UpdateSettings) {
+    let mut just_run;
+    let mut workers = settings
+        .collectors
+        .into_iter()
+        .map(|s| Worker::spawn(s, settings.interval))
+        .collect::<Vec<Worker>>();
 
     loop {
+        let now = Instant::now();
+        for worker in workers.iter_mut() {
+            if is_section_demanded(worker.name, worker.sensitive) && worker.due(now) {
+                worker.dispatch(now);
+            }
+        }
+
+        // Collectors run while no lock is held, only their results are
+        // applied under the write lock
+        let polls = workers.iter_mut().map(|w| w.poll()).collect::<Vec<Poll>>();
+        // Samples of the collectors which just ran, by collection time, so
+        // that a section which didn't run isn't recorded again
+        let mut samples: Vec<(u64, Vec<(String, f64)>)> = Vec::new();
+
         {
             let mut status_ref = STATUS.write().unwrap();
 
-            status_ref.host = match HostData::get() {
-                Ok(h) => Some(h),
-                Err(e) => {
-                    if !error_suppressions.host {
-                        error!("Could not get host data: {}", e);
+            for (worker, poll) in workers.iter_mut().zip(polls) {
+                let log = worker.log_errors();
+                match poll {
+                    Poll::Idle | Poll::Late => (),
+                    Poll::TimedOut => {
+                        let e = anyhow::Error::new(CollectorErr::TimedOut(worker.timeout()));
+                        let error = worker.errors.fail(worker.name, &e, log);
+                        status_ref.errors.insert(worker.name, error);
+                        status_ref.timed_out.insert(worker.name);
                     }
-                    None
-                }
-            };
-            status_ref.temp = match TempData::get() {
-                Ok(t) => Some(t.degrees),
-                Err(e) => {
-                    if !error_suppressions.temp {
-                        error!("Could not get temperature data: {}", e);
+                    Poll::Done(collected) => {
+                        status_ref.timed_out.remove(worker.name);
+
+                        let data = match collected {
+                            Ok(c) => {
+                                worker.errors.recover(worker.name, log);
+                                status_ref.errors.remove(worker.name);
+                                samples.push((c.timing.collected_at / 1000, c.samples));
+                                status_ref.timing.insert(worker.name, c.timing);
+                                Some(c.data)
+                            }
+                            Err(e) => {
+                                let error = worker.errors.fail(worker.name, &e, log);
+                                status_ref.errors.insert(worker.name, error);
+                                status_ref.timing.remove(worker.name);
+                                None
+                            }
+                        };
+                        status_ref.set(worker.key, data);
                     }
-                    None
                 }
-            };
+            }
+        }
 
-            status_ref.net_stats = match net_data {
-                Some(ref mut n) => match n.update() {
-                    Ok(()) => Some(
-                        n.stats
-                            .values()
-                            .cloned()
-                            .filter(|x| x.has_updated)
-                            .collect(),
-                    ),
-                    Err(e) => {
-                        if !error_suppressions.net {
-                            error!("Could not get network data: {}", e);
-                        }
-                        None
-                    }
-                },
-                None => None,
-            };
+        {
+            let status_ref = STATUS.read().unwrap();
+            let mut status_str_ref = STATUS_STR.write().unwrap();
 
-            status_ref.ram = match RamData::get() {
-                Ok(r) => Some(r),
-                Err(e) => {
-                    if !error_suppressions.ram {
-                        error!("Could not get RAM data: {}", e);
-                    }
-                    None
-                }
-            };
-            status_ref.disk = match DiskData::get() {
-                Ok(d) => Some(d.filesystems),
-                Err(e) => {
-                    if !error_suppressions.disk {
-                        error!("Could not get disk data: {}", e);
-                    }
-                    None
-                }
-            };
+            *status_str_ref = status_ref.to_value(false).to_string();
+            *STATUS_SUMMARY_STR.write().unwrap() = status_ref.to_value(true).to_string();
+            STATUS_GENERATION.fetch_add(1, Ordering::Release);
+            *LAST_UPDATE.write().unwrap() = Some(now);
 
-            status_ref.proc = match procs {
-                Some(ref mut p) => match p.update() {
-                    Ok(()) => Some(p.processes.clone()),
-                    Err(e) => {
-                        if !error_suppressions.proc {
-                            error!("Could not get processes data: {}", e);
+            if history::is_enabled() || store::is_enabled() {
+                for (collected_at, recorded) in samples {
+                    if let Some(history) = HISTORY.write().unwrap().as_mut() {
+                        history.record(collected_at, &recorded);
+                    }
+                    if let Some(store) = STORE.read().unwrap().as_ref() {
+                        if let Err(e) = store.record(collected_at, recorded) {
+                            error!("Could not record data in the store: {}", e);
                         }
-                        None
                     }
-                },
-                None => None,
-            };
+                }
+            }
 
-            status_ref.cpu_usage = match cpu_usage.update() {
-                Ok(()) => Some(cpu_usage.usage.clone()),
-                Err(e) => {
-                    if !error_suppressions.cpu {
-                        error!("Could not get CPU usage: {}", e);
-                    }
-                    None
+            if let Some(recorder) = RECORDER.read().unwrap().as_ref() {
+                if let Err(e) = recorder.commit() {
+                    error!("Could not write the recording: {}", e);
                 }
             }
         }
 
         {
-            let status_ref = STATUS.read().unwrap();
-            let mut status_str_ref = STATUS_STR.write().unwrap();
-
-            *status_str_ref = serde_json::to_string(&*status_ref).unwrap();
+            let (lock, cvar) = &*UPDATED;
+            let _guard = lock.lock().unwrap();
+            cvar.notify_all();
         }
 
         just_run = true;
-        while ACTIVE_WS_CONNECTIONS.load(Ordering::Relaxed) <= 0 || just_run {
-            thread::sleep(Duration::from_millis(1000));
+        while !is_demanded() || just_run {
+            let wait = match is_demanded() {
+                true => until_next_run(&workers, settings.interval),
+                false => settings.interval,
+            };
+
+            let (lock, cvar) = &*WAKE_UP;
+            let mut woken = lock.lock().unwrap();
+            if !*woken {
+                woken = cvar.wait_timeout(woken, wait).unwrap().0;
+            }
+            *woken = false;
             just_run = false;
         }
     }
 }
+
+// Sleeps until the next needed collector is due. Late collectors are checked
+// for an answer at least every `interval`
+fn until_next_run(workers: &[Worker], interval: Duration) -> Duration {
+    let now = Instant::now();
+    let next_run = workers
+        .iter()
+        .filter(|w| is_section_demanded(w.name, w.sensitive))
+        .filter_map(|w| w.next_run(now))
+        .min();
+
+    let wait = next_run.map_or(interval, |n| n.saturating_duration_since(now));
+    match workers.iter().any(|w| w.is_pending()) {
+        true => wait.min(interval),
+        false => wait,
+    }
+}
+
+/// Makes sure the given sections, by key, were collected at most `max_age`
+/// ago, waking the update loop up and waiting for it when nobody kept them
+/// running (e.g. while WebSocket clients only subscribe to other sections).
+/// Blocks, so it must not be called from async code
+pub fn ensure_fresh(keys: &[&str], max_age: Duration, timeout: Duration) {
+    register_demand();
+
+    let names = collectors()
+        .iter()
+        .filter(|c| keys.contains(&c.key))
+        .map(|c| c.name)
+        .collect::<Vec<&'static str>>();
+    if are_fresh(&names, max_age) {
+        return;
+    }
+
+    let asked = Instant::now();
+    {
+        let (lock, cvar) = &*WAKE_UP;
+        *lock.lock().unwrap() = true;
+        cvar.notify_one();
+    }
+
+    // Sections which are disabled, failing or backing off don't get any
+    // fresher, so waiting stops after the first update started since asking
+    let (lock, cvar) = &*UPDATED;
+    let _guard = cvar
+        .wait_timeout_while(lock.lock().unwrap(), timeout, |_| {
+            !are_fresh(&names, max_age) && !LAST_UPDATE.read().unwrap().is_some_and(|u| u >= asked)
+        })
+        .unwrap();
+}
+
+fn are_fresh(names: &[&str], max_age: Duration) -> bool {
+    let now = UNIX_EPOCH.elapsed().unwrap_or_default().as_millis() as u64;
+    let status_ref = STATUS.read().unwrap();
+
+    names.iter().all(|name| {
+        status_ref
+            .timing
+            .get(name)
+            .is_some_and(|t| now.saturating_sub(t.collected_at) <= max_age.as_millis() as u64)
+    })
+}
+
+pub fn now_secs() -> u64 {
+    UNIX_EPOCH.elapsed().map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Keeps the update loop running for a while even if no WebSocket client is
+/// connected, for consumers that poll the data periodically
+pub fn register_demand() {
+    LAST_DEMAND.store(now_secs(), Ordering::Relaxed);
+}
+
+/// Sections are collector names
+pub fn subscribe(sections: &[String]) {
+    let mut subscribers = SECTION_SUBSCRIBERS.lock().unwrap();
+    for s in sections {
+        *subscribers.entry(s.clone()).or_insert(0) += 1;
+    }
+}
+
+pub fn unsubscribe(sections: &[String]) {
+    let mut subscribers = SECTION_SUBSCRIBERS.lock().unwrap();
+    for s in sections {
+        if let Some(count) = subscribers.get_mut(s) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+fn is_recently_demanded() -> bool {
+    now_secs().saturating_sub(LAST_DEMAND.load(Ordering::Relaxed)) < DEMAND_WINDOW_SECS
+}
+
+// Collectors nobody needs are skipped, processes being the most expensive
+// one. History doesn't record sensitive data, while recordings capture
+// everything
+fn is_section_demanded(name: &str, sensitive: bool) -> bool {
+    SECTION_SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .get(name)
+        .is_some_and(|c| *c > 0)
+        || is_recently_demanded()
+        || record::is_enabled()
+        || (!sensitive && (history::is_enabled() || store::is_enabled()))
+}
+
+// History and recordings need to be recorded even when nobody is looking at
+// the data
+fn is_demanded() -> bool {
+    ACTIVE_CLIENTS.load(Ordering::Relaxed) > 0
+        || history::is_enabled()
+        || store::is_enabled()
+        || record::is_enabled()
+        || is_recently_demanded()
+}
@@ -0,0 +1,99 @@
+pub mod err;
+pub mod schema;
+
+use std::time::Duration;
+
+use anyhow::{Context, Error, Result};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use toml::Table;
+
+use self::err::CollectorErr;
+
+/// A source of data filling one section of the status. The update loop only
+/// knows collectors through this trait, so adding one means implementing it
+/// and listing the collector in `builtin_collectors`
+pub trait Collector: Send {
+    /// Name used in the configuration (`[collectors.<name>]`), subscriptions
+    /// and the REST API
+    fn name(&self) -> &'static str;
+
+    /// Key of the section in the serialized status
+    fn key(&self) -> &'static str {
+        self.name()
+    }
+
+    /// One line summary, used by the API documentation
+    fn description(&self) -> &'static str;
+
+    /// Applies the collector specific options of its configuration section.
+    /// Collectors without options reject any
+    fn configure(&mut self, options: &Table) -> Result<()> {
+        match options.keys().next() {
+            Some(option) => Err(Error::new(CollectorErr::UnknownOption {
+                collector: self.name().to_string(),
+                option: option.clone(),
+            })),
+            None => Ok(()),
+        }
+    }
+
+    /// Acquires what the collector needs before its first run. Collectors
+    /// failing here are disabled for the rest of the run
+    fn init(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn collect(&mut self) -> Result<Value>;
+
+    /// Interval used when the configuration doesn't set one, None meaning
+    /// every update
+    fn interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// JSON schema of the collected data
+    fn schema(&self) -> Value;
+
+    /// Sensitive data is only available to clients with the full scope, and
+    /// isn't recorded by the history
+    fn sensitive(&self) -> bool {
+        false
+    }
+
+    /// Numeric samples recorded by the history and the store, e.g.
+    /// `net.eth0.download_speed`
+    fn samples(&self, _data: &Value) -> Vec<(String, f64)> {
+        Vec::new()
+    }
+}
+
+/// What the rest of the program knows about a registered collector
+#[derive(Clone)]
+pub struct CollectorInfo {
+    pub name: &'static str,
+    pub key: &'static str,
+    pub description: &'static str,
+    pub sensitive: bool,
+    pub schema: Value,
+}
+
+impl CollectorInfo {
+    pub fn of(collector: &dyn Collector) -> Self {
+        CollectorInfo {
+            name: collector.name(),
+            key: collector.key(),
+            description: collector.description(),
+            sensitive: collector.sensitive(),
+            schema: collector.schema(),
+        }
+    }
+}
+
+/// Deserializes collector specific options, for collectors taking some.
+/// Unknown options are rejected by `deny_unknown_fields` on `T`
+pub fn options<T: DeserializeOwned>(collector: &str, options: &Table) -> Result<T> {
+    toml::Value::Table(options.clone())
+        .try_into()
+        .with_context(|| format!("Invalid options for the {} collector", collector))
+}
@@ -0,0 +1,41 @@
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum CollectorErr {
+    UnknownOption { collector: String, option: String },
+    Unavailable,
+    TimedOut(Duration),
+}
+
+impl CollectorErr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CollectorErr::UnknownOption { .. } => "unknown_option",
+            CollectorErr::Unavailable => "unavailable",
+            CollectorErr::TimedOut(_) => "timeout",
+        }
+    }
+}
+
+impl std::error::Error for CollectorErr {}
+
+impl fmt::Display for CollectorErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CollectorErr::UnknownOption { collector, option } => {
+                write!(
+                    f,
+                    "`{}` is not an option of the {} collector",
+                    option, collector
+                )
+            }
+            CollectorErr::Unavailable => {
+                write!(f, "The collector was not initialized")
+            }
+            CollectorErr::TimedOut(t) => {
+                write!(f, "No answer within {}ms, the data is stale", t.as_millis())
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// Building blocks for the JSON schemas of the collectors
+
+use serde_json::{json, Map, Value};
+
+pub fn integer() -> Value {
+    json!({ "type": "integer", "format": "int64", "minimum": 0 })
+}
+
+pub fn number() -> Value {
+    json!({ "type": "number", "format": "double" })
+}
+
+pub fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+pub fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+pub fn object(properties: &[(&str, Value)]) -> Value {
+    let required = properties.iter().map(|(n, _)| *n).collect::<Vec<&str>>();
+    let properties = properties
+        .iter()
+        .map(|(n, v)| (n.to_string(), v.clone()))
+        .collect::<Map<String, Value>>();
+
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+pub fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+// Object whose keys are not known in advance
+pub fn map(values: Value) -> Value {
+    json!({ "type": "object", "additionalProperties": values })
+}
+
+pub fn optional(schema: Value) -> Value {
+    json!({ "oneOf": [schema, { "type": "null" }] })
+}
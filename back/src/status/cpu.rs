@@ -1,100 +1,380 @@
 mod consts;
 pub mod err;
 
-use std::fs;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Error, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+pub use self::consts::SECTION_KEY;
 use self::consts::{
-    GUEST, GUEST_NICE, IDLE, IOWAIT, IRQ, NICE, PROC_STAT, SOFTIRQ, STEAL, SYSTEM, USER,
+    AGGREGATE_LABEL, BTIME_LABEL, CPU_PRESENT, CTXT_LABEL, GUEST, GUEST_NICE, IDLE, IOWAIT, IRQ,
+    NICE, PROCESSES_LABEL, PROCS_BLOCKED_LABEL, PROCS_RUNNING_LABEL, PROC_STAT, SOFTIRQ, STEAL,
+    SYSTEM, USER,
 };
 use self::err::CpuErr;
+use super::collector::schema::{array, integer, map, number, object, optional};
+use super::collector::Collector;
+use super::source::Source;
 
-#[derive(Clone, Serialize)]
+/// Jiffies spent in each mode
+#[derive(Clone, Serialize, Deserialize, Default, Debug, PartialEq)]
 pub struct CoreUsage {
-    user: u64,
-    nice: u64,
-    system: u64,
-    idle: u64,
-    iowait: u64,
-    irq: u64,
-    softirq: u64,
-    steal: u64,
-    guest: u64,
-    guest_nice: u64,
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+    pub guest: u64,
+    pub guest_nice: u64,
 }
 
-#[derive(Serialize)]
-pub struct CpuUsage {
-    pub usage: Vec<CoreUsage>,
-    last: Vec<CoreUsage>,
-}
+impl CoreUsage {
+    // Older kernels have fewer modes, the missing ones being 0
+    fn parse(fields: &[&str]) -> Result<Self> {
+        let field = |i: usize| fields.get(i).map_or(Ok(0), |f| f.parse::<u64>());
 
-impl CpuUsage {
-    pub fn new() -> Self {
-        return CpuUsage {
-            usage: Vec::new(),
-            last: Vec::new(),
+        Ok(CoreUsage {
+            user: field(USER)?,
+            nice: field(NICE)?,
+            system: field(SYSTEM)?,
+            idle: field(IDLE)?,
+            iowait: field(IOWAIT)?,
+            irq: field(IRQ)?,
+            softirq: field(SOFTIRQ)?,
+            steal: field(STEAL)?,
+            guest: field(GUEST)?,
+            guest_nice: field(GUEST_NICE)?,
+        })
+    }
+
+    // Counters can go backwards, e.g. the idle time of a core coming back
+    // online on some kernels
+    fn since(&self, old: &CoreUsage) -> CoreUsage {
+        CoreUsage {
+            user: self.user.saturating_sub(old.user),
+            nice: self.nice.saturating_sub(old.nice),
+            system: self.system.saturating_sub(old.system),
+            idle: self.idle.saturating_sub(old.idle),
+            iowait: self.iowait.saturating_sub(old.iowait),
+            irq: self.irq.saturating_sub(old.irq),
+            softirq: self.softirq.saturating_sub(old.softirq),
+            steal: self.steal.saturating_sub(old.steal),
+            guest: self.guest.saturating_sub(old.guest),
+            guest_nice: self.guest_nice.saturating_sub(old.guest_nice),
+        }
+    }
+
+    // Guest time is already counted in user time
+    fn total(&self) -> u64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+
+    /// Fraction of the time spent not idle
+    pub fn busy_ratio(&self) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 0.0;
+        }
+
+        (total - self.idle - self.iowait) as f64 / total as f64
+    }
+
+    pub fn percentages(&self) -> CpuPercentages {
+        let total = self.total();
+        let percent = |jiffies: u64| match total {
+            0 => 0.0,
+            _ => jiffies as f64 / total as f64 * 100.0,
         };
+
+        CpuPercentages {
+            busy: self.busy_ratio() * 100.0,
+            user: percent(self.user),
+            nice: percent(self.nice),
+            system: percent(self.system),
+            idle: percent(self.idle),
+            iowait: percent(self.iowait),
+            irq: percent(self.irq),
+            softirq: percent(self.softirq),
+            steal: percent(self.steal),
+        }
     }
+}
 
-    pub fn update(&mut self) -> Result<()> {
-        let first = self.last.is_empty();
+/// Share of the time spent in each mode, in percents
+#[derive(Clone, Serialize, Deserialize, Default, Debug)]
+pub struct CpuPercentages {
+    // Everything but idle and iowait
+    pub busy: f64,
+    pub user: f64,
+    pub nice: f64,
+    pub system: f64,
+    pub idle: f64,
+    pub iowait: f64,
+    pub irq: f64,
+    pub softirq: f64,
+    pub steal: f64,
+}
 
-        let mut cores_usage: Vec<CoreUsage> = vec![];
-        let proc_stat = fs::read_to_string((*PROC_STAT).as_str())?;
-        for line in proc_stat.lines() {
-            let split_line = line.split(" ").filter(|x| *x != "").collect::<Vec<&str>>();
+/// Load of all the cores or of a single one, since the previous run
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CpuLoad {
+    pub percent: CpuPercentages,
+    pub jiffies: CoreUsage,
+}
 
-            if split_line.len() != 11 || split_line[0] == "intr" {
-                break;
-            }
+impl CpuLoad {
+    fn of(jiffies: CoreUsage) -> Self {
+        CpuLoad {
+            percent: jiffies.percentages(),
+            jiffies,
+        }
+    }
+}
 
-            cores_usage.push(CoreUsage {
-                user: split_line[USER].parse::<u64>()?,
-                nice: split_line[NICE].parse::<u64>()?,
-                system: split_line[SYSTEM].parse::<u64>()?,
-                idle: split_line[IDLE].parse::<u64>()?,
-                iowait: split_line[IOWAIT].parse::<u64>()?,
-                irq: split_line[IRQ].parse::<u64>()?,
-                softirq: split_line[SOFTIRQ].parse::<u64>()?,
-                steal: split_line[STEAL].parse::<u64>()?,
-                guest: split_line[GUEST].parse::<u64>()?,
-                guest_nice: split_line[GUEST_NICE].parse::<u64>()?,
-            });
-
-            if first {
-                self.last.push(cores_usage[cores_usage.len() - 1].clone());
-            } else {
-                let i = cores_usage.len() - 1;
-
-                if self.last.len() <= i {
-                    return Err(Error::new(CpuErr::CoresChanged));
-                }
+/// The CPU section
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CpuData {
+    pub total: CpuLoad,
+    // Online cores, by CPU id
+    pub cores: BTreeMap<u32, CpuLoad>,
+    // Ids of the cores which are present but offline
+    pub offline: Vec<u32>,
+    // Rates since the previous run, None on the first one
+    pub context_switches_per_sec: Option<f64>,
+    pub forks_per_sec: Option<f64>,
+    pub procs_running: u64,
+    pub procs_blocked: u64,
+    // Unix timestamp, in seconds
+    pub boot_time: u64,
+}
 
-                let cur_cpu_usage = cores_usage[i].clone();
+// What /proc/stat holds at a given time
+#[derive(Default)]
+struct StatSnapshot {
+    total: CoreUsage,
+    cores: BTreeMap<u32, CoreUsage>,
+    context_switches: u64,
+    forks: u64,
+    procs_running: u64,
+    procs_blocked: u64,
+    boot_time: u64,
+    // Monotonic time of the reading
+    read_at: Duration,
+}
 
-                cores_usage[i].user -= self.last[i].user;
-                cores_usage[i].nice -= self.last[i].nice;
-                cores_usage[i].system -= self.last[i].system;
-                cores_usage[i].idle -= self.last[i].idle;
-                cores_usage[i].iowait -= self.last[i].iowait;
-                cores_usage[i].irq -= self.last[i].irq;
-                cores_usage[i].softirq -= self.last[i].softirq;
-                cores_usage[i].steal -= self.last[i].steal;
-                cores_usage[i].guest -= self.last[i].guest;
-                cores_usage[i].guest_nice -= self.last[i].guest_nice;
+impl StatSnapshot {
+    fn read(source: &dyn Source) -> Result<Self> {
+        let mut total = None;
+        let mut snapshot = StatSnapshot {
+            read_at: source.monotonic(),
+            ..Default::default()
+        };
 
-                self.last[i] = cur_cpu_usage;
+        let proc_stat = source.read_to_string(PROC_STAT)?;
+        for line in proc_stat.lines() {
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            let Some((label, values)) = fields.split_first() else {
+                continue;
+            };
+            let value = || -> Result<u64> {
+                Ok(values.first().copied().unwrap_or_default().parse::<u64>()?)
+            };
+
+            match *label {
+                AGGREGATE_LABEL => total = Some(CoreUsage::parse(&fields)?),
+                CTXT_LABEL => snapshot.context_switches = value()?,
+                BTIME_LABEL => snapshot.boot_time = value()?,
+                PROCESSES_LABEL => snapshot.forks = value()?,
+                PROCS_RUNNING_LABEL => snapshot.procs_running = value()?,
+                PROCS_BLOCKED_LABEL => snapshot.procs_blocked = value()?,
+                _ => {
+                    let Some(Ok(id)) = label
+                        .strip_prefix(AGGREGATE_LABEL)
+                        .map(|id| id.parse::<u32>())
+                    else {
+                        continue;
+                    };
+                    snapshot.cores.insert(id, CoreUsage::parse(&fields)?);
+                }
             }
         }
 
-        if self.last.len() != cores_usage.len() {
-            return Err(Error::new(CpuErr::CoresChanged));
-        }
+        snapshot.total = total.ok_or(CpuErr::MissingAggregate)?;
+        Ok(snapshot)
+    }
+}
+
+// Ids in the kernel's CPU list format, e.g. `0-3,6`
+fn parse_cpu_list(list: &str) -> Result<BTreeSet<u32>> {
+    let malformed = || Error::new(CpuErr::MalformedCpuList(list.to_string()));
+
+    let mut ids = BTreeSet::new();
+    for range in list.trim().split(',').filter(|r| !r.is_empty()) {
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        let first = first.parse::<u32>().map_err(|_| malformed())?;
+        let last = last.parse::<u32>().map_err(|_| malformed())?;
+        ids.extend(first..=last);
+    }
+
+    Ok(ids)
+}
+
+// Present cores, None where the kernel doesn't tell (e.g. in containers)
+fn present_cores(source: &dyn Source) -> Result<Option<BTreeSet<u32>>> {
+    match source.read_optional(CPU_PRESENT)? {
+        Some(list) => Ok(Some(parse_cpu_list(&list)?)),
+        None => Ok(None),
+    }
+}
+
+#[derive(Default)]
+pub struct CpuUsage {
+    pub data: Option<CpuData>,
+    last: Option<StatSnapshot>,
+    // Every core seen online, for the kernels not listing the present ones
+    seen: BTreeSet<u32>,
+}
 
-        self.usage = cores_usage;
+impl CpuUsage {
+    pub fn update(&mut self, source: &dyn Source) -> Result<()> {
+        let current = StatSnapshot::read(source)?;
+        self.seen.extend(current.cores.keys());
+        let present = present_cores(source)?.unwrap_or_else(|| self.seen.clone());
+
+        // Counters are since boot, so nothing is reported until there is a
+        // previous snapshot to compute the load from
+        let Some(last) = self.last.take() else {
+            self.last = Some(current);
+            self.data = None;
+            return Ok(());
+        };
+
+        // Cores seen for the first time, or coming back online, are only
+        // re-baselined, and left out until their next snapshot
+        let cores = current
+            .cores
+            .iter()
+            .filter_map(|(id, core)| {
+                let old = last.cores.get(id)?;
+                Some((*id, CpuLoad::of(core.since(old))))
+            })
+            .collect::<BTreeMap<u32, CpuLoad>>();
+
+        let offline = present
+            .iter()
+            .filter(|id| !current.cores.contains_key(id))
+            .copied()
+            .collect();
+
+        let elapsed = current.read_at.saturating_sub(last.read_at).as_secs_f64();
+        let rate = |current: u64, old: u64| match elapsed > 0.0 {
+            true => Some(current.saturating_sub(old) as f64 / elapsed),
+            false => None,
+        };
+
+        self.data = Some(CpuData {
+            total: CpuLoad::of(current.total.since(&last.total)),
+            cores,
+            offline,
+            context_switches_per_sec: rate(current.context_switches, last.context_switches),
+            forks_per_sec: rate(current.forks, last.forks),
+            procs_running: current.procs_running,
+            procs_blocked: current.procs_blocked,
+            boot_time: current.boot_time,
+        });
+        self.last = Some(current);
         Ok(())
     }
 }
+
+pub struct CpuCollector {
+    source: Arc<dyn Source>,
+    usage: CpuUsage,
+}
+
+impl CpuCollector {
+    pub fn new(source: Arc<dyn Source>) -> Self {
+        CpuCollector {
+            source,
+            usage: CpuUsage::default(),
+        }
+    }
+}
+
+impl Collector for CpuCollector {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn key(&self) -> &'static str {
+        SECTION_KEY
+    }
+
+    fn description(&self) -> &'static str {
+        "CPU load per mode, for all cores and each online core by id, and scheduler activity"
+    }
+
+    fn collect(&mut self) -> Result<Value> {
+        self.usage.update(self.source.as_ref())?;
+        Ok(serde_json::to_value(&self.usage.data)?)
+    }
+
+    fn schema(&self) -> Value {
+        let modes = [
+            "user",
+            "nice",
+            "system",
+            "idle",
+            "iowait",
+            "irq",
+            "softirq",
+            "steal",
+            "guest",
+            "guest_nice",
+        ];
+        // Guest time being part of user time, it has no percentage
+        let mut percent = vec![("busy", number())];
+        percent.extend(modes[..8].iter().map(|m| (*m, number())));
+        let load = object(&[
+            ("percent", object(&percent)),
+            ("jiffies", object(&modes.map(|m| (m, integer())))),
+        ]);
+
+        object(&[
+            ("total", load.clone()),
+            ("cores", map(load)),
+            ("offline", array(integer())),
+            ("context_switches_per_sec", optional(number())),
+            ("forks_per_sec", optional(number())),
+            ("procs_running", integer()),
+            ("procs_blocked", integer()),
+            ("boot_time", integer()),
+        ])
+    }
+
+    fn samples(&self, data: &Value) -> Vec<(String, f64)> {
+        let Ok(data) = CpuData::deserialize(data) else {
+            return Vec::new();
+        };
+
+        let mut samples = vec![(String::from("cpu.all.usage"), data.total.percent.busy)];
+        for (id, core) in data.cores.iter() {
+            samples.push((format!("cpu.{}.usage", id), core.percent.busy));
+        }
+        samples
+    }
+}
@@ -1,16 +1,7 @@
-use crate::status::DOCKER_PROC_DIR_ENV;
-
-use lazy_static::lazy_static;
-
-const PROC_STAT_DEFAULT: &str = "/proc/stat";
-
-lazy_static! {
-    pub static ref PROC_STAT: String = if let Ok(proc) = std::env::var(DOCKER_PROC_DIR_ENV) {
-        format!("{}/stat", proc)
-    } else {
-        String::from(PROC_STAT_DEFAULT)
-    };
-}
+pub const PROC_STAT: &str = "/proc/stat";
+// CPUs which can be brought online, e.g. `0-3`. Offline ones are missing from
+// /proc/stat
+pub const CPU_PRESENT: &str = "/sys/devices/system/cpu/present";
 
 pub const USER: usize = 1;
 pub const NICE: usize = 2;
@@ -22,3 +13,14 @@ pub const SOFTIRQ: usize = 7;
 pub const STEAL: usize = 8;
 pub const GUEST: usize = 9;
 pub const GUEST_NICE: usize = 10;
+
+// Key of the section in the serialized status
+pub const SECTION_KEY: &str = "cpu_usage";
+
+// Labels of the /proc/stat lines, core lines being `cpu<id>`
+pub const AGGREGATE_LABEL: &str = "cpu";
+pub const CTXT_LABEL: &str = "ctxt";
+pub const BTIME_LABEL: &str = "btime";
+pub const PROCESSES_LABEL: &str = "processes";
+pub const PROCS_RUNNING_LABEL: &str = "procs_running";
+pub const PROCS_BLOCKED_LABEL: &str = "procs_blocked";
@@ -2,7 +2,17 @@ use std::fmt;
 
 #[derive(Debug)]
 pub enum CpuErr {
-    CoresChanged,
+    MissingAggregate,
+    MalformedCpuList(String),
+}
+
+impl CpuErr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CpuErr::MissingAggregate => "missing_aggregate",
+            CpuErr::MalformedCpuList(_) => "malformed_cpu_list",
+        }
+    }
 }
 
 impl std::error::Error for CpuErr {}
@@ -10,7 +20,8 @@ impl std::error::Error for CpuErr {}
 impl fmt::Display for CpuErr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            CpuErr::CoresChanged => write!(f, "The number of cores changed"),
+            CpuErr::MissingAggregate => write!(f, "/proc/stat has no `cpu` line"),
+            CpuErr::MalformedCpuList(l) => write!(f, "Malformed CPU list `{}`", l),
         }
     }
 }
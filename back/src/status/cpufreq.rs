@@ -0,0 +1,294 @@
+mod consts;
+pub mod err;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use toml::Table;
+
+pub use self::consts::SECTION_KEY;
+use self::consts::{
+    AVAILABLE_GOVERNORS, CPU_DIR, CUR_FREQ, FREQUENCY_CAPPED, GOVERNOR, MAX_FREQ, MIN_FREQ,
+    MS_PER_TICK, SINCE_BOOT_SHIFT, SOFT_TEMP_LIMIT, THROTTLED, THROTTLED_PATHS, TIME_IN_STATE,
+    UNDER_VOLTAGE,
+};
+use self::err::CpuFreqErr;
+use super::collector::schema::{array, boolean, integer, map, object, optional, string};
+use super::collector::{self, Collector};
+use super::source::Source;
+
+/// Frequency scaling of a core, frequencies being in kHz
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CoreFreq {
+    pub cur_khz: u64,
+    pub min_khz: u64,
+    pub max_khz: u64,
+    pub governor: String,
+    pub available_governors: Vec<String>,
+    // Milliseconds spent at each frequency since the previous run, None on
+    // the first run of the core or when the kernel doesn't keep cpufreq stats
+    pub time_in_state_ms: Option<BTreeMap<u64, u64>>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct ThrottleFlags {
+    pub under_voltage: bool,
+    pub frequency_capped: bool,
+    pub throttled: bool,
+    pub soft_temp_limit: bool,
+}
+
+impl ThrottleFlags {
+    fn of(flags: u32) -> Self {
+        ThrottleFlags {
+            under_voltage: flags & UNDER_VOLTAGE != 0,
+            frequency_capped: flags & FREQUENCY_CAPPED != 0,
+            throttled: flags & THROTTLED != 0,
+            soft_temp_limit: flags & SOFT_TEMP_LIMIT != 0,
+        }
+    }
+}
+
+/// Raspberry Pi firmware throttling flags
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Throttling {
+    // As printed by `vcgencmd get_throttled`
+    pub flags: u32,
+    pub now: ThrottleFlags,
+    pub since_boot: ThrottleFlags,
+}
+
+impl Throttling {
+    fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let flags = u32::from_str_radix(text.trim_start_matches("0x"), 16)
+            .map_err(|_| Error::new(CpuFreqErr::MalformedThrottled(text.to_string())))?;
+
+        Ok(Throttling {
+            flags,
+            now: ThrottleFlags::of(flags),
+            since_boot: ThrottleFlags::of(flags >> SINCE_BOOT_SHIFT),
+        })
+    }
+}
+
+/// The CPU frequency section
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CpuFreqData {
+    // Cores with frequency scaling, by CPU id
+    pub cores: BTreeMap<u32, CoreFreq>,
+    // None on boards other than Raspberry Pis
+    pub throttling: Option<Throttling>,
+}
+
+// Time spent at each frequency since boot, in USER_HZ ticks
+fn parse_time_in_state(text: &str) -> Result<BTreeMap<u64, u64>> {
+    let mut times = BTreeMap::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let malformed = || Error::new(CpuFreqErr::MalformedTimeInState(line.to_string()));
+
+        let (freq, time) = line.split_once(' ').ok_or_else(malformed)?;
+        let freq = freq.trim().parse::<u64>().map_err(|_| malformed())?;
+        let time = time.trim().parse::<u64>().map_err(|_| malformed())?;
+        times.insert(freq, time);
+    }
+
+    Ok(times)
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct CpuFreqOptions {
+    // Host file with the throttling flags, the known locations being tried
+    // when not set
+    throttled_path: Option<String>,
+}
+
+pub struct CpuFreqCollector {
+    source: Arc<dyn Source>,
+    options: CpuFreqOptions,
+    // Last time_in_state of every core, in ticks
+    last_times: BTreeMap<u32, BTreeMap<u64, u64>>,
+}
+
+impl CpuFreqCollector {
+    pub fn new(source: Arc<dyn Source>) -> Self {
+        CpuFreqCollector {
+            source,
+            options: CpuFreqOptions::default(),
+            last_times: BTreeMap::new(),
+        }
+    }
+
+    // Cores whose frequency is scaled, offline ones having no cpufreq policy
+    fn core_ids(&self) -> Result<Vec<u32>> {
+        let entries = match self.source.read_dir(CPU_DIR) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut ids = entries
+            .iter()
+            .filter_map(|e| e.strip_prefix("cpu")?.parse::<u32>().ok())
+            .collect::<Vec<u32>>();
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn read_core(&mut self, id: u32) -> Result<Option<CoreFreq>> {
+        let source = self.source.as_ref();
+        let path = |file: &str| format!("{}/cpu{}/{}", CPU_DIR, id, file);
+        let khz = |file: &str| -> Result<u64> {
+            Ok(source.read_to_string(&path(file))?.trim().parse::<u64>()?)
+        };
+
+        let Some(cur) = source.read_optional(&path(CUR_FREQ))? else {
+            return Ok(None);
+        };
+
+        let time_in_state = match source.read_optional(&path(TIME_IN_STATE))? {
+            Some(text) => {
+                let times = parse_time_in_state(&text)?;
+                // Cores seen for the first time or coming back online are
+                // only re-baselined, the times being since boot
+                self.last_times.insert(id, times.clone()).map(|last| {
+                    times
+                        .iter()
+                        .map(|(freq, time)| {
+                            let since = time.saturating_sub(*last.get(freq).unwrap_or(&0));
+                            (*freq, since * MS_PER_TICK)
+                        })
+                        .collect()
+                })
+            }
+            None => None,
+        };
+
+        Ok(Some(CoreFreq {
+            cur_khz: cur.trim().parse::<u64>()?,
+            min_khz: khz(MIN_FREQ)?,
+            max_khz: khz(MAX_FREQ)?,
+            governor: source
+                .read_optional(&path(GOVERNOR))?
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+            available_governors: source
+                .read_optional(&path(AVAILABLE_GOVERNORS))?
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+            time_in_state_ms: time_in_state,
+        }))
+    }
+
+    fn throttling(&self) -> Result<Option<Throttling>> {
+        let paths = match &self.options.throttled_path {
+            Some(path) => vec![path.as_str()],
+            None => THROTTLED_PATHS.to_vec(),
+        };
+
+        for path in paths {
+            if let Some(text) = self.source.read_optional(path)? {
+                return Ok(Some(Throttling::parse(&text)?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Collector for CpuFreqCollector {
+    fn name(&self) -> &'static str {
+        "cpufreq"
+    }
+
+    fn key(&self) -> &'static str {
+        SECTION_KEY
+    }
+
+    fn description(&self) -> &'static str {
+        "CPU frequency scaling of each core by id, and the Raspberry Pi throttling flags"
+    }
+
+    fn configure(&mut self, options: &Table) -> Result<()> {
+        self.options = collector::options(self.name(), options)?;
+        Ok(())
+    }
+
+    fn collect(&mut self) -> Result<Value> {
+        let mut cores = BTreeMap::new();
+        for id in self.core_ids()? {
+            if let Some(core) = self.read_core(id)? {
+                cores.insert(id, core);
+            }
+        }
+        // Forgets the cores gone offline, so that they start over when they
+        // come back
+        self.last_times.retain(|id, _| cores.contains_key(id));
+
+        Ok(serde_json::to_value(CpuFreqData {
+            cores,
+            throttling: self.throttling()?,
+        })?)
+    }
+
+    fn schema(&self) -> Value {
+        let flags = object(&[
+            ("under_voltage", boolean()),
+            ("frequency_capped", boolean()),
+            ("throttled", boolean()),
+            ("soft_temp_limit", boolean()),
+        ]);
+
+        object(&[
+            (
+                "cores",
+                map(object(&[
+                    ("cur_khz", integer()),
+                    ("min_khz", integer()),
+                    ("max_khz", integer()),
+                    ("governor", string()),
+                    ("available_governors", array(string())),
+                    ("time_in_state_ms", optional(map(integer()))),
+                ])),
+            ),
+            (
+                "throttling",
+                optional(object(&[
+                    ("flags", integer()),
+                    ("now", flags.clone()),
+                    ("since_boot", flags),
+                ])),
+            ),
+        ])
+    }
+
+    fn samples(&self, data: &Value) -> Vec<(String, f64)> {
+        let Ok(data) = CpuFreqData::deserialize(data) else {
+            return Vec::new();
+        };
+
+        let mut samples = data
+            .cores
+            .iter()
+            .map(|(id, core)| (format!("cpufreq.{}.khz", id), core.cur_khz as f64))
+            .collect::<Vec<(String, f64)>>();
+        if let Some(throttling) = data.throttling {
+            samples.push((
+                String::from("cpufreq.throttled"),
+                throttling.now.throttled as u8 as f64,
+            ));
+            samples.push((
+                String::from("cpufreq.under_voltage"),
+                throttling.now.under_voltage as u8 as f64,
+            ));
+        }
+        samples
+    }
+}
@@ -0,0 +1,26 @@
+pub const CPU_DIR: &str = "/sys/devices/system/cpu";
+// Under the directory of every core
+pub const CUR_FREQ: &str = "cpufreq/scaling_cur_freq";
+pub const MIN_FREQ: &str = "cpufreq/scaling_min_freq";
+pub const MAX_FREQ: &str = "cpufreq/scaling_max_freq";
+pub const GOVERNOR: &str = "cpufreq/scaling_governor";
+pub const AVAILABLE_GOVERNORS: &str = "cpufreq/scaling_available_governors";
+// `<frequency in kHz> <time in USER_HZ ticks>` lines
+pub const TIME_IN_STATE: &str = "cpufreq/stats/time_in_state";
+pub const MS_PER_TICK: u64 = 10;
+
+// Raspberry Pi firmware throttling flags, as printed by
+// `vcgencmd get_throttled`, tried in order
+pub const THROTTLED_PATHS: [&str; 2] = [
+    "/sys/devices/platform/soc/soc:firmware/get_throttled",
+    "/sys/devices/platform/soc@107c000000/soc@107c000000:firmware/get_throttled",
+];
+pub const UNDER_VOLTAGE: u32 = 0x1;
+pub const FREQUENCY_CAPPED: u32 = 0x2;
+pub const THROTTLED: u32 = 0x4;
+pub const SOFT_TEMP_LIMIT: u32 = 0x8;
+// The same flags, for what happened since boot
+pub const SINCE_BOOT_SHIFT: u32 = 16;
+
+// Key of the section in the serialized status
+pub const SECTION_KEY: &str = "cpu_freq";
@@ -0,0 +1,31 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum CpuFreqErr {
+    MalformedThrottled(String),
+    MalformedTimeInState(String),
+}
+
+impl CpuFreqErr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CpuFreqErr::MalformedThrottled(_) => "malformed_throttled",
+            CpuFreqErr::MalformedTimeInState(_) => "malformed_time_in_state",
+        }
+    }
+}
+
+impl std::error::Error for CpuFreqErr {}
+
+impl fmt::Display for CpuFreqErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuFreqErr::MalformedThrottled(t) => {
+                write!(f, "Malformed throttling flags `{}`", t)
+            }
+            CpuFreqErr::MalformedTimeInState(l) => {
+                write!(f, "Malformed time_in_state line `{}`", l)
+            }
+        }
+    }
+}
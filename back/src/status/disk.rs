@@ -1,18 +1,32 @@
 mod consts;
 
-use nix::sys::statvfs::statvfs;
-use serde::Serialize;
-use std::fs;
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::io;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use toml::Table;
 
-use self::consts::{EXCLUDED_MOUNTS, FILESYSTEM, MOUNTPOINT, PROC_MOUNTS};
+pub use self::consts::SECTION_KEY;
+use self::consts::{
+    EXCLUDED_MOUNTS, FILESYSTEM, FS_TYPE, MAX_MOUNT_BACKOFF, MOUNTPOINT, MOUNT_BACKOFF,
+    MOUNT_TIMEOUT, PROC_MOUNTS, REMOTE_FS_TYPES,
+};
+use super::collector::schema::{array, integer, object, string};
+use super::collector::{self, Collector};
+use super::source::{FsStats, Source};
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct FsData {
-    filesystem: String,
-    mountpoint: String,
-    total: u64,
-    available: u64,
+    pub filesystem: String,
+    pub mountpoint: String,
+    pub total: u64,
+    pub available: u64,
 }
 
 #[derive(Serialize)]
@@ -20,10 +34,96 @@ pub struct DiskData {
     pub filesystems: Vec<FsData>,
 }
 
+#[derive(Default)]
+struct MountState {
+    // statvfs call which didn't return yet
+    pending: Option<Receiver<io::Result<FsStats>>>,
+    // Consecutive calls that timed out
+    hangs: u32,
+    retry_at: Option<Instant>,
+}
+
+/// Calls statvfs on network and FUSE mounts in a separate thread, since they
+/// block indefinitely when their server goes away. Mounts which hang are
+/// skipped for a while, longer every time
+#[derive(Default)]
+pub struct MountGuard {
+    mounts: HashMap<String, MountState>,
+}
+
+fn is_remote(fs_type: &str) -> bool {
+    REMOTE_FS_TYPES.contains(&fs_type) || fs_type.starts_with("fuse")
+}
+
+impl MountGuard {
+    fn statvfs(
+        &mut self,
+        source: &Arc<dyn Source>,
+        mountpoint: &str,
+        fs_type: &str,
+    ) -> Option<FsStats> {
+        if !is_remote(fs_type) {
+            return source.statvfs(mountpoint).ok();
+        }
+
+        let state = self.mounts.entry(mountpoint.to_string()).or_default();
+        if let Some(pending) = &state.pending {
+            match pending.try_recv() {
+                Err(TryRecvError::Empty) => return None,
+                _ => state.pending = None,
+            }
+        }
+
+        let now = Instant::now();
+        if state.retry_at.is_some_and(|r| now < r) {
+            return None;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let (source, path) = (source.clone(), mountpoint.to_string());
+        thread::spawn(move || {
+            let _ = sender.send(source.statvfs(&path));
+        });
+
+        match receiver.recv_timeout(MOUNT_TIMEOUT) {
+            Ok(stats) => {
+                state.hangs = 0;
+                state.retry_at = None;
+                stats.ok()
+            }
+            Err(_) => {
+                state.hangs = state.hangs.saturating_add(1);
+                let backoff = MOUNT_BACKOFF
+                    .saturating_mul(2u32.saturating_pow(state.hangs - 1))
+                    .min(MAX_MOUNT_BACKOFF);
+                warn!(
+                    "Mount {} is not answering, skipping it for {}s",
+                    mountpoint,
+                    backoff.as_secs()
+                );
+
+                state.retry_at = Some(now + backoff);
+                state.pending = Some(receiver);
+                None
+            }
+        }
+    }
+
+    // Forgets the mounts which are gone
+    fn retain(&mut self, mountpoints: &[&str]) {
+        self.mounts.retain(|m, _| mountpoints.contains(&m.as_str()));
+    }
+}
+
 impl DiskData {
-    pub fn get() -> Result<Self, io::Error> {
+    pub fn get(
+        source: &Arc<dyn Source>,
+        excluded_mounts: &[String],
+        guard: &mut MountGuard,
+    ) -> Result<Self, io::Error> {
         let mut filesystems = Vec::new();
-        let proc_mounts = fs::read_to_string((*PROC_MOUNTS).as_str())?;
+        let proc_mounts = source.read_to_string(PROC_MOUNTS)?;
+        let mut mountpoints = Vec::new();
 
         for l in proc_mounts.lines() {
             let split_mount = l.split_whitespace().collect::<Vec<&str>>();
@@ -31,25 +131,110 @@ impl DiskData {
                 continue;
             }
 
-            if EXCLUDED_MOUNTS
+            if excluded_mounts
                 .iter()
                 .any(|path| split_mount[MOUNTPOINT].starts_with(path))
             {
                 continue;
             }
 
-            let Ok(disk_stats) = statvfs(split_mount[1]) else {
+            let fs_type = split_mount.get(FS_TYPE).copied().unwrap_or_default();
+            mountpoints.push(split_mount[MOUNTPOINT]);
+            let Some(disk_stats) = guard.statvfs(source, split_mount[MOUNTPOINT], fs_type) else {
                 continue;
             };
 
             filesystems.push(FsData {
                 filesystem: String::from(split_mount[FILESYSTEM]),
                 mountpoint: String::from(split_mount[MOUNTPOINT]),
-                total: (disk_stats.block_size() * disk_stats.blocks()) as u64,
-                available: (disk_stats.block_size() * disk_stats.blocks_available()) as u64,
+                total: disk_stats.total,
+                available: disk_stats.available,
             })
         }
 
+        guard.retain(&mountpoints);
         return Ok(DiskData { filesystems });
     }
 }
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DiskOptions {
+    // Mount points starting with any of these are skipped
+    excluded_mounts: Vec<String>,
+}
+
+impl Default for DiskOptions {
+    fn default() -> Self {
+        DiskOptions {
+            excluded_mounts: EXCLUDED_MOUNTS.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+}
+
+pub struct DiskCollector {
+    source: Arc<dyn Source>,
+    options: DiskOptions,
+    guard: MountGuard,
+}
+
+impl DiskCollector {
+    pub fn new(source: Arc<dyn Source>) -> Self {
+        DiskCollector {
+            source,
+            options: DiskOptions::default(),
+            guard: MountGuard::default(),
+        }
+    }
+}
+
+impl Collector for DiskCollector {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn key(&self) -> &'static str {
+        SECTION_KEY
+    }
+
+    fn description(&self) -> &'static str {
+        "Usage of the mounted filesystems, in bytes"
+    }
+
+    fn configure(&mut self, options: &Table) -> Result<()> {
+        self.options = collector::options(self.name(), options)?;
+        Ok(())
+    }
+
+    fn collect(&mut self) -> Result<Value> {
+        let disk = DiskData::get(&self.source, &self.options.excluded_mounts, &mut self.guard)?;
+        Ok(serde_json::to_value(disk.filesystems)?)
+    }
+
+    fn schema(&self) -> Value {
+        array(object(&[
+            ("filesystem", string()),
+            ("mountpoint", string()),
+            ("total", integer()),
+            ("available", integer()),
+        ]))
+    }
+
+    fn samples(&self, data: &Value) -> Vec<(String, f64)> {
+        let mut samples = Vec::new();
+
+        for fs in Vec::<FsData>::deserialize(data).unwrap_or_default() {
+            samples.push((format!("disk.{}.total", fs.mountpoint), fs.total as f64));
+            samples.push((
+                format!("disk.{}.used", fs.mountpoint),
+                fs.total.saturating_sub(fs.available) as f64,
+            ));
+            samples.push((
+                format!("disk.{}.available", fs.mountpoint),
+                fs.available as f64,
+            ));
+        }
+
+        samples
+    }
+}
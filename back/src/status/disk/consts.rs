@@ -1,18 +1,29 @@
-use crate::status::DOCKER_MOUNTS_FILE_ENV;
+use std::time::Duration;
 
-use lazy_static::lazy_static;
-
-pub const PROC_MOUNTS_DEFAULT: &str = "/proc/mounts";
-
-lazy_static! {
-    pub static ref PROC_MOUNTS: String = if let Ok(mounts) = std::env::var(DOCKER_MOUNTS_FILE_ENV) {
-        mounts
-    } else {
-        String::from(PROC_MOUNTS_DEFAULT)
-    };
-}
+pub const PROC_MOUNTS: &str = "/proc/mounts";
 
 pub const FILESYSTEM: usize = 0;
 pub const MOUNTPOINT: usize = 1;
+pub const FS_TYPE: usize = 2;
 
 pub const EXCLUDED_MOUNTS: &[&str; 6] = &["/proc", "/sys", "/run", "/dev", "/tmp", "/var"];
+
+// Filesystems whose statvfs can hang, FUSE ones (`fuse.*`) included
+pub const REMOTE_FS_TYPES: &[&str; 9] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "ceph",
+    "glusterfs",
+    "afs",
+];
+pub const MOUNT_TIMEOUT: Duration = Duration::from_millis(500);
+// Doubled on every consecutive hang
+pub const MOUNT_BACKOFF: Duration = Duration::from_secs(10);
+pub const MAX_MOUNT_BACKOFF: Duration = Duration::from_secs(600);
+
+// Key of the section in the serialized status
+pub const SECTION_KEY: &str = "disk";
@@ -0,0 +1,145 @@
+use std::io;
+use std::num::{ParseFloatError, ParseIntError};
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use log::{error, info};
+use serde::Serialize;
+
+use super::collector::err::CollectorErr;
+use super::cpu::err::CpuErr;
+use super::cpufreq::err::CpuFreqErr;
+use super::host::err::HostErr;
+use super::load::err::LoadErr;
+use super::net::err::NetDataUpdateError;
+use super::now_secs;
+use super::proc::err::{ProcDataCreationErr, ProcDataRetrievalErr};
+use super::ram::err::MemRetrievalErr;
+
+// A repeating error is logged again at most this often
+const ERROR_LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Current error of a collector, as sent in the `errors` section
+#[derive(Serialize, Clone, Debug)]
+pub struct CollectorError {
+    pub kind: String,
+    pub message: String,
+    // Unix timestamps, in seconds
+    pub first_seen: u64,
+    pub last_seen: u64,
+    // Consecutive failed runs with this error
+    pub count: u64,
+}
+
+// CamelCase to snake_case, for io::ErrorKind names
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// Short machine readable name of an error, from the collectors' error types
+/// or the underlying I/O and parsing errors
+pub fn kind_of(e: &Error) -> String {
+    let kind = if let Some(e) = e.downcast_ref::<CpuErr>() {
+        e.kind()
+    } else if let Some(e) = e.downcast_ref::<CpuFreqErr>() {
+        e.kind()
+    } else if let Some(e) = e.downcast_ref::<HostErr>() {
+        e.kind()
+    } else if let Some(e) = e.downcast_ref::<LoadErr>() {
+        e.kind()
+    } else if let Some(e) = e.downcast_ref::<MemRetrievalErr>() {
+        e.kind()
+    } else if let Some(e) = e.downcast_ref::<NetDataUpdateError>() {
+        e.kind()
+    } else if let Some(e) = e.downcast_ref::<ProcDataCreationErr>() {
+        e.kind()
+    } else if let Some(e) = e.downcast_ref::<ProcDataRetrievalErr>() {
+        e.kind()
+    } else if let Some(e) = e.downcast_ref::<CollectorErr>() {
+        e.kind()
+    } else if let Some(e) = e.downcast_ref::<io::Error>() {
+        return snake_case(&format!("{:?}", e.kind()));
+    } else if e.is::<ParseIntError>() || e.is::<ParseFloatError>() {
+        "parse"
+    } else {
+        "other"
+    };
+
+    kind.to_string()
+}
+
+/// Follows the errors of a collector, logging an error when it shows up and
+/// then only a summary of its repetitions every ERROR_LOG_INTERVAL
+#[derive(Default)]
+pub struct ErrorTracker {
+    current: Option<CollectorError>,
+    last_logged: Option<Instant>,
+    // Repetitions since the last log line
+    unlogged: u64,
+}
+
+impl ErrorTracker {
+    pub fn fail(&mut self, collector: &str, e: &Error, log: bool) -> CollectorError {
+        let kind = kind_of(e);
+        let message = e.to_string();
+        let now = now_secs();
+
+        match &mut self.current {
+            Some(c) if c.kind == kind && c.message == message => {
+                c.count += 1;
+                c.last_seen = now;
+                self.unlogged += 1;
+
+                if log
+                    && self
+                        .last_logged
+                        .is_none_or(|l| l.elapsed() >= ERROR_LOG_INTERVAL)
+                {
+                    error!(
+                        "Could not get {} data: {} (repeated {} times, failing since {}s)",
+                        collector,
+                        message,
+                        self.unlogged,
+                        now.saturating_sub(c.first_seen)
+                    );
+                    self.last_logged = Some(Instant::now());
+                    self.unlogged = 0;
+                }
+            }
+            _ => {
+                if log {
+                    error!("Could not get {} data: {}", collector, message);
+                }
+                self.current = Some(CollectorError {
+                    kind,
+                    message,
+                    first_seen: now,
+                    last_seen: now,
+                    count: 1,
+                });
+                self.last_logged = Some(Instant::now());
+                self.unlogged = 0;
+            }
+        }
+
+        self.current.clone().unwrap()
+    }
+
+    pub fn recover(&mut self, collector: &str, log: bool) {
+        if let Some(c) = self.current.take() {
+            if log {
+                info!(
+                    "The {} collector recovered after {} failed runs",
+                    collector, c.count
+                );
+            }
+        }
+    }
+}
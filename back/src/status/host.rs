@@ -1,31 +1,36 @@
 mod consts;
 pub mod err;
 
-use std::fs;
+use std::sync::Arc;
 
 use anyhow::{Error, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+pub use self::consts::SECTION_KEY;
 use self::consts::{HOST_PATH, UPTIME_PATH};
 use self::err::HostErr;
+use super::collector::schema::{integer, object, string};
+use super::collector::Collector;
+use super::source::Source;
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct HostData {
-    hostname: String,
-    uptime: u64,
+    pub hostname: String,
+    pub uptime: u64,
 }
 
 impl HostData {
-    pub fn get() -> Result<Self> {
+    pub fn get(source: &dyn Source) -> Result<Self> {
         let hostname;
         let uptime;
 
-        match HostData::get_hostname() {
+        match HostData::get_hostname(source) {
             Ok(h) => hostname = h,
             Err(e) => return Err(e),
         }
 
-        match HostData::get_uptime() {
+        match HostData::get_uptime(source) {
             Ok(u) => uptime = u,
             Err(e) => return Err(e),
         }
@@ -33,16 +38,14 @@ impl HostData {
         return Ok(HostData { hostname, uptime });
     }
 
-    fn get_hostname() -> Result<String> {
-        let hostname = fs::read_to_string((*HOST_PATH).as_str())?
-            .trim_end()
-            .to_string();
+    fn get_hostname(source: &dyn Source) -> Result<String> {
+        let hostname = source.read_to_string(HOST_PATH)?.trim_end().to_string();
 
         return Ok(hostname);
     }
 
-    fn get_uptime() -> Result<u64> {
-        let uptime_unparsed = fs::read_to_string((*UPTIME_PATH).as_str())?;
+    fn get_uptime(source: &dyn Source) -> Result<u64> {
+        let uptime_unparsed = source.read_to_string(UPTIME_PATH)?;
         let uptime = uptime_unparsed.split(".").nth(0);
 
         match uptime {
@@ -51,3 +54,43 @@ impl HostData {
         }
     }
 }
+
+pub struct HostCollector {
+    source: Arc<dyn Source>,
+}
+
+impl HostCollector {
+    pub fn new(source: Arc<dyn Source>) -> Self {
+        HostCollector { source }
+    }
+}
+
+impl Collector for HostCollector {
+    fn name(&self) -> &'static str {
+        "host"
+    }
+
+    fn key(&self) -> &'static str {
+        SECTION_KEY
+    }
+
+    fn description(&self) -> &'static str {
+        "Hostname and uptime"
+    }
+
+    fn collect(&mut self) -> Result<Value> {
+        Ok(serde_json::to_value(HostData::get(self.source.as_ref())?)?)
+    }
+
+    fn schema(&self) -> Value {
+        object(&[("hostname", string()), ("uptime", integer())])
+    }
+
+    fn samples(&self, data: &Value) -> Vec<(String, f64)> {
+        let Ok(host) = HostData::deserialize(data) else {
+            return Vec::new();
+        };
+
+        vec![(String::from("host.uptime"), host.uptime as f64)]
+    }
+}
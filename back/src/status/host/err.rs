@@ -5,6 +5,14 @@ pub enum HostErr {
     MalformedUptimeFile,
 }
 
+impl HostErr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HostErr::MalformedUptimeFile => "malformed_uptime_file",
+        }
+    }
+}
+
 impl std::error::Error for HostErr {}
 
 impl fmt::Display for HostErr {
@@ -0,0 +1,268 @@
+mod consts;
+pub mod err;
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub use self::consts::SECTION_KEY;
+use self::consts::{CPU, IO, MEMORY, PRESSURE_DIR, PROC_LOADAVG};
+use self::err::LoadErr;
+use super::collector::schema::{integer, number, object, optional};
+use super::collector::Collector;
+use super::source::Source;
+
+/// Share of the time some (or all) non-idle tasks were stalled waiting for a
+/// resource
+#[derive(Clone, Serialize, Deserialize, Default, Debug)]
+pub struct Stall {
+    // In percents, over the last 10 seconds, minute and 5 minutes
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+    // Microseconds stalled since boot
+    pub total_us: u64,
+    // Microseconds stalled since the previous run, None on the first one
+    pub stalled_us: Option<u64>,
+}
+
+impl Stall {
+    // `some avg10=0.00 avg60=0.00 avg300=0.00 total=0`, without the first
+    // word
+    fn parse(line: &str, fields: &[&str]) -> Result<Self> {
+        let malformed = || Error::new(LoadErr::MalformedPressure(line.to_string()));
+
+        let mut stall = Stall::default();
+        for field in fields {
+            let (name, value) = field.split_once('=').ok_or_else(malformed)?;
+            match name {
+                "avg10" => stall.avg10 = value.parse::<f64>().map_err(|_| malformed())?,
+                "avg60" => stall.avg60 = value.parse::<f64>().map_err(|_| malformed())?,
+                "avg300" => stall.avg300 = value.parse::<f64>().map_err(|_| malformed())?,
+                "total" => stall.total_us = value.parse::<u64>().map_err(|_| malformed())?,
+                _ => {}
+            }
+        }
+
+        Ok(stall)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ResourcePressure {
+    pub some: Stall,
+    // Missing for the CPU before Linux 5.13
+    pub full: Option<Stall>,
+}
+
+impl ResourcePressure {
+    fn parse(text: &str) -> Result<Self> {
+        let mut some = None;
+        let mut full = None;
+        for line in text.lines() {
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            match fields.split_first() {
+                Some((&"some", fields)) => some = Some(Stall::parse(line, fields)?),
+                Some((&"full", fields)) => full = Some(Stall::parse(line, fields)?),
+                _ => {}
+            }
+        }
+
+        Ok(ResourcePressure {
+            some: some.ok_or_else(|| Error::new(LoadErr::MalformedPressure(text.to_string())))?,
+            full,
+        })
+    }
+
+    pub fn stalls(&self) -> impl Iterator<Item = (&'static str, &Stall)> {
+        [("some", Some(&self.some)), ("full", self.full.as_ref())]
+            .into_iter()
+            .filter_map(|(kind, stall)| Some((kind, stall?)))
+    }
+}
+
+/// Pressure stall information, telling CPU saturation from memory and I/O
+/// stalls
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Pressure {
+    pub cpu: ResourcePressure,
+    pub memory: ResourcePressure,
+    pub io: ResourcePressure,
+}
+
+impl Pressure {
+    pub fn resources(&self) -> [(&'static str, &ResourcePressure); 3] {
+        [(CPU, &self.cpu), (MEMORY, &self.memory), (IO, &self.io)]
+    }
+}
+
+/// The load section
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct LoadData {
+    // Average number of runnable or uninterruptible tasks over the last 1, 5
+    // and 15 minutes
+    pub load1: f64,
+    pub load5: f64,
+    pub load15: f64,
+    pub runnable: u64,
+    pub tasks: u64,
+    pub last_pid: u64,
+    // None when the kernel has PSI disabled
+    pub pressure: Option<Pressure>,
+}
+
+// `0.52 0.58 0.59 2/345 12345`
+fn parse_loadavg(text: &str) -> Result<LoadData> {
+    let text = text.trim();
+    let malformed = || Error::new(LoadErr::MalformedLoadavg(text.to_string()));
+
+    let fields = text.split_whitespace().collect::<Vec<&str>>();
+    let [load1, load5, load15, tasks, last_pid] = fields[..] else {
+        return Err(malformed());
+    };
+    let (runnable, tasks) = tasks.split_once('/').ok_or_else(malformed)?;
+
+    Ok(LoadData {
+        load1: load1.parse::<f64>()?,
+        load5: load5.parse::<f64>()?,
+        load15: load15.parse::<f64>()?,
+        runnable: runnable.parse::<u64>()?,
+        tasks: tasks.parse::<u64>()?,
+        last_pid: last_pid.parse::<u64>()?,
+        pressure: None,
+    })
+}
+
+// None when PSI is disabled: the files are missing without CONFIG_PSI, and
+// can't be read when booting with `psi=0`
+fn read_pressure(source: &dyn Source, resource: &str) -> Result<Option<ResourcePressure>> {
+    match source.read_to_string(&format!("{}/{}", PRESSURE_DIR, resource)) {
+        Ok(text) => Ok(Some(ResourcePressure::parse(&text)?)),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::Unsupported
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub struct LoadCollector {
+    source: Arc<dyn Source>,
+    // Last stall totals, by `<resource>.<some|full>`
+    last_totals: HashMap<String, u64>,
+}
+
+impl LoadCollector {
+    pub fn new(source: Arc<dyn Source>) -> Self {
+        LoadCollector {
+            source,
+            last_totals: HashMap::new(),
+        }
+    }
+
+    fn pressure(&mut self) -> Result<Option<Pressure>> {
+        let source = self.source.as_ref();
+        let (Some(cpu), Some(memory), Some(io)) = (
+            read_pressure(source, CPU)?,
+            read_pressure(source, MEMORY)?,
+            read_pressure(source, IO)?,
+        ) else {
+            return Ok(None);
+        };
+        let mut pressure = Pressure { cpu, memory, io };
+
+        // Time stalled since the previous run, from the totals since boot
+        for (resource, stalls) in [
+            (CPU, &mut pressure.cpu),
+            (MEMORY, &mut pressure.memory),
+            (IO, &mut pressure.io),
+        ] {
+            let mut since_last = |kind: &str, stall: &mut Stall| {
+                let key = format!("{}.{}", resource, kind);
+                let last = self.last_totals.insert(key, stall.total_us);
+                stall.stalled_us = last.map(|l| stall.total_us.saturating_sub(l));
+            };
+            since_last("some", &mut stalls.some);
+            if let Some(full) = stalls.full.as_mut() {
+                since_last("full", full);
+            }
+        }
+
+        Ok(Some(pressure))
+    }
+}
+
+impl Collector for LoadCollector {
+    fn name(&self) -> &'static str {
+        "load"
+    }
+
+    fn key(&self) -> &'static str {
+        SECTION_KEY
+    }
+
+    fn description(&self) -> &'static str {
+        "Load averages, and the pressure stall information of the CPU, memory and I/O"
+    }
+
+    fn collect(&mut self) -> Result<Value> {
+        let mut load = parse_loadavg(&self.source.read_to_string(PROC_LOADAVG)?)?;
+        load.pressure = self.pressure()?;
+
+        Ok(serde_json::to_value(load)?)
+    }
+
+    fn schema(&self) -> Value {
+        let stall = object(&[
+            ("avg10", number()),
+            ("avg60", number()),
+            ("avg300", number()),
+            ("total_us", integer()),
+            ("stalled_us", optional(integer())),
+        ]);
+        let resource = object(&[("some", stall.clone()), ("full", optional(stall))]);
+
+        object(&[
+            ("load1", number()),
+            ("load5", number()),
+            ("load15", number()),
+            ("runnable", integer()),
+            ("tasks", integer()),
+            ("last_pid", integer()),
+            (
+                "pressure",
+                optional(object(&[
+                    ("cpu", resource.clone()),
+                    ("memory", resource.clone()),
+                    ("io", resource),
+                ])),
+            ),
+        ])
+    }
+
+    fn samples(&self, data: &Value) -> Vec<(String, f64)> {
+        let Ok(data) = LoadData::deserialize(data) else {
+            return Vec::new();
+        };
+
+        let mut samples = vec![
+            (String::from("load.1"), data.load1),
+            (String::from("load.5"), data.load5),
+            (String::from("load.15"), data.load15),
+        ];
+        for (resource, stalls) in data.pressure.iter().flat_map(|p| p.resources()) {
+            for (kind, stall) in stalls.stalls() {
+                samples.push((format!("load.pressure.{}.{}", resource, kind), stall.avg10));
+            }
+        }
+        samples
+    }
+}
@@ -0,0 +1,9 @@
+pub const PROC_LOADAVG: &str = "/proc/loadavg";
+// One file per resource, missing or unreadable when PSI is disabled
+pub const PRESSURE_DIR: &str = "/proc/pressure";
+pub const CPU: &str = "cpu";
+pub const MEMORY: &str = "memory";
+pub const IO: &str = "io";
+
+// Key of the section in the serialized status
+pub const SECTION_KEY: &str = "load";
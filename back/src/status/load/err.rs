@@ -0,0 +1,27 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum LoadErr {
+    MalformedLoadavg(String),
+    MalformedPressure(String),
+}
+
+impl LoadErr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LoadErr::MalformedLoadavg(_) => "malformed_loadavg",
+            LoadErr::MalformedPressure(_) => "malformed_pressure",
+        }
+    }
+}
+
+impl std::error::Error for LoadErr {}
+
+impl fmt::Display for LoadErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadErr::MalformedLoadavg(l) => write!(f, "Malformed /proc/loadavg `{}`", l),
+            LoadErr::MalformedPressure(l) => write!(f, "Malformed pressure line `{}`", l),
+        }
+    }
+}
@@ -2,16 +2,21 @@ mod consts;
 pub mod err;
 
 use std::collections::HashMap;
-use std::fs;
-use std::time::UNIX_EPOCH;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Error, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+pub use self::consts::SECTION_KEY;
 use self::consts::{NET_DIR, RX_DIR, TX_DIR};
 use self::err::NetDataUpdateError;
+use super::collector::schema::{array, boolean, integer, number, object, string};
+use super::collector::Collector;
+use super::source::Source;
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct IfaStats {
     pub interface: NetworkInterface,
     pub upload_total: u64,
@@ -20,21 +25,23 @@ pub struct IfaStats {
     pub download_speed: f64,
     pub timestamp: u128,
     pub has_updated: bool,
+    // Monotonic time of the reading, speeds being computed from it
+    #[serde(skip)]
+    pub elapsed: Duration,
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct NetworkInterface {
-    interface_path: String,
-    interface_name: String,
+    pub interface_path: String,
+    pub interface_name: String,
 }
 
 pub struct NetData {
     pub stats: HashMap<String, IfaStats>,
-    old_stats: HashMap<String, IfaStats>,
 }
 
-fn u64_from_file(path: String) -> Result<u64> {
-    let file_content = fs::read_to_string(path)?;
+fn u64_from_file(source: &dyn Source, path: String) -> Result<u64> {
+    let file_content = source.read_to_string(&path)?;
     let num = file_content.replace("\n", "").parse::<u64>()?;
 
     return Ok(num);
@@ -44,19 +51,18 @@ impl NetData {
     pub fn new() -> Self {
         NetData {
             stats: HashMap::new(),
-            old_stats: HashMap::new(),
         }
     }
 
-    pub fn update(&mut self) -> Result<()> {
-        let current_interfaces = match Self::get_interfaces() {
+    pub fn update(&mut self, source: &dyn Source) -> Result<()> {
+        let current_interfaces = match Self::get_interfaces(source) {
             Ok(i) => i,
             Err(e) => return Err(Error::new(NetDataUpdateError::NoInterfaces(e))),
         };
 
         let mut new_stats: HashMap<String, IfaStats> = HashMap::new();
         for interface in current_interfaces {
-            match Self::get_ifa_stats(&interface) {
+            match Self::get_ifa_stats(source, &interface) {
                 Ok(s) => {
                     new_stats.insert(interface.interface_name, s);
                 }
@@ -64,7 +70,8 @@ impl NetData {
             }
         }
 
-        for (old_ifa, old_stats) in &self.old_stats {
+        // Speeds are computed against the previous update
+        for (old_ifa, old_stats) in &self.stats {
             let new_ifa_stats = match new_stats.get(old_ifa) {
                 Some(s) => s,
                 None => {
@@ -78,6 +85,7 @@ impl NetData {
                             timestamp: old_stats.timestamp,
                             upload_speed: old_stats.upload_speed,
                             upload_total: old_stats.upload_total,
+                            elapsed: old_stats.elapsed,
                         },
                     );
                     continue;
@@ -90,18 +98,19 @@ impl NetData {
             );
         }
 
-        self.old_stats = self.stats.clone();
         self.stats = new_stats;
 
         Ok(())
     }
 
-    fn get_ifa_stats(interface: &NetworkInterface) -> Result<IfaStats> {
-        let timestamp = UNIX_EPOCH.elapsed().unwrap().as_millis();
+    fn get_ifa_stats(source: &dyn Source, interface: &NetworkInterface) -> Result<IfaStats> {
+        let timestamp = source.now_millis();
+        let elapsed = source.monotonic();
+        let path = &interface.interface_path;
 
         return Ok(IfaStats {
-            upload_total: u64_from_file(format!("{}/{}", interface.interface_path, RX_DIR))?,
-            download_total: u64_from_file(format!("{}/{}", interface.interface_path, TX_DIR))?,
+            upload_total: u64_from_file(source, format!("{}/{}", path, RX_DIR))?,
+            download_total: u64_from_file(source, format!("{}/{}", path, TX_DIR))?,
 
             download_speed: 0.0,
             upload_speed: 0.0,
@@ -109,57 +118,39 @@ impl NetData {
             interface: interface.clone(),
             timestamp,
             has_updated: true,
+            elapsed,
         });
     }
 
+    // Counters going backwards (e.g. an interface that was recreated) and
+    // readings made at the same time give no speed
     fn compute_speed(current: &IfaStats, old: &IfaStats) -> IfaStats {
-        let elapsed = (current.timestamp - old.timestamp) as f64;
+        let elapsed = current.elapsed.saturating_sub(old.elapsed).as_secs_f64() * 1e3;
+        let speed = |current: u64, old: u64| match elapsed > 0.0 {
+            true => (current.saturating_sub(old) as f64 / elapsed * 1024.0).round(),
+            false => 0.0,
+        };
 
         return IfaStats {
             interface: old.interface.to_owned(),
             upload_total: current.upload_total,
             download_total: current.download_total,
 
-            upload_speed: (((current.upload_total - old.upload_total) as f64 / elapsed) * 1024.0)
-                .round(),
-            download_speed: (((current.download_total - old.download_total) as f64 / elapsed)
-                * 1024.0)
-                .round(),
+            upload_speed: speed(current.upload_total, old.upload_total),
+            download_speed: speed(current.download_total, old.download_total),
             timestamp: current.timestamp,
             has_updated: current.has_updated,
+            elapsed: current.elapsed,
         };
     }
 
-    fn get_interfaces() -> Result<Vec<NetworkInterface>> {
-        let mut interfaces_entries: Vec<fs::DirEntry> = Vec::new();
+    // Entries which aren't interfaces, like `bonding_masters`, are skipped
+    // later on since they have no statistics
+    fn get_interfaces(source: &dyn Source) -> Result<Vec<NetworkInterface>> {
         let mut interfaces: Vec<NetworkInterface> = Vec::new();
 
-        let files = fs::read_dir((*NET_DIR).as_str())?;
-        for entry_res in files {
-            let dir_entry: fs::DirEntry;
-            match entry_res {
-                Ok(d) => dir_entry = d,
-                Err(_) => continue,
-            };
-
-            if !dir_entry.metadata()?.is_file() {
-                interfaces_entries.push(dir_entry)
-            }
-        }
-
-        for interface in interfaces_entries {
-            let interface_path = interface.path();
-            let Some(interface_name) = interface_path.file_name() else {
-                continue;
-            };
-            let Some(interface_name) = interface_name.to_str() else {
-                continue;
-            };
-            let Some(interface_path) = interface_path.to_str() else {
-                continue;
-            };
-
-            let interface_path = String::from(interface_path);
+        for interface_name in source.read_dir(NET_DIR)? {
+            let interface_path = format!("{}/{}", NET_DIR, interface_name);
             if interfaces
                 .iter()
                 .map(|x| x.interface_path.clone())
@@ -171,10 +162,77 @@ impl NetData {
 
             interfaces.push(NetworkInterface {
                 interface_path: interface_path,
-                interface_name: String::from(interface_name),
+                interface_name,
             });
         }
 
         return Ok(interfaces);
     }
 }
+
+pub struct NetCollector {
+    source: Arc<dyn Source>,
+    data: NetData,
+}
+
+impl NetCollector {
+    pub fn new(source: Arc<dyn Source>) -> Self {
+        NetCollector {
+            source,
+            data: NetData::new(),
+        }
+    }
+}
+
+impl Collector for NetCollector {
+    fn name(&self) -> &'static str {
+        "net"
+    }
+
+    fn key(&self) -> &'static str {
+        SECTION_KEY
+    }
+
+    fn description(&self) -> &'static str {
+        "Traffic of every network interface"
+    }
+
+    fn collect(&mut self) -> Result<Value> {
+        self.data.update(self.source.as_ref())?;
+
+        let stats = self
+            .data
+            .stats
+            .values()
+            .filter(|x| x.has_updated)
+            .collect::<Vec<&IfaStats>>();
+        Ok(serde_json::to_value(stats)?)
+    }
+
+    fn schema(&self) -> Value {
+        array(object(&[
+            (
+                "interface",
+                object(&[("interface_path", string()), ("interface_name", string())]),
+            ),
+            ("upload_total", integer()),
+            ("download_total", integer()),
+            ("upload_speed", number()),
+            ("download_speed", number()),
+            ("timestamp", integer()),
+            ("has_updated", boolean()),
+        ]))
+    }
+
+    fn samples(&self, data: &Value) -> Vec<(String, f64)> {
+        let mut samples = Vec::new();
+
+        for ifa in Vec::<IfaStats>::deserialize(data).unwrap_or_default() {
+            let name = &ifa.interface.interface_name;
+            samples.push((format!("net.{}.download_speed", name), ifa.download_speed));
+            samples.push((format!("net.{}.upload_speed", name), ifa.upload_speed));
+        }
+
+        samples
+    }
+}
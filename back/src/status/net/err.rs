@@ -6,6 +6,14 @@ pub enum NetDataUpdateError {
     NoInterfaces(Error),
 }
 
+impl NetDataUpdateError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NetDataUpdateError::NoInterfaces(_) => "no_interfaces",
+        }
+    }
+}
+
 impl std::error::Error for NetDataUpdateError {}
 
 impl fmt::Display for NetDataUpdateError {
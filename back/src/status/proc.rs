@@ -1,87 +1,85 @@
 mod consts;
 pub mod err;
 
+pub use self::consts::SECTION_KEY;
 use self::consts::{
     NAME, PID, POSSIBLE_STATES, PROC_DIR, PROC_PID_RE, RSS, START_TIME, SYSTEM_TIME, THREADS,
-    USER_TIME,
+    USER_HZ, USER_TIME,
 };
-use self::err::{ProcDataCreationErr, ProcDataRetrievalErr};
+use self::err::ProcDataRetrievalErr;
 
 use std::collections::HashMap;
-use std::fs;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use nix::unistd;
 use rayon::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use anyhow::{Error, Result};
 
-#[derive(Serialize, Clone)]
+use super::collector::err::CollectorErr;
+use super::collector::schema::{array, integer, number, object, optional, string};
+use super::collector::Collector;
+use super::source::Source;
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Process {
-    pid: u64,
-    name: String,
-    mem: u64,
-    threads: u16,
-    cpu_usage: u64,
-    start_time: u64,
+    pub pid: u64,
+    pub name: String,
+    pub mem: u64,
+    pub threads: u16,
+    // Share of a CPU used since the previous run, in percents, None on the
+    // first run
+    pub cpu_usage: Option<f64>,
+    pub start_time: u64,
 }
 
 pub struct ProcessData {
     pub processes: Vec<Process>,
     old_processes_map: HashMap<(u64, u64), u64>,
+    // Monotonic time of the previous run
+    last_update: Option<Duration>,
     page_size: u64,
 }
 
 impl ProcessData {
-    pub fn new() -> Result<Self> {
-        let page_size: u64;
-        match unistd::sysconf(unistd::SysconfVar::PAGE_SIZE) {
-            Ok(o) => {
-                if let Some(ps) = o {
-                    page_size = ps as u64
-                } else {
-                    return Err(Error::new(ProcDataCreationErr::PageSizeEmpty));
-                }
-            }
-            Err(e) => return Err(Error::new(ProcDataCreationErr::PageSizeErr(e))),
-        }
+    pub fn new(source: &dyn Source) -> Result<Self> {
+        let page_size = source.page_size()?;
 
         return Ok(ProcessData {
             processes: Vec::new(),
             old_processes_map: HashMap::new(),
+            last_update: None,
             page_size,
         });
     }
 
-    pub fn update(&mut self) -> Result<()> {
+    pub fn update(&mut self, source: &dyn Source) -> Result<()> {
         let new_processes_arc = Arc::new(Mutex::new(HashMap::new()));
 
         let processes_arc: Arc<Mutex<Vec<Process>>> = Arc::new(Mutex::new(Vec::new()));
-        let files = fs::read_dir((*PROC_DIR).as_str())?
-            .into_iter()
-            .filter_map(Result::ok)
-            .collect::<Vec<fs::DirEntry>>();
+        let files = source.read_dir(PROC_DIR)?;
+
+        let now = source.monotonic();
+        let elapsed = self
+            .last_update
+            .map(|l| now.saturating_sub(l).as_secs_f64())
+            .filter(|e| *e > 0.0);
 
         files.par_iter().for_each(|pid| {
             let pid_dir: String;
 
             match Self::validate_pid_dir(pid) {
-                Ok(d) => {
-                    if let Some(dir_str) = d.path().to_str() {
-                        pid_dir = dir_str.to_string();
-                    } else {
-                        return;
-                    }
-                }
+                Ok(d) => pid_dir = format!("{}/{}", PROC_DIR, d),
                 Err(_) => return,
             }
 
-            let Ok(proc_stat) = fs::read_to_string(format!("{}/stat", pid_dir)) else {
+            let Ok(proc_stat) = source.read_to_string(&format!("{}/stat", pid_dir)) else {
                 return;
             };
 
-            if let Some(p) = self.get_proc_data(&proc_stat, new_processes_arc.clone()) {
+            if let Some(p) = self.get_proc_data(&proc_stat, elapsed, new_processes_arc.clone()) {
                 {
                     let mut processes = processes_arc.lock().unwrap();
                     processes.push(p);
@@ -95,12 +93,14 @@ impl ProcessData {
             self.old_processes_map = new_processes_map.clone();
             self.processes = processes.clone();
         }
+        self.last_update = Some(now);
         return Ok(());
     }
 
     fn get_proc_data(
         &self,
         stat_data: &String,
+        elapsed: Option<f64>,
         new_procs_arc: Arc<Mutex<HashMap<(u64, u64), u64>>>,
     ) -> Option<Process> {
         let mut proc_data: Process = Process {
@@ -108,7 +108,7 @@ impl ProcessData {
             name: String::new(),
             mem: 0,
             threads: 0,
-            cpu_usage: 0,
+            cpu_usage: None,
             start_time: 0,
         };
 
@@ -167,16 +167,17 @@ impl ProcessData {
         ) {
             let mut new_procs = new_procs_arc.lock().unwrap();
 
-            if let Some(old) = self
+            // Processes not seen on the previous run started since then, so
+            // all of their time was spent during the interval
+            let jiffies = match self
                 .old_processes_map
                 .get(&(proc_data.pid, proc_data.start_time))
             {
-                proc_data.cpu_usage = (user + sys) - old;
-                new_procs.insert((proc_data.pid, proc_data.start_time), user + sys);
-            } else {
-                proc_data.cpu_usage = user + sys;
-                new_procs.insert((proc_data.pid, proc_data.start_time), proc_data.cpu_usage);
-            }
+                Some(old) => (user + sys).saturating_sub(*old),
+                None => user + sys,
+            };
+            new_procs.insert((proc_data.pid, proc_data.start_time), user + sys);
+            proc_data.cpu_usage = elapsed.map(|e| jiffies as f64 / USER_HZ / e * 100.0);
         } else {
             return None;
         }
@@ -184,17 +185,65 @@ impl ProcessData {
         return Some(proc_data);
     }
 
-    fn validate_pid_dir(dir: &fs::DirEntry) -> Result<&fs::DirEntry> {
-        let dir_name: String;
-        match dir.path().into_os_string().into_string() {
-            Ok(d) => dir_name = d,
-            Err(_) => return Err(Error::new(ProcDataRetrievalErr::NotPidDir)),
-        }
-
-        if !PROC_PID_RE.is_match(&dir_name) {
+    fn validate_pid_dir(dir_name: &str) -> Result<&str> {
+        if !PROC_PID_RE.is_match(dir_name) {
             return Err(Error::new(ProcDataRetrievalErr::NotPidDir));
         }
 
-        return Ok(dir);
+        return Ok(dir_name);
+    }
+}
+
+pub struct ProcCollector {
+    source: Arc<dyn Source>,
+    data: Option<ProcessData>,
+}
+
+impl ProcCollector {
+    pub fn new(source: Arc<dyn Source>) -> Self {
+        ProcCollector { source, data: None }
+    }
+}
+
+impl Collector for ProcCollector {
+    fn name(&self) -> &'static str {
+        "proc"
+    }
+
+    fn key(&self) -> &'static str {
+        SECTION_KEY
+    }
+
+    fn description(&self) -> &'static str {
+        "Running processes"
+    }
+
+    fn init(&mut self) -> Result<()> {
+        self.data = Some(ProcessData::new(self.source.as_ref())?);
+        Ok(())
+    }
+
+    fn collect(&mut self) -> Result<Value> {
+        let Some(data) = &mut self.data else {
+            return Err(Error::new(CollectorErr::Unavailable));
+        };
+
+        data.update(self.source.as_ref())?;
+        Ok(serde_json::to_value(&data.processes)?)
+    }
+
+    fn schema(&self) -> Value {
+        array(object(&[
+            ("pid", integer()),
+            ("name", string()),
+            ("mem", integer()),
+            ("threads", integer()),
+            ("cpu_usage", optional(number())),
+            ("start_time", integer()),
+        ]))
+    }
+
+    fn sensitive(&self) -> bool {
+        true
     }
 }
@@ -13,6 +13,23 @@ pub enum ProcDataCreationErr {
     PageSizeErr(Errno),
 }
 
+impl ProcDataRetrievalErr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProcDataRetrievalErr::NotPidDir => "not_pid_dir",
+        }
+    }
+}
+
+impl ProcDataCreationErr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::PageSizeEmpty => "page_size_empty",
+            Self::PageSizeErr(_) => "page_size_error",
+        }
+    }
+}
+
 impl std::error::Error for ProcDataRetrievalErr {}
 impl std::error::Error for ProcDataCreationErr {}
 
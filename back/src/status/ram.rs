@@ -3,25 +3,30 @@ pub mod err;
 
 use crate::status::ram::err::MemRetrievalErr;
 
-use std::fs;
+use std::sync::Arc;
 
 use anyhow::{Error, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+pub use self::consts::SECTION_KEY;
 use self::consts::{AVAILABLE, CACHED, EXPECTED_MEM_VALUES, FREE, LABELS, PROC_MEMINFO, TOTAL};
+use super::collector::schema::{integer, object};
+use super::collector::Collector;
+use super::source::Source;
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct RamData {
-    total: u64,
-    used: u64,
-    available: u64,
-    free: u64,
-    cached: u64,
+    pub total: u64,
+    pub used: u64,
+    pub available: u64,
+    pub free: u64,
+    pub cached: u64,
 }
 
 impl RamData {
-    pub fn get() -> Result<Self> {
-        let meminfo = fs::read_to_string((*PROC_MEMINFO).as_str())?;
+    pub fn get(source: &dyn Source) -> Result<Self> {
+        let meminfo = source.read_to_string(PROC_MEMINFO)?;
 
         let mut mem_values: Vec<u64> = Vec::new();
         for line in meminfo.lines() {
@@ -46,3 +51,54 @@ impl RamData {
         });
     }
 }
+
+pub struct RamCollector {
+    source: Arc<dyn Source>,
+}
+
+impl RamCollector {
+    pub fn new(source: Arc<dyn Source>) -> Self {
+        RamCollector { source }
+    }
+}
+
+impl Collector for RamCollector {
+    fn name(&self) -> &'static str {
+        "ram"
+    }
+
+    fn key(&self) -> &'static str {
+        SECTION_KEY
+    }
+
+    fn description(&self) -> &'static str {
+        "Memory usage, in bytes"
+    }
+
+    fn collect(&mut self) -> Result<Value> {
+        Ok(serde_json::to_value(RamData::get(self.source.as_ref())?)?)
+    }
+
+    fn schema(&self) -> Value {
+        object(&[
+            ("total", integer()),
+            ("used", integer()),
+            ("available", integer()),
+            ("free", integer()),
+            ("cached", integer()),
+        ])
+    }
+
+    fn samples(&self, data: &Value) -> Vec<(String, f64)> {
+        let Ok(ram) = RamData::deserialize(data) else {
+            return Vec::new();
+        };
+
+        vec![
+            (String::from("ram.total"), ram.total as f64),
+            (String::from("ram.used"), ram.used as f64),
+            (String::from("ram.available"), ram.available as f64),
+            (String::from("ram.cached"), ram.cached as f64),
+        ]
+    }
+}
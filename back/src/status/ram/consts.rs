@@ -1,8 +1,4 @@
-use crate::status::DOCKER_PROC_DIR_ENV;
-
-use lazy_static::lazy_static;
-
-pub const PROC_MEMINFO_DEFAULT: &str = "/proc/meminfo";
+pub const PROC_MEMINFO: &str = "/proc/meminfo";
 
 const TOTAL_LABEL: &str = "MemTotal:";
 const FREE_LABEL: &str = "MemFree:";
@@ -17,10 +13,5 @@ pub const EXPECTED_MEM_VALUES: usize = 4;
 
 pub const LABELS: &'static [&str] = &[TOTAL_LABEL, FREE_LABEL, AVAILABLE_LABEL, CACHED_LABEL];
 
-lazy_static! {
-    pub static ref PROC_MEMINFO: String = if let Ok(proc) = std::env::var(DOCKER_PROC_DIR_ENV) {
-        format!("{}/meminfo", proc)
-    } else {
-        String::from(PROC_MEMINFO_DEFAULT)
-    };
-}
+// Key of the section in the serialized status
+pub const SECTION_KEY: &str = "ram";
@@ -5,6 +5,14 @@ pub enum MemRetrievalErr {
     NotEnoughValues,
 }
 
+impl MemRetrievalErr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MemRetrievalErr::NotEnoughValues => "not_enough_values",
+        }
+    }
+}
+
 impl std::error::Error for MemRetrievalErr {}
 
 impl fmt::Display for MemRetrievalErr {
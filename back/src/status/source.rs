@@ -0,0 +1,142 @@
+mod consts;
+
+use std::fs;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use anyhow::{Error, Result};
+use lazy_static::lazy_static;
+use nix::sys::statvfs::statvfs;
+use nix::unistd;
+
+use self::consts::{HOST_MOUNTS, HOST_ROOT_ENV, PATH_OVERRIDES, PROC_MOUNTS};
+use super::proc::err::ProcDataCreationErr;
+
+lazy_static! {
+    static ref HOST: Arc<dyn Source> = Arc::new(HostSource::from_env());
+    static ref STARTED: Instant = Instant::now();
+}
+
+/// Size of a mounted filesystem, in bytes
+pub struct FsStats {
+    pub total: u64,
+    pub available: u64,
+}
+
+/// Where collectors read the host's data from. Paths are the ones of the
+/// host (`/proc/stat`, `/sys/class/net`...), the source deciding where they
+/// actually are, so that collectors can be run against fixtures
+pub trait Source: Send + Sync {
+    fn read_to_string(&self, path: &str) -> io::Result<String>;
+
+    /// Contents of a file which only exists on some kernels or boards, None
+    /// when it doesn't
+    fn read_optional(&self, path: &str) -> io::Result<Option<String>> {
+        match self.read_to_string(path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Names of the entries of a directory
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>>;
+
+    fn statvfs(&self, mountpoint: &str) -> io::Result<FsStats>;
+
+    /// Size of memory pages, in bytes
+    fn page_size(&self) -> Result<u64> {
+        match unistd::sysconf(unistd::SysconfVar::PAGE_SIZE) {
+            Ok(Some(page_size)) => Ok(page_size as u64),
+            Ok(None) => Err(Error::new(ProcDataCreationErr::PageSizeEmpty)),
+            Err(e) => Err(Error::new(ProcDataCreationErr::PageSizeErr(e))),
+        }
+    }
+
+    /// Unix timestamp, in milliseconds, of the data
+    fn now_millis(&self) -> u128 {
+        UNIX_EPOCH.elapsed().unwrap_or_default().as_millis()
+    }
+
+    /// Time on a clock which never jumps, unlike the Unix time on NTP
+    /// corrections, rates being computed from it
+    fn monotonic(&self) -> Duration {
+        STARTED.elapsed()
+    }
+}
+
+/// The machine pi-status runs on, or the one whose `/` is mounted at
+/// PST_HOST_ROOT when running in a container
+pub struct HostSource {
+    root: Option<String>,
+    // (host path, where it is)
+    overrides: Vec<(&'static str, String)>,
+}
+
+impl HostSource {
+    pub fn from_env() -> Self {
+        let root = std::env::var(HOST_ROOT_ENV)
+            .ok()
+            .map(|r| r.trim_end_matches('/').to_string())
+            .filter(|r| !r.is_empty());
+        let overrides = PATH_OVERRIDES
+            .iter()
+            .filter_map(|(env, path)| Some((*path, std::env::var(env).ok()?)))
+            .collect();
+
+        HostSource { root, overrides }
+    }
+
+    fn under_root(&self, path: &str) -> String {
+        match &self.root {
+            Some(root) => format!("{}{}", root, path),
+            None => path.to_string(),
+        }
+    }
+
+    fn resolve(&self, path: &str) -> String {
+        for (host_path, replacement) in &self.overrides {
+            if let Some(rest) = path.strip_prefix(host_path) {
+                if rest.is_empty() || rest.starts_with('/') {
+                    return format!("{}{}", replacement.trim_end_matches('/'), rest);
+                }
+            }
+        }
+
+        if path == PROC_MOUNTS && self.root.is_some() {
+            return self.under_root(HOST_MOUNTS);
+        }
+        self.under_root(path)
+    }
+}
+
+impl Source for HostSource {
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(self.resolve(path))
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        Ok(fs::read_dir(self.resolve(path))?
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .collect())
+    }
+
+    // Mount points are those of the host, so they are only put under the
+    // root and never overridden. Block sizes and counts are 32 bits wide on
+    // some targets
+    #[allow(clippy::unnecessary_cast)]
+    fn statvfs(&self, mountpoint: &str) -> io::Result<FsStats> {
+        let stats = statvfs(self.under_root(mountpoint).as_str())?;
+
+        Ok(FsStats {
+            total: stats.block_size() as u64 * stats.blocks() as u64,
+            available: stats.block_size() as u64 * stats.blocks_available() as u64,
+        })
+    }
+}
+
+/// The source of the machine pi-status runs on
+pub fn host() -> Arc<dyn Source> {
+    HOST.clone()
+}
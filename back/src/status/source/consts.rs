@@ -0,0 +1,17 @@
+// Where the host's `/` is mounted when running in a container
+pub const HOST_ROOT_ENV: &str = "PST_HOST_ROOT";
+
+// Overrides of single host paths, taking precedence over PST_HOST_ROOT:
+// (environment variable, host path it replaces), the most specific first
+pub const PATH_OVERRIDES: [(&str, &str); 5] = [
+    ("PST_MOUNTS_FILE", "/proc/mounts"),
+    ("PST_HOST_FILE", "/etc/hostname"),
+    ("PST_THERMAL_DIR", "/sys/class/thermal"),
+    ("PST_NET_DIR", "/sys/class/net"),
+    ("PST_PROC_DIR", "/proc"),
+];
+
+pub const PROC_MOUNTS: &str = "/proc/mounts";
+// `/proc/mounts` links to the mounts of the reading process, i.e. those of the
+// container, while the host's are those of its init process
+pub const HOST_MOUNTS: &str = "/proc/1/mounts";
@@ -1,22 +1,94 @@
 mod consts;
 
-use std::fs;
+use std::sync::Arc;
 
 use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use toml::Table;
 
+pub use self::consts::SECTION_KEY;
 use self::consts::TEMP_PATH;
+use super::collector::schema::number;
+use super::collector::{self, Collector};
+use super::source::Source;
 
 pub struct TempData {
-    pub degrees: f32,
+    pub degrees: f64,
 }
 
 impl TempData {
-    pub fn get() -> Result<Self> {
-        let temp_str = fs::read_to_string((*TEMP_PATH).as_str())?;
-        let temp = temp_str.replace("\n", "").parse::<f32>()?;
+    pub fn get(source: &dyn Source, path: &str) -> Result<Self> {
+        let temp_str = source.read_to_string(path)?;
+        let temp = temp_str.replace("\n", "").parse::<f64>()?;
 
         return Ok(Self {
             degrees: temp / 1e3,
         });
     }
 }
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TempOptions {
+    // Host file reporting the temperature, in thousandths of degree
+    thermal_zone: String,
+}
+
+impl Default for TempOptions {
+    fn default() -> Self {
+        TempOptions {
+            thermal_zone: String::from(TEMP_PATH),
+        }
+    }
+}
+
+pub struct TempCollector {
+    source: Arc<dyn Source>,
+    options: TempOptions,
+}
+
+impl TempCollector {
+    pub fn new(source: Arc<dyn Source>) -> Self {
+        TempCollector {
+            source,
+            options: TempOptions::default(),
+        }
+    }
+}
+
+impl Collector for TempCollector {
+    fn name(&self) -> &'static str {
+        "temp"
+    }
+
+    fn key(&self) -> &'static str {
+        SECTION_KEY
+    }
+
+    fn description(&self) -> &'static str {
+        "CPU temperature, in degrees Celsius"
+    }
+
+    fn configure(&mut self, options: &Table) -> Result<()> {
+        self.options = collector::options(self.name(), options)?;
+        Ok(())
+    }
+
+    fn collect(&mut self) -> Result<Value> {
+        Ok(Value::from(
+            TempData::get(self.source.as_ref(), &self.options.thermal_zone)?.degrees,
+        ))
+    }
+
+    fn schema(&self) -> Value {
+        number()
+    }
+
+    fn samples(&self, data: &Value) -> Vec<(String, f64)> {
+        match data.as_f64() {
+            Some(temp) => vec![(String::from("temp"), temp)],
+            None => Vec::new(),
+        }
+    }
+}
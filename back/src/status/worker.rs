@@ -0,0 +1,250 @@
+use crate::record::RECORDER;
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use anyhow::Result;
+use log::warn;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::errors::ErrorTracker;
+use super::{CollectorSettings, ScheduledCollector, MAX_BACKOFF};
+
+/// When a section was collected, as sent in the `timing` section
+#[derive(Serialize, Clone, Debug)]
+pub struct Timing {
+    // Unix timestamp, in milliseconds
+    pub collected_at: u64,
+    // How long the run took
+    pub duration_ms: f64,
+    // Time since the previous run, which the deltas and rates of the section
+    // cover. None on the first run
+    pub window_ms: Option<f64>,
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1e3
+}
+
+/// Data of a run, with the samples the history records from it
+pub struct Collected {
+    pub data: Value,
+    pub samples: Vec<(String, f64)>,
+    pub timing: Timing,
+}
+
+pub enum Poll {
+    // Nothing was asked, or the answer was already handled
+    Idle,
+    Done(Result<Collected>),
+    // The collector just missed its deadline
+    TimedOut,
+    // Still no answer after timing out
+    Late,
+}
+
+/// Runs a collector on its own thread, so that one blocking (e.g. on a
+/// stale network mount) only delays its own section
+pub struct Worker {
+    pub name: &'static str,
+    pub key: &'static str,
+    pub sensitive: bool,
+    pub errors: ErrorTracker,
+    settings: CollectorSettings,
+    interval: Duration,
+    requests: Sender<()>,
+    results: Receiver<Result<Collected>>,
+    // Runs are scheduled from the previous schedule rather than from when
+    // they happened, so that they don't drift
+    next_run: Option<Instant>,
+    pending_since: Option<Instant>,
+    timed_out: bool,
+    // Consecutive runs that timed out
+    timeouts: u32,
+    backoff_until: Option<Instant>,
+}
+
+impl Worker {
+    /// Collectors without an interval of their own run every
+    /// `default_interval`
+    pub fn spawn(scheduled: ScheduledCollector, default_interval: Duration) -> Self {
+        let interval = scheduled.interval(default_interval);
+        let ScheduledCollector {
+            mut collector,
+            settings,
+        } = scheduled;
+        let (name, key, sensitive) = (collector.name(), collector.key(), collector.sensitive());
+
+        let (requests, requests_receiver) = mpsc::channel::<()>();
+        let (results_sender, results) = mpsc::channel();
+
+        // Disabled collectors get no thread, their requests channel being
+        // closed right away
+        if settings.enabled {
+            let spawned = thread::Builder::new()
+                .name(format!("collector-{}", name))
+                .spawn(move || {
+                    if let Err(e) = collector.init() {
+                        warn!(
+                            "Could not start the {} collector: {}. For this run its data will not be retrieved",
+                            name, e
+                        );
+                        return;
+                    }
+
+                    let mut last_start: Option<Instant> = None;
+                    for () in requests_receiver {
+                        let start = Instant::now();
+                        let collected_at = UNIX_EPOCH.elapsed().unwrap_or_default().as_millis();
+
+                        // The recording only cuts frames between runs
+                        let recorder = RECORDER.read().unwrap().clone();
+                        let run = recorder.as_ref().map(|r| r.run());
+                        let data = collector.collect();
+                        drop(run);
+
+                        let collected = data.map(|data| Collected {
+                            samples: match sensitive {
+                                true => Vec::new(),
+                                false => collector.samples(&data),
+                            },
+                            data,
+                            timing: Timing {
+                                collected_at: collected_at as u64,
+                                duration_ms: millis(start.elapsed()),
+                                window_ms: last_start.map(|l| millis(start - l)),
+                            },
+                        });
+                        last_start = Some(start);
+
+                        if results_sender.send(collected).is_err() {
+                            return;
+                        }
+                    }
+                });
+
+            if let Err(e) = spawned {
+                warn!("Could not start the {} collector thread: {}", name, e);
+            }
+        }
+
+        Worker {
+            name,
+            key,
+            sensitive,
+            errors: ErrorTracker::default(),
+            settings,
+            interval,
+            requests,
+            results,
+            next_run: None,
+            pending_since: None,
+            timed_out: false,
+            timeouts: 0,
+            backoff_until: None,
+        }
+    }
+
+    pub fn log_errors(&self) -> bool {
+        !self.settings.suppress_errors
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.settings.timeout
+    }
+
+    /// When the collector should run next, None while it's disabled or
+    /// waiting for an answer
+    pub fn next_run(&self, now: Instant) -> Option<Instant> {
+        if !self.settings.enabled || self.pending_since.is_some() {
+            return None;
+        }
+
+        let next_run = self.next_run.unwrap_or(now);
+        Some(self.backoff_until.map_or(next_run, |b| b.max(next_run)))
+    }
+
+    pub fn due(&self, now: Instant) -> bool {
+        self.next_run(now).is_some_and(|n| n <= now)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending_since.is_some()
+    }
+
+    /// Asks the thread for a run. A thread which is gone (its collector
+    /// failed to start) disables the collector
+    pub fn dispatch(&mut self, now: Instant) {
+        if self.requests.send(()).is_err() {
+            self.settings.enabled = false;
+            return;
+        }
+
+        // Runs which were missed (e.g. while nobody needed the data) are
+        // skipped, staying on the same schedule
+        let scheduled = self.next_run.unwrap_or(now);
+        let missed = now.saturating_duration_since(scheduled).as_nanos() / self.interval.as_nanos();
+        let next_run = u32::try_from(missed + 1)
+            .ok()
+            .and_then(|runs| self.interval.checked_mul(runs))
+            .and_then(|d| scheduled.checked_add(d))
+            // Too far behind to catch up with the schedule, which starts over
+            .unwrap_or(now + self.interval);
+        self.next_run = Some(next_run);
+        self.pending_since = Some(now);
+    }
+
+    /// Waits for the answer to the last request until its deadline. Late
+    /// collectors are only checked for an answer, without waiting again
+    pub fn poll(&mut self) -> Poll {
+        let Some(pending_since) = self.pending_since else {
+            return Poll::Idle;
+        };
+
+        let received = if self.timed_out {
+            self.results.try_recv().map_err(|e| match e {
+                TryRecvError::Empty => RecvTimeoutError::Timeout,
+                TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+            })
+        } else {
+            let wait = match pending_since.checked_add(self.settings.timeout) {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => self.settings.timeout,
+            };
+            self.results.recv_timeout(wait)
+        };
+
+        match received {
+            Ok(collected) => {
+                self.pending_since = None;
+                if self.timed_out {
+                    // Runs of a collector that keeps hanging get further apart
+                    let base = self.interval.max(self.settings.timeout);
+                    let backoff = base
+                        .saturating_mul(2u32.saturating_pow(self.timeouts))
+                        .min(MAX_BACKOFF);
+                    self.backoff_until = Some(Instant::now() + backoff);
+                    self.timed_out = false;
+                } else {
+                    self.timeouts = 0;
+                    self.backoff_until = None;
+                }
+
+                Poll::Done(collected)
+            }
+            Err(RecvTimeoutError::Timeout) if self.timed_out => Poll::Late,
+            Err(RecvTimeoutError::Timeout) => {
+                self.timed_out = true;
+                self.timeouts = self.timeouts.saturating_add(1);
+                Poll::TimedOut
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                self.pending_since = None;
+                self.settings.enabled = false;
+                Poll::Idle
+            }
+        }
+    }
+}
@@ -0,0 +1,440 @@
+mod consts;
+pub mod err;
+
+use crate::history::{Point, MAX_QUERY_POINTS};
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Error, Result};
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+
+pub use self::consts::{DEFAULT_COMMIT_INTERVAL, DEFAULT_RAW_RETENTION, DEFAULT_ROLLUP_RETENTION};
+use self::consts::{MAX_PENDING_BATCHES, RETENTION_SWEEP_INTERVAL_SECS, ROLLUP_RESOLUTION, SCHEMA};
+use self::err::StoreErr;
+
+lazy_static! {
+    pub static ref STORE: RwLock<Option<Store>> = RwLock::new(None);
+}
+
+pub struct StoreConfig {
+    pub path: PathBuf,
+    pub commit_interval: u64,
+    pub raw_retention: u64,
+    pub rollup_retention: u64,
+}
+
+struct Batch {
+    timestamp: u64,
+    samples: Vec<(String, f64)>,
+}
+
+/// On-disk metrics store. Samples are handed to a writer thread which commits
+/// them in batches, to limit the writes on SD cards
+pub struct Store {
+    sender: Sender<Batch>,
+    reader: Mutex<Connection>,
+    raw_retention: u64,
+}
+
+fn open(path: &PathBuf) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+
+    // WAL keeps the database consistent on power loss, at worst losing the
+    // last batches, while NORMAL synchronization avoids an fsync per commit
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.execute_batch(SCHEMA)?;
+
+    Ok(conn)
+}
+
+impl Store {
+    pub fn start(config: StoreConfig) -> Result<Self> {
+        let writer = open(&config.path)?;
+        let reader = Connection::open_with_flags(
+            &config.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+
+        let (sender, receiver) = mpsc::channel();
+        let raw_retention = config.raw_retention;
+        thread::spawn(move || StoreWriter::new(writer, config).run(receiver));
+
+        Ok(Store {
+            sender,
+            reader: Mutex::new(reader),
+            raw_retention,
+        })
+    }
+
+    pub fn record(&self, timestamp: u64, samples: Vec<(String, f64)>) -> Result<()> {
+        self.sender
+            .send(Batch { timestamp, samples })
+            .map_err(|_| Error::new(StoreErr::WriterGone))
+    }
+
+    pub fn metrics(&self) -> Result<Vec<String>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare_cached("SELECT name FROM metrics ORDER BY name")?;
+        let names = stmt
+            .query_map([], |r| r.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        Ok(names)
+    }
+
+    /// Returns the points of the metric in `[from, to]`, aggregated with the
+    /// given step, like the in-memory history query. Raw samples are used
+    /// while they are retained and the step is finer than the rollups, the
+    /// step being never finer than a second, or than the rollups when they
+    /// are used
+    pub fn query(
+        &self,
+        metric: &str,
+        from: u64,
+        to: u64,
+        step: Option<u64>,
+        now: u64,
+    ) -> Result<Vec<Point>> {
+        if from > to {
+            return Err(Error::new(StoreErr::InvalidRange));
+        }
+
+        let conn = self.reader.lock().unwrap();
+        let metric_id: Option<i64> = conn
+            .prepare_cached("SELECT id FROM metrics WHERE name = ?1")?
+            .query_row(params![metric], |r| r.get(0))
+            .optional()?;
+        let Some(metric_id) = metric_id else {
+            return Err(Error::new(StoreErr::UnknownMetric(metric.to_string())));
+        };
+
+        let raw_available = from >= now.saturating_sub(self.raw_retention);
+        let step = step.unwrap_or(0).max(1).max((to - from) / MAX_QUERY_POINTS);
+
+        let mut stmt = if raw_available && step < ROLLUP_RESOLUTION {
+            conn.prepare_cached(
+                "SELECT timestamp - timestamp % ?4 AS bucket, MIN(value), AVG(value), MAX(value)
+                 FROM samples
+                 WHERE timestamp BETWEEN ?2 AND ?3 AND metric_id = ?1
+                 GROUP BY bucket ORDER BY bucket",
+            )?
+        } else {
+            conn.prepare_cached(
+                "SELECT timestamp - timestamp % ?4 AS bucket, MIN(min),
+                        SUM(avg * count) / SUM(count), MAX(max)
+                 FROM rollups
+                 WHERE timestamp BETWEEN ?2 AND ?3 AND metric_id = ?1
+                 GROUP BY bucket ORDER BY bucket",
+            )?
+        };
+
+        let step = if raw_available {
+            step
+        } else {
+            step.max(ROLLUP_RESOLUTION)
+        };
+
+        let points = stmt
+            .query_map(
+                params![metric_id, from as i64, to as i64, step as i64],
+                |r| {
+                    Ok(Point {
+                        timestamp: r.get::<_, i64>(0)? as u64,
+                        min: r.get(1)?,
+                        avg: r.get(2)?,
+                        max: r.get(3)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<Point>>>()?;
+
+        Ok(points)
+    }
+}
+
+struct StoreWriter {
+    conn: Connection,
+    config: StoreConfig,
+    metric_ids: HashMap<String, i64>,
+    pending: VecDeque<Batch>,
+    last_sweep: Option<Instant>,
+}
+
+impl StoreWriter {
+    fn new(conn: Connection, config: StoreConfig) -> Self {
+        StoreWriter {
+            conn,
+            config,
+            metric_ids: HashMap::new(),
+            pending: VecDeque::new(),
+            last_sweep: None,
+        }
+    }
+
+    fn run(mut self, receiver: Receiver<Batch>) {
+        let commit_interval = Duration::from_secs(self.config.commit_interval);
+        let mut last_commit = Instant::now();
+
+        loop {
+            let timeout = commit_interval.saturating_sub(last_commit.elapsed());
+            match receiver.recv_timeout(timeout) {
+                Ok(batch) => {
+                    if self.pending.len() >= MAX_PENDING_BATCHES {
+                        self.pending.pop_front();
+                    }
+                    self.pending.push_back(batch);
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => {
+                    if let Err(e) = self.commit() {
+                        error!("Could not write metrics to the store: {}", e);
+                    }
+                    return;
+                }
+            }
+
+            if last_commit.elapsed() < commit_interval {
+                continue;
+            }
+            last_commit = Instant::now();
+
+            if let Err(e) = self.commit() {
+                error!("Could not write metrics to the store: {}", e);
+            }
+
+            if self
+                .last_sweep
+                .is_none_or(|s| s.elapsed().as_secs() >= RETENTION_SWEEP_INTERVAL_SECS)
+            {
+                self.last_sweep = Some(Instant::now());
+                if let Err(e) = self.sweep() {
+                    warn!("Could not apply the store retention policy: {}", e);
+                }
+            }
+        }
+    }
+
+    fn metric_id(&mut self, name: &str) -> Result<i64> {
+        if let Some(id) = self.metric_ids.get(name) {
+            return Ok(*id);
+        }
+
+        self.conn
+            .prepare_cached("INSERT OR IGNORE INTO metrics (name) VALUES (?1)")?
+            .execute(params![name])?;
+        let id: i64 = self
+            .conn
+            .prepare_cached("SELECT id FROM metrics WHERE name = ?1")?
+            .query_row(params![name], |r| r.get(0))?;
+
+        self.metric_ids.insert(name.to_string(), id);
+        Ok(id)
+    }
+
+    // Writes every pending batch in a single transaction, then recomputes the
+    // rollups of the minutes it touched
+    fn commit(&mut self) -> Result<()> {
+        let Some(first) = self.pending.iter().map(|b| b.timestamp).min() else {
+            return Ok(());
+        };
+
+        let mut rows = Vec::new();
+        for batch in &self.pending {
+            for (name, value) in &batch.samples {
+                if value.is_finite() {
+                    rows.push((batch.timestamp, name.clone(), *value));
+                }
+            }
+        }
+
+        let ids = rows
+            .iter()
+            .map(|(_, name, _)| Ok((name.clone(), self.metric_id(name)?)))
+            .collect::<Result<HashMap<String, i64>>>()?;
+
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO samples (timestamp, metric_id, value) VALUES (?1, ?2, ?3)",
+            )?;
+            for (timestamp, name, value) in &rows {
+                insert.execute(params![*timestamp as i64, ids[name], value])?;
+            }
+
+            tx.prepare_cached(
+                "INSERT OR REPLACE INTO rollups (timestamp, metric_id, min, avg, max, count)
+                 SELECT timestamp - timestamp % ?1 AS bucket, metric_id,
+                        MIN(value), AVG(value), MAX(value), COUNT(*)
+                 FROM samples
+                 WHERE timestamp >= ?2
+                 GROUP BY bucket, metric_id",
+            )?
+            .execute(params![
+                ROLLUP_RESOLUTION as i64,
+                (first - first % ROLLUP_RESOLUTION) as i64
+            ])?;
+        }
+        tx.commit()?;
+
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn sweep(&mut self) -> Result<()> {
+        let Some(now) = self.pending.back().map(|b| b.timestamp).or_else(|| {
+            self.conn
+                .query_row("SELECT MAX(timestamp) FROM samples", [], |r| {
+                    r.get::<_, Option<i64>>(0)
+                })
+                .ok()
+                .flatten()
+                .map(|t| t as u64)
+        }) else {
+            return Ok(());
+        };
+
+        let raw_deleted = self.conn.execute(
+            "DELETE FROM samples WHERE timestamp < ?1",
+            params![now.saturating_sub(self.config.raw_retention) as i64],
+        )?;
+        let rollups_deleted = self.conn.execute(
+            "DELETE FROM rollups WHERE timestamp < ?1",
+            params![now.saturating_sub(self.config.rollup_retention) as i64],
+        )?;
+
+        if raw_deleted > 0 || rollups_deleted > 0 {
+            info!(
+                "Store retention removed {} samples and {} rollups",
+                raw_deleted, rollups_deleted
+            );
+        }
+
+        Ok(())
+    }
+}
+
+pub fn is_enabled() -> bool {
+    STORE.read().unwrap().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    const HOUR: u64 = 60 * 60;
+
+    fn config(name: &str) -> StoreConfig {
+        let path = env::temp_dir().join(format!("pi-status-{}-{}.db", name, std::process::id()));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+
+        StoreConfig {
+            path,
+            commit_interval: 60,
+            raw_retention: HOUR,
+            rollup_retention: 24 * HOUR,
+        }
+    }
+
+    // The writer without its thread, and a reader on the same database
+    fn open_store(config: StoreConfig) -> (StoreWriter, Store) {
+        let writer = StoreWriter::new(open(&config.path).unwrap(), config);
+        let store = Store {
+            sender: mpsc::channel().0,
+            reader: Mutex::new(Connection::open(&writer.config.path).unwrap()),
+            raw_retention: writer.config.raw_retention,
+        };
+
+        (writer, store)
+    }
+
+    fn write(writer: &mut StoreWriter, from: u64, to: u64, step: u64) {
+        for timestamp in (from..=to).step_by(step as usize) {
+            writer.pending.push_back(Batch {
+                timestamp,
+                samples: vec![(String::from("temp"), (timestamp % 100) as f64)],
+            });
+        }
+        writer.commit().unwrap();
+    }
+
+    fn count(writer: &StoreWriter, table: &str) -> i64 {
+        writer
+            .conn
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn samples_are_rolled_up_by_minute() {
+        let (mut writer, store) = open_store(config("rollups"));
+        write(&mut writer, 6000, 6119, 1);
+
+        assert_eq!(count(&writer, "samples"), 120);
+        assert_eq!(count(&writer, "rollups"), 2);
+        assert_eq!(store.metrics().unwrap(), ["temp"]);
+
+        // Raw samples while retained, rollups once the step is coarse enough
+        let raw = store.query("temp", 6000, 6009, None, 6119).unwrap();
+        assert_eq!(raw.len(), 10);
+        let minutes = store.query("temp", 6000, 6119, Some(60), 6119).unwrap();
+        assert_eq!(minutes.len(), 2);
+        assert_eq!((minutes[0].min, minutes[0].max), (0.0, 59.0));
+        assert_eq!(
+            minutes[1].avg,
+            (60..120).map(|v| (v % 100) as f64).sum::<f64>() / 60.0
+        );
+
+        assert!(store.query("temp", 6119, 6000, None, 6119).is_err());
+        let Err(e) = store.query("load", 6000, 6119, None, 6119) else {
+            panic!("an unknown metric was queried");
+        };
+        assert!(matches!(e.downcast_ref(), Some(StoreErr::UnknownMetric(_))));
+    }
+
+    #[test]
+    fn long_ranges_are_queried_with_coarser_steps() {
+        let (mut writer, store) = open_store(config("points"));
+        write(&mut writer, 6000, 6119, 1);
+
+        // 20s steps, which the raw samples are still used for
+        let points = store
+            .query("temp", 5000, 5000 + 20 * MAX_QUERY_POINTS, None, 6119)
+            .unwrap();
+        assert_eq!(points.len(), 6);
+    }
+
+    #[test]
+    fn retention_sweeps_expire_raw_samples_first() {
+        let (mut writer, store) = open_store(config("sweep"));
+        let now = 2 * 24 * HOUR;
+        write(&mut writer, now - 30 * HOUR, now, 60);
+
+        writer.sweep().unwrap();
+
+        // Raw samples of the last hour, rollups of the last day
+        assert_eq!(count(&writer, "samples"), 61);
+        assert_eq!(count(&writer, "rollups"), 24 * 60 + 1);
+
+        let old = store
+            .query("temp", now - 2 * HOUR, now - HOUR - 60, None, now)
+            .unwrap();
+        assert_eq!(old.len(), 60);
+        assert!(store
+            .query("temp", 0, now - 25 * HOUR, None, now)
+            .unwrap()
+            .is_empty());
+    }
+}
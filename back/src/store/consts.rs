@@ -0,0 +1,34 @@
+pub const DEFAULT_COMMIT_INTERVAL: &str = "1m";
+pub const DEFAULT_RAW_RETENTION: &str = "24h";
+pub const DEFAULT_ROLLUP_RETENTION: &str = "30d";
+
+// Resolution, in seconds, of the downsampled data kept after raw data expires
+pub const ROLLUP_RESOLUTION: u64 = 60;
+
+pub const RETENTION_SWEEP_INTERVAL_SECS: u64 = 60 * 60;
+
+// Upper bound of batches kept in memory while the database can't be written,
+// older ones are dropped first
+pub const MAX_PENDING_BATCHES: usize = 60 * 60;
+
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS metrics (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS samples (
+    timestamp INTEGER NOT NULL,
+    metric_id INTEGER NOT NULL REFERENCES metrics(id),
+    value REAL NOT NULL,
+    PRIMARY KEY (timestamp, metric_id)
+) WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS rollups (
+    timestamp INTEGER NOT NULL,
+    metric_id INTEGER NOT NULL REFERENCES metrics(id),
+    min REAL NOT NULL,
+    avg REAL NOT NULL,
+    max REAL NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (timestamp, metric_id)
+) WITHOUT ROWID;
+";
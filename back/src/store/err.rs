@@ -0,0 +1,20 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum StoreErr {
+    WriterGone,
+    InvalidRange,
+    UnknownMetric(String),
+}
+
+impl std::error::Error for StoreErr {}
+
+impl fmt::Display for StoreErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreErr::WriterGone => write!(f, "The store writer thread is not running"),
+            StoreErr::InvalidRange => write!(f, "The requested range is not valid"),
+            StoreErr::UnknownMetric(m) => write!(f, "No stored history for metric `{}`", m),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+mod consts;
+pub mod err;
+
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Error, Result};
+use log::{error, info};
+use rustls::server::{AllowAnyAuthenticatedClient, ClientHello, NoClientAuth, ResolvesServerCert};
+use rustls::sign::{self, CertifiedKey, SigningKey};
+use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig, SignatureScheme};
+use rustls_pemfile::Item;
+use serde::{Deserialize, Serialize};
+
+use self::consts::{KEY_CHECK_MESSAGE, RELOAD_CHECK_INTERVAL_SECS};
+use self::err::TlsErr;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    // When set, only clients presenting a certificate signed by one of these
+    // CAs can connect
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ca: Option<PathBuf>,
+    // Plain HTTP port redirecting to the TLS one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_port: Option<u16>,
+}
+
+fn load_certs(path: &Path) -> Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("Could not open {}", path.display()))?,
+    );
+    let certs = rustls_pemfile::certs(&mut reader)
+        .with_context(|| format!("Could not read certificates from {}", path.display()))?;
+
+    if certs.is_empty() {
+        return Err(Error::new(TlsErr::NoCertificates(
+            path.display().to_string(),
+        )));
+    }
+
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_key(path: &Path) -> Result<PrivateKey> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("Could not open {}", path.display()))?,
+    );
+
+    for item in rustls_pemfile::read_all(&mut reader)
+        .with_context(|| format!("Could not read the private key from {}", path.display()))?
+    {
+        match item {
+            Item::PKCS8Key(k) | Item::RSAKey(k) | Item::ECKey(k) => return Ok(PrivateKey(k)),
+            _ => (),
+        }
+    }
+
+    Err(Error::new(TlsErr::NoPrivateKey(path.display().to_string())))
+}
+
+fn load_certified_key(config: &TlsConfig) -> Result<CertifiedKey> {
+    let certs = load_certs(&config.cert)?;
+    let key = load_key(&config.key)?;
+    let signing_key = sign::any_supported_type(&key).map_err(|_| {
+        Error::new(TlsErr::UnsupportedPrivateKey(
+            config.key.display().to_string(),
+        ))
+    })?;
+
+    if !signs_for(&certs[0], signing_key.as_ref()) {
+        return Err(Error::new(TlsErr::KeyMismatch {
+            cert: config.cert.display().to_string(),
+            key: config.key.display().to_string(),
+        }));
+    }
+
+    Ok(CertifiedKey::new(certs, signing_key))
+}
+
+// Whether the end-entity certificate verifies a signature made with the key,
+// rustls serving mismatched pairs without complaining and handshakes failing
+// afterwards
+fn signs_for(cert: &Certificate, key: &dyn SigningKey) -> bool {
+    let schemes = [
+        (
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            &webpki::ECDSA_P256_SHA256,
+        ),
+        (
+            SignatureScheme::ECDSA_NISTP384_SHA384,
+            &webpki::ECDSA_P384_SHA384,
+        ),
+        (SignatureScheme::ED25519, &webpki::ED25519),
+        (
+            SignatureScheme::RSA_PKCS1_SHA256,
+            &webpki::RSA_PKCS1_2048_8192_SHA256,
+        ),
+    ];
+    let offered = schemes.iter().map(|(s, _)| *s).collect::<Vec<_>>();
+
+    let Some(signer) = key.choose_scheme(&offered) else {
+        return false;
+    };
+    let Some((_, algorithm)) = schemes.iter().find(|(s, _)| *s == signer.scheme()) else {
+        return false;
+    };
+    let Ok(signature) = signer.sign(KEY_CHECK_MESSAGE) else {
+        return false;
+    };
+
+    webpki::EndEntityCert::try_from(cert.0.as_slice())
+        .and_then(|c| c.verify_signature(algorithm, KEY_CHECK_MESSAGE, &signature))
+        .is_ok()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Hands the current certificate to every new handshake. Swapping it only
+/// affects connections established afterwards, so open WebSocket sessions
+/// are kept
+pub struct CertResolver {
+    config: TlsConfig,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+impl CertResolver {
+    fn new(config: TlsConfig) -> Result<Self> {
+        let key = load_certified_key(&config)?;
+
+        Ok(CertResolver {
+            config,
+            current: RwLock::new(Arc::new(key)),
+        })
+    }
+
+    // Polls the modification time of the files rather than relying on file
+    // system notifications, which don't survive the renames done by certbot
+    fn watch(self: Arc<Self>) {
+        let files_modified = || (modified(&self.config.cert), modified(&self.config.key));
+        let mut last_modified = files_modified();
+
+        loop {
+            thread::sleep(Duration::from_secs(RELOAD_CHECK_INTERVAL_SECS));
+
+            let now_modified = files_modified();
+            if now_modified == last_modified {
+                continue;
+            }
+            last_modified = now_modified;
+
+            // A half written pair fails to load, the next change to the files
+            // is going to be picked up
+            match load_certified_key(&self.config) {
+                Ok(key) => {
+                    *self.current.write().unwrap() = Arc::new(key);
+                    info!("Reloaded TLS certificate {}", self.config.cert.display());
+                }
+                Err(e) => error!(
+                    "Could not reload TLS certificate, keeping the previous one: {:#}",
+                    e
+                ),
+            }
+        }
+    }
+}
+
+/// Builds the server configuration and starts watching the certificate
+/// files for changes
+pub fn server_config(config: &TlsConfig) -> Result<ServerConfig> {
+    let client_verifier = match &config.client_ca {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(path)? {
+                roots
+                    .add(&cert)
+                    .with_context(|| format!("Invalid client CA in {}", path.display()))?;
+            }
+            AllowAnyAuthenticatedClient::new(roots)
+        }
+        None => NoClientAuth::new(),
+    };
+
+    let resolver = Arc::new(CertResolver::new(config.clone())?);
+    thread::spawn({
+        let resolver = resolver.clone();
+        move || resolver.watch()
+    });
+
+    Ok(ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(client_verifier)
+        .with_cert_resolver(resolver))
+}
+
+/// Loads everything once, without keeping anything, to report mistakes at
+/// startup
+pub fn validate(config: &TlsConfig) -> Result<()> {
+    load_certified_key(config)?;
+    if let Some(path) = &config.client_ca {
+        load_certs(path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/tls")
+            .join(name)
+    }
+
+    fn config(cert: &str, key: &str) -> TlsConfig {
+        TlsConfig {
+            cert: fixture(cert),
+            key: fixture(key),
+            client_ca: None,
+            redirect_port: None,
+        }
+    }
+
+    #[test]
+    fn loads_matching_pairs() {
+        assert!(load_certified_key(&config("cert.pem", "key.pem")).is_ok());
+        assert!(load_certified_key(&config("rsa-cert.pem", "rsa-key.pem")).is_ok());
+    }
+
+    #[test]
+    fn rejects_keys_of_another_certificate() {
+        for (cert, key) in [
+            ("cert.pem", "other-key.pem"),
+            ("cert.pem", "rsa-key.pem"),
+            ("rsa-cert.pem", "key.pem"),
+        ] {
+            let Err(e) = load_certified_key(&config(cert, key)) else {
+                panic!("{} was accepted with {}", key, cert);
+            };
+            assert!(matches!(e.downcast_ref(), Some(TlsErr::KeyMismatch { .. })));
+        }
+    }
+}
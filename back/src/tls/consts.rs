@@ -0,0 +1,5 @@
+// How often certificate files are checked for changes
+pub const RELOAD_CHECK_INTERVAL_SECS: u64 = 10;
+
+// Signed with a newly loaded key and verified with its certificate
+pub const KEY_CHECK_MESSAGE: &[u8] = b"pi-status key check";
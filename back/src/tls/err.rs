@@ -0,0 +1,28 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum TlsErr {
+    NoCertificates(String),
+    NoPrivateKey(String),
+    UnsupportedPrivateKey(String),
+    KeyMismatch { cert: String, key: String },
+}
+
+impl std::error::Error for TlsErr {}
+
+impl fmt::Display for TlsErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TlsErr::NoCertificates(p) => write!(f, "No PEM certificates found in {}", p),
+            TlsErr::NoPrivateKey(p) => write!(f, "No PEM private key found in {}", p),
+            TlsErr::UnsupportedPrivateKey(p) => {
+                write!(f, "The private key in {} is not of a supported type", p)
+            }
+            TlsErr::KeyMismatch { cert, key } => write!(
+                f,
+                "The private key in {} does not match the certificate in {}",
+                key, cert
+            ),
+        }
+    }
+}
@@ -1,60 +1,248 @@
-use crate::status::{ACTIVE_WS_CONNECTIONS, STATUS_STR};
+pub mod api;
+mod consts;
+mod delta;
+mod encoding;
+pub mod login;
+mod openapi;
+mod rejections;
+pub mod session;
+pub mod sse;
+mod subscription;
+pub mod ws;
 
+use crate::acl::{Acl, Action};
+use crate::auth::Scope;
+use crate::history::err::HistoryErr;
+use crate::history::{self, Point, TierSpec, DEFAULT_QUERY_RANGE_SECS, HISTORY};
+use crate::metrics::{self, MetricsConfig};
+use crate::status::{self, ACTIVE_CLIENTS, STATUS};
+use crate::store::err::StoreErr;
+use crate::store::{self, STORE};
+
+use std::net::IpAddr;
 use std::sync::atomic::Ordering;
-use std::sync::{Arc, RwLock};
-use std::time::Duration;
 
-use actix::{Actor, AsyncContext, StreamHandler};
-use actix_web::{web as ActixWeb, Error as ActixError, HttpRequest, HttpResponse};
-use actix_web_actors::ws::{self, Message, ProtocolError};
-use log::error;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::error::ErrorForbidden;
+use actix_web::http::header;
+use actix_web::{web as ActixWeb, Error as ActixError, HttpMessage, HttpRequest, HttpResponse};
+use futures_util::future::{self, Either, Ready};
+use serde::{Deserialize, Serialize};
+
+use self::session::SessionInfo;
+
+// Client address as resolved by the access control, behind proxies too
+#[derive(Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+/// Rejects clients not allowed by the listener's access control list before
+/// they reach any endpoint
+pub fn filter_client<S, B>(
+    acl: &Acl,
+    req: ServiceRequest,
+    srv: &S,
+) -> Either<S::Future, Ready<Result<ServiceResponse<B>, ActixError>>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = ActixError>,
+{
+    let Some(peer) = req.peer_addr() else {
+        return Either::Right(future::ready(Err(ErrorForbidden("Forbidden"))));
+    };
+
+    let client = acl.client_ip(peer.ip(), req.headers());
+    match acl.check(client) {
+        Action::Allow => {
+            req.extensions_mut().insert(ClientIp(client));
+            Either::Left(srv.call(req))
+        }
+        Action::Deny => {
+            rejections::log(&req, client);
+            Either::Right(future::ready(Err(ErrorForbidden("Forbidden"))))
+        }
+    }
+}
+
+pub struct RedirectConfig {
+    pub https_port: u16,
+}
 
-pub async fn serve_data(
+/// Sends plain HTTP clients to the same path on the TLS listener
+pub async fn redirect_to_https(
     req: HttpRequest,
-    stream: ActixWeb::Payload,
-) -> Result<HttpResponse, ActixError> {
-    ACTIVE_WS_CONNECTIONS.fetch_add(1, Ordering::Relaxed);
-    ws::start(
-        WsDataSession {
-            data: STATUS_STR.clone(),
-        },
-        &req,
-        stream,
-    )
+    config: ActixWeb::Data<RedirectConfig>,
+) -> HttpResponse {
+    let host = {
+        let info = req.connection_info();
+        let host = info.host();
+
+        // Drop the port, keeping the brackets of IPv6 addresses
+        match host.rfind(']') {
+            Some(end) => host[..=end].to_string(),
+            None => host.split(':').next().unwrap_or(host).to_string(),
+        }
+    };
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+
+    HttpResponse::PermanentRedirect()
+        .insert_header((
+            header::LOCATION,
+            format!("https://{}:{}{}", host, config.https_port, path),
+        ))
+        .finish()
+}
+
+#[derive(Serialize)]
+struct Sessions {
+    count: u64,
+    sessions: Vec<SessionInfo>,
+}
+
+pub async fn serve_sessions(req: HttpRequest) -> HttpResponse {
+    if login::scope(&req) < Scope::Admin {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    HttpResponse::Ok().json(Sessions {
+        count: ACTIVE_CLIENTS.load(Ordering::Relaxed),
+        sessions: session::list(),
+    })
 }
 
-struct WsDataSession {
-    data: Arc<RwLock<String>>,
+pub async fn serve_metrics(
+    req: HttpRequest,
+    config: ActixWeb::Data<MetricsConfig>,
+    data_config: ActixWeb::Data<ws::DataConfig>,
+) -> HttpResponse {
+    let config = MetricsConfig {
+        processes: config.processes && login::scope(&req) >= Scope::Full,
+    };
+    // Exported sections which were not collected recently, e.g. skipped while
+    // nobody was watching them, run again before the scrape is answered
+    api::fresh_sections(&data_config, metrics::sections(&config)).await;
+    let body = {
+        let status_ref = STATUS.read().unwrap();
+        metrics::render(&status_ref, &config)
+    };
+
+    HttpResponse::Ok()
+        .content_type(metrics::CONTENT_TYPE)
+        .body(body)
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    metric: Option<String>,
+    from: Option<u64>,
+    to: Option<u64>,
+    step: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct HistoryMetrics {
+    tiers: Vec<TierSpec>,
+    metrics: Vec<String>,
 }
 
-impl StreamHandler<Result<Message, ProtocolError>> for WsDataSession {
-    fn handle(&mut self, msg: Result<Message, ProtocolError>, _: &mut Self::Context) {
-        match msg {
-            Ok(m) => match m {
-                Message::Close(_) => {
-                    ACTIVE_WS_CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+#[derive(Serialize)]
+struct HistoryPoints<'a> {
+    metric: &'a str,
+    from: u64,
+    to: u64,
+    points: Vec<Point>,
+}
+
+// Ranges not fully covered by the in-memory history are answered by the
+// on-disk store, when enabled
+pub async fn serve_history(query: ActixWeb::Query<HistoryQuery>) -> HttpResponse {
+    if !history::is_enabled() && !store::is_enabled() {
+        return HttpResponse::NotFound().body("History is not enabled");
+    }
+
+    let Some(metric) = query.metric.clone() else {
+        return match ActixWeb::block(history_metrics).await {
+            Ok(Ok(metrics)) => HttpResponse::Ok().json(metrics),
+            Ok(Err(e)) => HttpResponse::InternalServerError().body(e.to_string()),
+            Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        };
+    };
+
+    let now = status::now_secs();
+    let to = query.to.unwrap_or(now);
+    let from = query
+        .from
+        .unwrap_or_else(|| to.saturating_sub(DEFAULT_QUERY_RANGE_SECS));
+    let step = query.step;
+
+    let in_memory = {
+        let history_ref = HISTORY.read().unwrap();
+        match history_ref.as_ref() {
+            Some(h) if !store::is_enabled() || h.oldest(&metric).is_some_and(|o| o <= from) => {
+                Some(h.query(&metric, from, to, step))
+            }
+            _ => None,
+        }
+    };
+
+    let points = match in_memory {
+        Some(points) => points,
+        None => {
+            let metric = metric.clone();
+            match ActixWeb::block(move || {
+                let points = match STORE.read().unwrap().as_ref() {
+                    Some(s) => s.query(&metric, from, to, step, now),
+                    None => Ok(Vec::new()),
+                };
+                // Metrics the store didn't commit yet may be in memory
+                match (points, HISTORY.read().unwrap().as_ref()) {
+                    (Err(e), Some(h))
+                        if matches!(e.downcast_ref(), Some(StoreErr::UnknownMetric(_))) =>
+                    {
+                        h.query(&metric, from, to, step)
+                    }
+                    (points, _) => points,
                 }
-                _ => (),
-            },
-            Err(e) => {
-                error!("Error occurred in WS receive operation: {}", e)
+            })
+            .await
+            {
+                Ok(points) => points,
+                Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
             }
         }
+    };
+
+    match points {
+        Ok(points) => HttpResponse::Ok().json(HistoryPoints {
+            metric: &metric,
+            from,
+            to,
+            points,
+        }),
+        Err(e) => match (e.downcast_ref::<HistoryErr>(), e.downcast_ref::<StoreErr>()) {
+            (Some(HistoryErr::UnknownMetric(_)), _) | (_, Some(StoreErr::UnknownMetric(_))) => {
+                HttpResponse::NotFound().body(e.to_string())
+            }
+            _ => HttpResponse::BadRequest().body(e.to_string()),
+        },
     }
 }
 
-impl Actor for WsDataSession {
-    type Context = ws::WebsocketContext<Self>;
-
-    fn started(&mut self, ctx: &mut Self::Context) {
-        ctx.run_later(Duration::ZERO, |act, ctx| {
-            let data = act.data.read().unwrap().clone();
-            ctx.text(data);
-        });
+fn history_metrics() -> anyhow::Result<HistoryMetrics> {
+    let mut metrics = Vec::new();
+    let mut tiers = Vec::new();
 
-        ctx.run_interval(Duration::from_secs(1), |act, ctx| {
-            let data = act.data.read().unwrap().clone();
-            ctx.text(data);
-        });
+    if let Some(history) = HISTORY.read().unwrap().as_ref() {
+        tiers.extend_from_slice(history.tiers());
+        metrics.extend(history.metrics());
     }
+    if let Some(store) = STORE.read().unwrap().as_ref() {
+        metrics.extend(store.metrics()?);
+    }
+
+    metrics.sort();
+    metrics.dedup();
+    Ok(HistoryMetrics { tiers, metrics })
 }
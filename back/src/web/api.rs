@@ -0,0 +1,121 @@
+use crate::auth::Scope;
+use crate::status::net::{self, IfaStats};
+use crate::status::proc::{self, Process};
+use crate::status::{self, STATUS};
+
+use actix_web::{web as ActixWeb, HttpRequest, HttpResponse};
+use log::error;
+use serde::Deserialize;
+
+use super::consts::FRESH_DATA_TIMEOUT;
+use super::login;
+use super::openapi;
+use super::subscription::{self, ProcessSelection, ProcessSort};
+use super::ws::DataConfig;
+
+// Data is refreshed when older than a couple of update intervals, which only
+// happens when nothing keeps the update loop running, or only running for
+// other sections
+pub(super) async fn fresh_sections(config: &DataConfig, keys: Vec<&'static str>) {
+    let max_age = config.update_interval * 2;
+    if let Err(e) =
+        ActixWeb::block(move || status::ensure_fresh(&keys, max_age, FRESH_DATA_TIMEOUT)).await
+    {
+        error!("Could not refresh the status: {}", e);
+    }
+}
+
+pub(super) async fn fresh_status(config: &DataConfig) {
+    let keys = status::collectors().iter().map(|c| c.key).collect();
+    fresh_sections(config, keys).await;
+}
+
+pub async fn serve_status(req: HttpRequest, config: ActixWeb::Data<DataConfig>) -> HttpResponse {
+    fresh_status(&config).await;
+
+    let status_value = STATUS
+        .read()
+        .unwrap()
+        .to_value(login::scope(&req) < Scope::Full);
+    HttpResponse::Ok().json(status_value)
+}
+
+/// Data of a single collector, by name
+pub async fn serve_section(
+    req: HttpRequest,
+    name: ActixWeb::Path<String>,
+    config: ActixWeb::Data<DataConfig>,
+) -> HttpResponse {
+    let Some(collector) = status::collector(&name) else {
+        return HttpResponse::NotFound().body(format!("No section named `{}`", name));
+    };
+    if collector.sensitive && login::scope(&req) < Scope::Full {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    fresh_sections(&config, vec![collector.key]).await;
+
+    match STATUS.read().unwrap().get(collector.key) {
+        Some(data) => HttpResponse::Ok().json(data),
+        None => HttpResponse::ServiceUnavailable().body("No data available"),
+    }
+}
+
+pub async fn serve_interface(
+    iface: ActixWeb::Path<String>,
+    config: ActixWeb::Data<DataConfig>,
+) -> HttpResponse {
+    fresh_sections(&config, vec![net::SECTION_KEY]).await;
+
+    let Some(net_stats) = STATUS
+        .read()
+        .unwrap()
+        .section::<Vec<IfaStats>>(net::SECTION_KEY)
+    else {
+        return HttpResponse::ServiceUnavailable().body("No data available");
+    };
+
+    match net_stats
+        .iter()
+        .find(|s| s.interface.interface_name == *iface)
+    {
+        Some(stats) => HttpResponse::Ok().json(stats),
+        None => HttpResponse::NotFound().body(format!("No interface named `{}`", iface)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProcessesQuery {
+    sort: Option<ProcessSort>,
+    limit: Option<usize>,
+}
+
+pub async fn serve_processes(
+    req: HttpRequest,
+    query: ActixWeb::Query<ProcessesQuery>,
+    config: ActixWeb::Data<DataConfig>,
+) -> HttpResponse {
+    if login::scope(&req) < Scope::Full {
+        return HttpResponse::Forbidden().body("Forbidden");
+    }
+
+    fresh_sections(&config, vec![proc::SECTION_KEY]).await;
+
+    let Some(processes) = STATUS
+        .read()
+        .unwrap()
+        .section::<Vec<Process>>(proc::SECTION_KEY)
+    else {
+        return HttpResponse::ServiceUnavailable().body("No data available");
+    };
+
+    let selection = ProcessSelection {
+        top: query.limit.unwrap_or(usize::MAX),
+        sort: query.sort.unwrap_or(ProcessSort::Cpu),
+    };
+    HttpResponse::Ok().json(subscription::top_processes(&processes, &selection))
+}
+
+pub async fn serve_openapi() -> HttpResponse {
+    HttpResponse::Ok().json(openapi::document())
+}
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+// Clients are pinged on this interval, and dropped when nothing was heard
+// from them within the timeout
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Delta-encoded sessions get a full snapshot at least this often, in updates
+pub const DELTA_RESYNC_UPDATES: u64 = 60;
+
+// WebSocket subprotocols, in order of preference when a client offers several
+pub const SUBPROTOCOLS: [&str; 6] = [
+    "msgpack+deflate",
+    "cbor+deflate",
+    "json+deflate",
+    "msgpack",
+    "cbor",
+    "json",
+];
+
+// Longest wait for fresh data on REST requests, stale data is served after it
+pub const FRESH_DATA_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Delay before browsers reconnect a dropped event stream
+pub const SSE_RETRY_MS: u64 = 3000;
+
+// A client whose requests keep being rejected is logged again at most this
+// often
+pub const REJECTION_LOG_INTERVAL: Duration = Duration::from_secs(60);
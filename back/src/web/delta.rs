@@ -0,0 +1,149 @@
+use json_patch::Patch;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::consts::DELTA_RESYNC_UPDATES;
+
+#[derive(Serialize)]
+struct FullMessage<'a> {
+    seq: u64,
+    full: &'a Value,
+}
+
+#[derive(Serialize)]
+struct PatchMessage {
+    seq: u64,
+    patch: Patch,
+}
+
+/// Turns consecutive snapshots into RFC 6902 JSON Patches against the
+/// previous one. Every message carries a sequence number, so clients can
+/// detect a lost message and ask for a resync
+pub struct DeltaEncoder {
+    seq: u64,
+    last: Option<Value>,
+    updates_since_full: u64,
+}
+
+impl DeltaEncoder {
+    pub fn new() -> Self {
+        DeltaEncoder {
+            seq: 0,
+            last: None,
+            updates_since_full: 0,
+        }
+    }
+
+    // The next message is going to be a full snapshot
+    pub fn resync(&mut self) {
+        self.last = None;
+    }
+
+    // Arrays being diffed by position, a reordered list of processes turns
+    // into a patch larger than the snapshot itself, which is then sent instead
+    fn diff(&self, current: &Value) -> Option<Patch> {
+        let last = self.last.as_ref()?;
+        if self.updates_since_full >= DELTA_RESYNC_UPDATES {
+            return None;
+        }
+
+        let patch = json_patch::diff(last, current);
+        let patch_len = serde_json::to_string(&patch).map_or(usize::MAX, |p| p.len());
+        (patch_len < current.to_string().len()).then_some(patch)
+    }
+
+    pub fn encode(&mut self, current: Value) -> Value {
+        self.seq += 1;
+        self.updates_since_full += 1;
+
+        let message = match self.diff(&current) {
+            Some(patch) => serde_json::to_value(PatchMessage {
+                seq: self.seq,
+                patch,
+            }),
+            None => {
+                self.updates_since_full = 0;
+                serde_json::to_value(FullMessage {
+                    seq: self.seq,
+                    full: &current,
+                })
+            }
+        };
+
+        self.last = Some(current);
+        message.unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn status(cpu: f64, processes: &[(u32, u64)]) -> Value {
+        let processes = processes
+            .iter()
+            .map(|(pid, cpu_usage)| {
+                json!({
+                    "pid": pid,
+                    "name": format!("process-{}", pid),
+                    "cmd": format!("/usr/bin/process-{} --daemon", pid),
+                    "cpu_usage": cpu_usage,
+                })
+            })
+            .collect::<Vec<Value>>();
+
+        json!({
+            "cpu_usage": {"total": {"percent": {"busy": cpu}}},
+            "hostname": "raspberrypi",
+            "processes": processes,
+        })
+    }
+
+    #[test]
+    fn patches_apply_to_the_previous_message() {
+        let mut encoder = DeltaEncoder::new();
+        let first = status(12.5, &[(1, 3), (42, 1), (120, 0)]);
+        let second = status(14.0, &[(1, 2), (42, 1), (120, 0)]);
+
+        let message = encoder.encode(first.clone());
+        assert_eq!(message["seq"], 1);
+        assert_eq!(message["full"], first);
+
+        let message = encoder.encode(second.clone());
+        assert_eq!(message["seq"], 2);
+        let patch: Patch = serde_json::from_value(message["patch"].clone()).unwrap();
+        let mut patched = first;
+        json_patch::patch(&mut patched, &patch).unwrap();
+        assert_eq!(patched, second);
+
+        // A typical update is much smaller as a patch
+        assert!(message.to_string().len() * 2 < second.to_string().len());
+    }
+
+    #[test]
+    fn reordered_arrays_are_sent_in_full() {
+        let mut encoder = DeltaEncoder::new();
+        encoder.encode(status(12.5, &[(1, 3), (42, 1), (120, 0)]));
+
+        let reordered = status(12.5, &[(120, 9), (1, 3), (42, 1)]);
+        let message = encoder.encode(reordered.clone());
+        assert_eq!(message["full"], reordered);
+    }
+
+    #[test]
+    fn full_snapshots_are_sent_periodically_and_on_resync() {
+        let mut encoder = DeltaEncoder::new();
+        let fulls = (0..DELTA_RESYNC_UPDATES * 2)
+            .filter(|i| {
+                let message = encoder.encode(status(*i as f64, &[(1, 3)]));
+                message.get("full").is_some()
+            })
+            .count();
+        assert_eq!(fulls, 2);
+
+        encoder.resync();
+        assert!(encoder.encode(status(0.0, &[(1, 3)])).get("full").is_some());
+    }
+}
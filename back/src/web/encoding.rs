@@ -0,0 +1,223 @@
+use crate::status::{STATUS, STATUS_GENERATION, STATUS_STR, STATUS_SUMMARY_STR};
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::HttpRequest;
+use anyhow::Result;
+use bytestring::ByteString;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use lazy_static::lazy_static;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::consts::SUBPROTOCOLS;
+
+lazy_static! {
+    // Status encoded in each format, along with the update it was encoded
+    // from, so that it's encoded at most once per update whatever the
+    // number of sessions
+    static ref SHARED: Mutex<HashMap<(WireFormat, bool), (u64, Payload)>> =
+        Mutex::new(HashMap::new());
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Encoding {
+    Json,
+    MsgPack,
+    Cbor,
+}
+
+/// Encoding of WebSocket messages, negotiated through the subprotocol.
+/// The `+deflate` subprotocols are our own opt-in compression, not the
+/// permessage-deflate extension (RFC 7692) which actix can't negotiate: each
+/// message is raw-deflated and sent as a binary frame, for the client to
+/// inflate itself
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct WireFormat {
+    pub encoding: Encoding,
+    pub deflate: bool,
+}
+
+#[derive(Clone)]
+pub enum Payload {
+    Text(ByteString),
+    Binary(Bytes),
+}
+
+impl WireFormat {
+    // What clients not asking for a subprotocol get
+    pub const JSON: WireFormat = WireFormat {
+        encoding: Encoding::Json,
+        deflate: false,
+    };
+
+    pub fn from_protocol(protocol: &str) -> Option<Self> {
+        let (encoding, deflate) = match protocol.strip_suffix("+deflate") {
+            Some(e) => (e, true),
+            None => (protocol, false),
+        };
+
+        let encoding = match encoding {
+            "json" => Encoding::Json,
+            "msgpack" => Encoding::MsgPack,
+            "cbor" => Encoding::Cbor,
+            _ => return None,
+        };
+
+        Some(WireFormat { encoding, deflate })
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Payload> {
+        let bytes = match self.encoding {
+            Encoding::Json => serde_json::to_vec(value)?,
+            // Named, so that structs are maps like in JSON rather than arrays
+            Encoding::MsgPack => rmp_serde::to_vec_named(value)?,
+            Encoding::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::ser::into_writer(value, &mut buf)?;
+                buf
+            }
+        };
+
+        self.finish(bytes)
+    }
+
+    /// Decodes a binary message sent by the client
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        let mut inflated = Vec::new();
+        let bytes = if self.deflate {
+            DeflateDecoder::new(bytes).read_to_end(&mut inflated)?;
+            &inflated
+        } else {
+            bytes
+        };
+
+        Ok(match self.encoding {
+            Encoding::Json => serde_json::from_slice(bytes)?,
+            Encoding::MsgPack => rmp_serde::from_slice(bytes)?,
+            Encoding::Cbor => ciborium::de::from_reader(bytes)?,
+        })
+    }
+
+    fn finish(&self, bytes: Vec<u8>) -> Result<Payload> {
+        if self.deflate {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&bytes)?;
+            return Ok(Payload::Binary(Bytes::from(encoder.finish()?)));
+        }
+
+        match self.encoding {
+            Encoding::Json => Ok(Payload::Text(ByteString::try_from(bytes)?)),
+            _ => Ok(Payload::Binary(Bytes::from(bytes))),
+        }
+    }
+
+    // JSON is already serialized by the update loop
+    fn encode_status(&self, summary: bool) -> Result<Payload> {
+        if self.encoding == Encoding::Json {
+            let json = match summary {
+                true => STATUS_SUMMARY_STR.read().unwrap().clone(),
+                false => STATUS_STR.read().unwrap().clone(),
+            };
+            return self.finish(json.into_bytes());
+        }
+
+        let status_ref = STATUS.read().unwrap();
+        if summary {
+            self.encode(&status_ref.to_value(true))
+        } else {
+            self.encode(&*status_ref)
+        }
+    }
+}
+
+/// Picks the supported subprotocol we prefer among the ones offered by the
+/// client, compressed binary formats first
+pub fn negotiate(req: &HttpRequest) -> Option<&'static str> {
+    let offered = req
+        .headers()
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .map(|p| p.trim())
+        .collect::<Vec<&str>>();
+
+    SUBPROTOCOLS.iter().find(|s| offered.contains(s)).copied()
+}
+
+/// The whole status, or the summary without processes data, encoded once per
+/// update
+pub fn shared_status(format: WireFormat, summary: bool) -> Result<Payload> {
+    let generation = STATUS_GENERATION.load(Ordering::Acquire);
+    let mut shared = SHARED.lock().unwrap();
+
+    if let Some((g, payload)) = shared.get(&(format, summary)) {
+        if *g == generation {
+            return Ok(payload.clone());
+        }
+    }
+
+    let payload = format.encode_status(summary)?;
+    shared.insert((format, summary), (generation, payload.clone()));
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn offering(protocols: &[&str]) -> HttpRequest {
+        let mut req = TestRequest::default();
+        for p in protocols {
+            req = req.append_header((header::SEC_WEBSOCKET_PROTOCOL, *p));
+        }
+        req.to_http_request()
+    }
+
+    #[test]
+    fn server_preference_wins() {
+        assert_eq!(
+            negotiate(&offering(&["json, msgpack+deflate"])),
+            Some("msgpack+deflate")
+        );
+        assert_eq!(
+            negotiate(&offering(&["json", "cbor+deflate, cbor"])),
+            Some("cbor+deflate")
+        );
+        assert_eq!(negotiate(&offering(&["json"])), Some("json"));
+    }
+
+    #[test]
+    fn unknown_subprotocols_are_ignored() {
+        assert_eq!(negotiate(&offering(&[])), None);
+        assert_eq!(negotiate(&offering(&["graphql-ws, mqtt"])), None);
+        assert_eq!(negotiate(&offering(&["xml, json"])), Some("json"));
+    }
+
+    #[test]
+    fn every_subprotocol_round_trips() {
+        let value = json!({"hostname": "raspberrypi", "temperature": 48.3, "cores": [1, 2]});
+
+        for protocol in SUBPROTOCOLS {
+            let format = WireFormat::from_protocol(protocol).unwrap();
+            let bytes = match format.encode(&value).unwrap() {
+                Payload::Text(text) => text.as_bytes().to_vec(),
+                Payload::Binary(bytes) => bytes.to_vec(),
+            };
+
+            let decoded: Value = format.decode(&bytes).unwrap();
+            assert_eq!(decoded, value, "{}", protocol);
+        }
+        assert!(WireFormat::from_protocol("json+gzip").is_none());
+    }
+}
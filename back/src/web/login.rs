@@ -0,0 +1,184 @@
+use crate::auth::{
+    Auth, Identity, Login, Scope, BEARER_PREFIX, LOGIN_FAILED_MESSAGE, LOGIN_PAGE, LOGIN_PATH,
+    SESSION_COOKIE, TOKEN_QUERY_PARAM, TOO_MANY_LOGINS_MESSAGE,
+};
+
+use super::ClientIp;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::{self, ContentType};
+use actix_web::{web as ActixWeb, Error as ActixError, HttpMessage, HttpRequest, HttpResponse};
+use futures_util::future::{self, Either, Ready};
+use log::{info, warn};
+use serde::Deserialize;
+
+fn identify(auth: &Auth, req: &ServiceRequest) -> Option<Identity> {
+    if let Some(token) = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix(BEARER_PREFIX))
+    {
+        return auth.authenticate_token(token.trim());
+    }
+
+    // Browsers can't set headers on WebSocket connections, hence the query
+    // parameter
+    if let Ok(query) = ActixWeb::Query::<HashMap<String, String>>::from_query(req.query_string()) {
+        if let Some(token) = query.get(TOKEN_QUERY_PARAM) {
+            return auth.authenticate_token(token);
+        }
+    }
+
+    let session = req.cookie(SESSION_COOKIE)?;
+    auth.authenticate_session(session.value())
+}
+
+fn wants_html(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.contains("text/html"))
+}
+
+/// Attaches the client identity to the request, rejecting unauthenticated
+/// clients. Browsers asking for a page are redirected to the login form
+pub fn authenticate<S, B>(
+    auth: &Auth,
+    req: ServiceRequest,
+    srv: &S,
+) -> Either<S::Future, Ready<Result<ServiceResponse<B>, ActixError>>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = ActixError>,
+{
+    if !auth.is_enabled() {
+        req.extensions_mut().insert(Identity::anonymous());
+        return Either::Left(srv.call(req));
+    }
+
+    if req.path() == LOGIN_PATH {
+        return Either::Left(srv.call(req));
+    }
+
+    match identify(auth, &req) {
+        Some(identity) => {
+            req.extensions_mut().insert(identity);
+            Either::Left(srv.call(req))
+        }
+        None if wants_html(&req) => {
+            let redirect = HttpResponse::SeeOther()
+                .insert_header((header::LOCATION, LOGIN_PATH))
+                .finish();
+            Either::Right(future::ready(Err(InternalError::from_response(
+                "Authentication needed",
+                redirect,
+            )
+            .into())))
+        }
+        None => {
+            let unauthorized = HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+                .body("Authentication needed");
+            Either::Right(future::ready(Err(InternalError::from_response(
+                "Authentication needed",
+                unauthorized,
+            )
+            .into())))
+        }
+    }
+}
+
+/// Scope of the client, as attached by the authentication middleware
+pub fn scope(req: &HttpRequest) -> Scope {
+    req.extensions()
+        .get::<Identity>()
+        .map(|i| i.scope)
+        .unwrap_or(Scope::Summary)
+}
+
+fn login_page(error: &str) -> String {
+    LOGIN_PAGE.replace("{error}", error)
+}
+
+pub async fn serve_login_page() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(login_page(""))
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+}
+
+fn session_cookie(req: &HttpRequest, value: String, ttl: Duration) -> Cookie<'static> {
+    Cookie::build(SESSION_COOKIE, value)
+        .path("/")
+        .secure(req.connection_info().scheme() == "https")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .max_age(time::Duration::seconds(ttl.as_secs() as i64))
+        .finish()
+}
+
+pub async fn login(
+    req: HttpRequest,
+    auth: ActixWeb::Data<Auth>,
+    form: ActixWeb::Form<LoginForm>,
+) -> HttpResponse {
+    let form = form.into_inner();
+    let username = form.username.clone();
+
+    // Set by the access control, which runs before authentication
+    let Some(client) = req.extensions().get::<ClientIp>().map(|c| c.0) else {
+        return HttpResponse::Forbidden().finish();
+    };
+    let login = ActixWeb::block({
+        let auth = auth.clone();
+        move || auth.login(client, &form.username, &form.password)
+    })
+    .await
+    .unwrap_or(Login::Failed);
+
+    match login {
+        Login::Session(session_id) => {
+            info!("User {} logged in", username);
+            HttpResponse::SeeOther()
+                .insert_header((header::LOCATION, "/"))
+                .cookie(session_cookie(&req, session_id, auth.session_ttl()))
+                .finish()
+        }
+        Login::Failed => {
+            warn!("Failed login attempt for user {}", username);
+            HttpResponse::Unauthorized()
+                .content_type(ContentType::html())
+                .body(login_page(LOGIN_FAILED_MESSAGE))
+        }
+        Login::Refused => {
+            warn!(
+                "Refused login attempt from {} after too many failures",
+                client
+            );
+            HttpResponse::TooManyRequests()
+                .content_type(ContentType::html())
+                .body(login_page(TOO_MANY_LOGINS_MESSAGE))
+        }
+    }
+}
+
+pub async fn logout(auth: ActixWeb::Data<Auth>, req: HttpRequest) -> HttpResponse {
+    if let Some(session) = req.cookie(SESSION_COOKIE) {
+        auth.logout(session.value());
+    }
+
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, LOGIN_PATH))
+        .cookie(session_cookie(&req, String::new(), Duration::ZERO))
+        .finish()
+}
@@ -0,0 +1,282 @@
+use crate::status;
+use crate::status::collector::schema::{array, integer, map, number, object, optional, string};
+use crate::status::proc;
+
+use serde_json::{json, Map, Value};
+
+// What an endpoint answers with
+enum Content {
+    // Named schema of the JSON body
+    Json(&'static str),
+    // Other media types, described in the summary
+    Text(&'static str),
+}
+
+struct Endpoint {
+    path: &'static str,
+    summary: &'static str,
+    content: Content,
+    // Scope needed when authentication is enabled
+    scope: Option<&'static str>,
+    // Other answers than a success and a missing authentication
+    errors: &'static [(&'static str, &'static str)],
+}
+
+const UNAVAILABLE: (&str, &str) = ("503", "The collector is disabled or failing");
+
+// Endpoints other than the per collector ones
+const ENDPOINTS: [Endpoint; 7] = [
+    Endpoint {
+        path: "/api/v1/status",
+        summary: "Every section at once",
+        content: Content::Json("Status"),
+        scope: None,
+        errors: &[],
+    },
+    Endpoint {
+        path: "/api/v1/net/{iface}",
+        summary: "Traffic of a network interface",
+        content: Content::Json("Interface"),
+        scope: None,
+        errors: &[("404", "No interface with this name"), UNAVAILABLE],
+    },
+    // Sorted and limited, unlike the other sections
+    Endpoint {
+        path: "/api/v1/processes",
+        summary: "Running processes",
+        content: Content::Json("proc"),
+        scope: Some("full"),
+        errors: &[UNAVAILABLE],
+    },
+    Endpoint {
+        path: "/api/v1/stream",
+        summary: "Updates of the status as Server-Sent Events, each one holding the same JSON as the status, or the subscribed sections",
+        content: Content::Text("text/event-stream"),
+        scope: None,
+        errors: &[("400", "Unknown section")],
+    },
+    Endpoint {
+        path: "/metrics",
+        summary: "Monitored resources in the Prometheus text format",
+        content: Content::Text("text/plain; version=0.0.4"),
+        scope: None,
+        errors: &[],
+    },
+    Endpoint {
+        path: "/api/history",
+        summary: "Available metrics without a `metric`, or the points of a metric between `from` and `to`",
+        content: Content::Json("History"),
+        scope: None,
+        errors: &[
+            ("400", "Invalid range"),
+            ("404", "History is not enabled, or the metric is unknown"),
+        ],
+    },
+    Endpoint {
+        path: "/api/admin/sessions",
+        summary: "Open WebSocket sessions and event streams",
+        content: Content::Json("Sessions"),
+        scope: Some("admin"),
+        errors: &[],
+    },
+];
+
+fn schema_ref(schema: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", schema) })
+}
+
+// Every collector's schema is named after it, the status being made of them
+fn schemas() -> Value {
+    let mut schemas = Map::new();
+    let mut sections = Vec::new();
+
+    for c in status::collectors().iter() {
+        schemas.insert(c.name.to_string(), c.schema.clone());
+        sections.push((c.key, optional(schema_ref(c.name))));
+    }
+    // Collectors which didn't answer in time, their sections being stale
+    sections.push((status::TIMED_OUT_KEY, array(string())));
+    // Current error of the failing collectors, by collector name
+    sections.push((status::ERRORS_KEY, map(schema_ref("CollectorError"))));
+    // When every section was collected, by collector name
+    sections.push((status::TIMING_KEY, map(schema_ref("Timing"))));
+
+    schemas.insert(String::from("Status"), object(&sections));
+    schemas.insert(
+        String::from("CollectorError"),
+        object(&[
+            ("kind", string()),
+            ("message", string()),
+            ("first_seen", integer()),
+            ("last_seen", integer()),
+            ("count", integer()),
+        ]),
+    );
+    schemas.insert(
+        String::from("Timing"),
+        object(&[
+            ("collected_at", integer()),
+            ("duration_ms", number()),
+            ("window_ms", optional(number())),
+        ]),
+    );
+    // Interfaces are served on their own too
+    if let Some(Value::Object(net)) = schemas.get_mut("net") {
+        if let Some(interface) = net.insert(String::from("items"), schema_ref("Interface")) {
+            schemas.insert(String::from("Interface"), interface);
+        }
+    }
+    schemas.insert(
+        String::from("Point"),
+        object(&[
+            ("timestamp", integer()),
+            ("min", number()),
+            ("avg", number()),
+            ("max", number()),
+        ]),
+    );
+    schemas.insert(
+        String::from("History"),
+        json!({ "oneOf": [
+            object(&[
+                ("tiers", array(object(&[("resolution", integer()), ("window", integer())]))),
+                ("metrics", array(string())),
+            ]),
+            object(&[
+                ("metric", string()),
+                ("from", integer()),
+                ("to", integer()),
+                ("points", array(schema_ref("Point"))),
+            ]),
+        ]}),
+    );
+    schemas.insert(
+        String::from("Sessions"),
+        object(&[
+            ("count", integer()),
+            (
+                "sessions",
+                array(object(&[
+                    ("id", integer()),
+                    (
+                        "transport",
+                        json!({ "type": "string", "enum": ["websocket", "sse"] }),
+                    ),
+                    ("peer", optional(string())),
+                    ("user", string()),
+                    ("connected_since", integer()),
+                    ("bytes_sent", integer()),
+                ])),
+            ),
+        ]),
+    );
+
+    Value::Object(schemas)
+}
+
+fn parameters(path: &str) -> Value {
+    let mut parameters = Vec::new();
+
+    if path.contains("{iface}") {
+        parameters.push(json!({
+            "name": "iface",
+            "in": "path",
+            "required": true,
+            "schema": string(),
+        }));
+    }
+    let sort = json!({ "type": "string", "enum": ["cpu", "memory"], "default": "cpu" });
+    match path {
+        "/api/v1/processes" => {
+            parameters.push(query("sort", sort));
+            parameters.push(query("limit", integer()));
+        }
+        // Filtered like a WebSocket subscription
+        "/api/v1/stream" => {
+            parameters.push(query("sections", string()));
+            parameters.push(query("interval", integer()));
+            parameters.push(query("top", integer()));
+            parameters.push(query("sort", sort));
+        }
+        // Unix timestamps and seconds
+        "/api/history" => {
+            parameters.push(query("metric", string()));
+            parameters.push(query("from", integer()));
+            parameters.push(query("to", integer()));
+            parameters.push(query("step", integer()));
+        }
+        _ => (),
+    }
+
+    Value::Array(parameters)
+}
+
+fn query(name: &str, schema: Value) -> Value {
+    json!({ "name": name, "in": "query", "schema": schema })
+}
+
+fn operation(endpoint: &Endpoint) -> Value {
+    let content = match endpoint.content {
+        Content::Json(schema) => json!({ "application/json": { "schema": schema_ref(schema) } }),
+        Content::Text(media_type) => json!({ media_type: { "schema": string() } }),
+    };
+    let mut responses = json!({
+        "200": { "description": endpoint.summary, "content": content },
+        "401": { "description": "Authentication is needed" },
+    });
+    if let Some(scope) = endpoint.scope {
+        responses["403"] = json!({ "description": format!("Needs the {} scope", scope) });
+    }
+    for (status, description) in endpoint.errors {
+        responses[*status] = json!({ "description": description });
+    }
+
+    json!({
+        "get": {
+            "summary": endpoint.summary,
+            "parameters": parameters(endpoint.path),
+            "responses": responses,
+        }
+    })
+}
+
+/// OpenAPI 3.1 description of the REST API, built from the endpoints table
+/// and the registered collectors
+pub fn document() -> Value {
+    let mut paths = Map::new();
+
+    for endpoint in &ENDPOINTS {
+        paths.insert(endpoint.path.to_string(), operation(endpoint));
+    }
+    for c in status::collectors().iter() {
+        // Served by /api/v1/processes
+        if c.key == proc::SECTION_KEY {
+            continue;
+        }
+
+        let endpoint = Endpoint {
+            path: "",
+            summary: c.description,
+            content: Content::Json(c.name),
+            scope: c.sensitive.then_some("full"),
+            errors: &[UNAVAILABLE],
+        };
+        paths.insert(format!("/api/v1/{}", c.name), operation(&endpoint));
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "pi-status",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "token": { "type": "http", "scheme": "bearer" },
+            },
+        },
+        "security": [{ "token": [] }],
+    })
+}
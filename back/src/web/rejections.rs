@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use actix_web::dev::ServiceRequest;
+use lazy_static::lazy_static;
+use log::warn;
+
+use super::consts::REJECTION_LOG_INTERVAL;
+
+lazy_static! {
+    static ref REJECTIONS: Mutex<RejectionLog> = Mutex::new(RejectionLog::default());
+}
+
+// Rejections of a client since it was last logged
+struct Rejections {
+    last_logged: Instant,
+    last_seen: Instant,
+    unlogged: u64,
+}
+
+/// Follows the requests rejected by the access control, so that a client
+/// hammering the server doesn't flood the logs: its first rejection is
+/// logged right away, then only a summary of its repetitions every
+/// REJECTION_LOG_INTERVAL
+#[derive(Default)]
+struct RejectionLog {
+    clients: HashMap<IpAddr, Rejections>,
+}
+
+enum Logged {
+    First,
+    // Rejections since the previous log line
+    Repeated(u64),
+    Skipped,
+}
+
+impl RejectionLog {
+    fn reject(&mut self, client: IpAddr, now: Instant) -> Logged {
+        // Clients which stopped are forgotten, with a last summary of what
+        // wasn't logged yet
+        self.clients.retain(|ip, r| {
+            let active = now.duration_since(r.last_seen) <= REJECTION_LOG_INTERVAL;
+            if !active && r.unlogged > 0 {
+                warn!("Rejected {} more requests from {}", r.unlogged, ip);
+            }
+            active
+        });
+
+        let Some(r) = self.clients.get_mut(&client) else {
+            self.clients.insert(
+                client,
+                Rejections {
+                    last_logged: now,
+                    last_seen: now,
+                    unlogged: 0,
+                },
+            );
+            return Logged::First;
+        };
+
+        r.last_seen = now;
+        r.unlogged += 1;
+        if now.duration_since(r.last_logged) < REJECTION_LOG_INTERVAL {
+            return Logged::Skipped;
+        }
+
+        let repeated = r.unlogged;
+        r.last_logged = now;
+        r.unlogged = 0;
+        Logged::Repeated(repeated)
+    }
+}
+
+pub fn log(req: &ServiceRequest, client: IpAddr) {
+    let logged = REJECTIONS.lock().unwrap().reject(client, Instant::now());
+
+    match logged {
+        Logged::First => warn!(
+            "Rejected {} request to {} from {}",
+            req.method(),
+            req.path(),
+            client
+        ),
+        Logged::Repeated(n) => warn!(
+            "Rejected {} request to {} from {} (repeated {} times since the last report)",
+            req.method(),
+            req.path(),
+            client,
+            n
+        ),
+        Logged::Skipped => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repetitions_are_summarized() {
+        let mut log = RejectionLog::default();
+        let client = IpAddr::from([203, 0, 113, 7]);
+        let other = IpAddr::from([203, 0, 113, 8]);
+        let start = Instant::now();
+
+        assert!(matches!(log.reject(client, start), Logged::First));
+        for _ in 0..10 {
+            assert!(matches!(log.reject(client, start), Logged::Skipped));
+        }
+        assert!(matches!(log.reject(other, start), Logged::First));
+
+        let later = start + REJECTION_LOG_INTERVAL;
+        assert!(matches!(log.reject(client, later), Logged::Repeated(11)));
+        assert!(matches!(log.reject(client, later), Logged::Skipped));
+    }
+
+    #[test]
+    fn clients_which_stopped_are_forgotten() {
+        let mut log = RejectionLog::default();
+        let client = IpAddr::from([203, 0, 113, 7]);
+        let start = Instant::now();
+
+        log.reject(client, start);
+        log.reject(IpAddr::from([203, 0, 113, 8]), start);
+
+        let later = start + REJECTION_LOG_INTERVAL * 2;
+        assert!(matches!(log.reject(client, later), Logged::First));
+        assert_eq!(log.clients.len(), 1);
+    }
+}
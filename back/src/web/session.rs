@@ -0,0 +1,100 @@
+use crate::auth::Identity;
+use crate::status;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use actix_web::{HttpMessage, HttpRequest};
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use super::ClientIp;
+
+lazy_static! {
+    static ref SESSIONS: Mutex<HashMap<u64, Arc<SessionEntry>>> = Mutex::new(HashMap::new());
+}
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    WebSocket,
+    Sse,
+}
+
+/// Bookkeeping of a client receiving updates, shared between the session
+/// and the admin endpoint
+pub struct SessionEntry {
+    pub id: u64,
+    transport: Transport,
+    peer: Option<String>,
+    user: String,
+    connected_since: u64,
+    bytes_sent: AtomicU64,
+}
+
+#[derive(Serialize)]
+pub struct SessionInfo {
+    pub id: u64,
+    pub transport: Transport,
+    pub peer: Option<String>,
+    pub user: String,
+    pub connected_since: u64,
+    pub bytes_sent: u64,
+}
+
+impl SessionEntry {
+    pub fn add_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+pub fn register(req: &HttpRequest, transport: Transport) -> Arc<SessionEntry> {
+    let peer = req
+        .extensions()
+        .get::<ClientIp>()
+        .map(|c| c.0.to_string())
+        .or_else(|| req.peer_addr().map(|a| a.ip().to_string()));
+    let user = req
+        .extensions()
+        .get::<Identity>()
+        .map(|i| i.name.clone())
+        .unwrap_or_default();
+
+    let entry = Arc::new(SessionEntry {
+        id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+        transport,
+        peer,
+        user,
+        connected_since: status::now_secs(),
+        bytes_sent: AtomicU64::new(0),
+    });
+
+    SESSIONS.lock().unwrap().insert(entry.id, entry.clone());
+    entry
+}
+
+pub fn unregister(id: u64) {
+    SESSIONS.lock().unwrap().remove(&id);
+}
+
+pub fn list() -> Vec<SessionInfo> {
+    let mut sessions = SESSIONS
+        .lock()
+        .unwrap()
+        .values()
+        .map(|s| SessionInfo {
+            id: s.id,
+            transport: s.transport,
+            peer: s.peer.clone(),
+            user: s.user.clone(),
+            connected_since: s.connected_since,
+            bytes_sent: s.bytes_sent.load(Ordering::Relaxed),
+        })
+        .collect::<Vec<SessionInfo>>();
+
+    sessions.sort_by_key(|s| s.id);
+    sessions
+}
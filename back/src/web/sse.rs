@@ -0,0 +1,216 @@
+use crate::auth::Scope;
+use crate::history::HISTORY;
+use crate::status::{self, ACTIVE_CLIENTS, STATUS};
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::rt::time;
+use actix_web::web::Bytes;
+use actix_web::{web as ActixWeb, Error as ActixError, HttpRequest, HttpResponse};
+use futures_util::stream::{self, StreamExt};
+use log::error;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::api::fresh_sections;
+use super::consts::SSE_RETRY_MS;
+use super::encoding::{self, Payload, WireFormat};
+use super::login;
+use super::session::{self, SessionEntry, Transport};
+use super::subscription::{self, ProcessSelection, ProcessSort, Subscription};
+use super::ws::DataConfig;
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    // Comma separated, e.g. `net,cpu`
+    sections: Option<String>,
+    // Milliseconds
+    interval: Option<u64>,
+    top: Option<usize>,
+    sort: Option<ProcessSort>,
+}
+
+impl StreamQuery {
+    // The whole status is streamed when nothing is filtered
+    fn subscription(&self) -> Option<Subscription> {
+        if self.sections.is_none() && self.interval.is_none() && self.top.is_none() {
+            return None;
+        }
+
+        let sections = self
+            .sections
+            .as_ref()
+            .map(|names| names.split(',').map(|n| n.trim().to_string()).collect());
+
+        Some(Subscription {
+            sections,
+            interval: self.interval,
+            processes: self.top.map(|top| ProcessSelection {
+                top,
+                sort: self.sort.unwrap_or(ProcessSort::Cpu),
+            }),
+            delta: false,
+        })
+    }
+}
+
+// Releases everything the stream holds once actix drops it, which happens as
+// soon as a write to a disconnected client fails
+struct StreamGuard {
+    entry: Arc<SessionEntry>,
+    sections: Vec<String>,
+}
+
+impl StreamGuard {
+    fn new(entry: Arc<SessionEntry>, sections: Vec<String>) -> Self {
+        ACTIVE_CLIENTS.fetch_add(1, Ordering::Relaxed);
+        status::subscribe(&sections);
+
+        StreamGuard { entry, sections }
+    }
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        ACTIVE_CLIENTS.fetch_sub(1, Ordering::Relaxed);
+        status::unsubscribe(&self.sections);
+        session::unregister(self.entry.id);
+    }
+}
+
+fn now_millis() -> u64 {
+    UNIX_EPOCH
+        .elapsed()
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn event(name: Option<&str>, id: Option<u64>, data: &str) -> Bytes {
+    let mut event = String::new();
+    if let Some(name) = name {
+        event.push_str(&format!("event: {}\n", name));
+    }
+    if let Some(id) = id {
+        event.push_str(&format!("id: {}\n", id));
+    }
+    event.push_str(&format!("data: {}\n\n", data));
+
+    Bytes::from(event)
+}
+
+// Current data of the streamed sections, as an update event
+fn update_event(subscription: Option<&Subscription>, sections: &[String], scope: Scope) -> Bytes {
+    let data = match subscription {
+        Some(s) => subscription::render(&STATUS.read().unwrap(), s, sections).to_string(),
+        None => match encoding::shared_status(WireFormat::JSON, scope < Scope::Full) {
+            Ok(Payload::Text(text)) => text.to_string(),
+            Ok(Payload::Binary(_)) => String::new(),
+            Err(e) => {
+                error!("Could not encode the status: {}", e);
+                String::new()
+            }
+        },
+    };
+
+    event(None, Some(now_millis()), &data)
+}
+
+// Reconnecting clients send the id of the last event they got, which is its
+// timestamp in milliseconds, so the history they missed can be sent first.
+// Only the history can fill that gap: without it, they only get the current
+// data, which every stream starts with
+fn resume_events(req: &HttpRequest) -> Vec<Bytes> {
+    let mut events = vec![Bytes::from(format!("retry: {}\n\n", SSE_RETRY_MS))];
+
+    let last_event = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.trim().parse::<u64>().ok());
+
+    if let Some(last_event) = last_event {
+        let missed_secs = now_millis().saturating_sub(last_event) / 1000 + 1;
+        let history = HISTORY
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|h| h.backfill(status::now_secs(), missed_secs));
+
+        if let Some(history) = history {
+            events.push(event(
+                Some("history"),
+                None,
+                &json!({ "history": history }).to_string(),
+            ));
+        }
+    }
+
+    events
+}
+
+pub async fn serve_stream(
+    req: HttpRequest,
+    query: ActixWeb::Query<StreamQuery>,
+    config: ActixWeb::Data<DataConfig>,
+) -> Result<HttpResponse, ActixError> {
+    let subscription = query.subscription();
+    let requested = match &subscription {
+        Some(s) => s.sections(),
+        None => Ok(subscription::all_sections()),
+    };
+    let requested = match requested {
+        Ok(r) => r,
+        Err(e) => return Ok(HttpResponse::BadRequest().body(e)),
+    };
+
+    let scope = login::scope(&req);
+    let sections = subscription::scope_sections(scope, &requested);
+
+    // Same cadence rules as WebSocket sessions
+    let interval = subscription
+        .as_ref()
+        .and_then(|s| s.interval())
+        .map_or(config.update_interval, |i| i.max(config.update_interval));
+
+    let guard = StreamGuard::new(session::register(&req, Transport::Sse), sections);
+
+    // The current data is sent right away, after the history a reconnecting
+    // client missed, the updates following one interval later
+    let keys = guard
+        .sections
+        .iter()
+        .filter_map(|s| status::collector(s).map(|c| c.key))
+        .collect();
+    fresh_sections(&config, keys).await;
+    let mut initial = resume_events(&req);
+    initial.push(update_event(subscription.as_ref(), &guard.sections, scope));
+    guard
+        .entry
+        .add_sent(initial.iter().map(Bytes::len).sum::<usize>());
+    let initial = stream::iter(initial);
+
+    let first_tick = time::Instant::now() + interval;
+    let updates = stream::unfold(
+        (guard, time::interval_at(first_tick, interval)),
+        move |(guard, mut ticks)| {
+            let subscription = subscription.clone();
+            async move {
+                ticks.tick().await;
+
+                let event = update_event(subscription.as_ref(), &guard.sections, scope);
+                guard.entry.add_sent(event.len());
+                Some((event, (guard, ticks)))
+            }
+        },
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        // Keeps reverse proxies like nginx from buffering the stream
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(initial.chain(updates).map(Ok::<Bytes, ActixError>)))
+}
@@ -0,0 +1,160 @@
+use crate::auth::Scope;
+use crate::status::proc::{self, Process};
+use crate::status::{self, Status};
+
+use std::cmp::Reverse;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Messages WebSocket clients can send, e.g.
+/// `{"type": "subscribe", "sections": ["net", "proc"], "interval": 2000,
+/// "processes": {"top": 10, "sort": "cpu"}}`
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ClientMessage {
+    Subscribe(Subscription),
+    // Asks delta-encoded sessions for a full snapshot
+    Resync,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessSort {
+    Cpu,
+    Memory,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessSelection {
+    pub top: usize,
+    pub sort: ProcessSort,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Subscription {
+    // Collector names, every section when not set
+    pub sections: Option<Vec<String>>,
+    // Milliseconds, never shorter than the server's update interval
+    pub interval: Option<u64>,
+    pub processes: Option<ProcessSelection>,
+    // Send JSON patches against the previous message instead of snapshots
+    #[serde(default)]
+    pub delta: bool,
+}
+
+impl Subscription {
+    /// Requested sections in the order of the status, failing on unknown
+    /// ones
+    pub fn sections(&self) -> Result<Vec<String>, String> {
+        let collectors = status::collectors();
+
+        if let Some(sections) = &self.sections {
+            if let Some(unknown) = sections
+                .iter()
+                .find(|s| !collectors.iter().any(|c| c.name == s.as_str()))
+            {
+                return Err(format!("Unknown section `{}`", unknown));
+            }
+        }
+
+        Ok(collectors
+            .iter()
+            .filter(|c| {
+                self.sections
+                    .as_ref()
+                    .is_none_or(|s| s.iter().any(|s| s == c.name))
+            })
+            .map(|c| c.name.to_string())
+            .collect())
+    }
+
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval.map(Duration::from_millis)
+    }
+}
+
+pub fn top_processes(processes: &[Process], selection: &ProcessSelection) -> Vec<Process> {
+    let mut processes = processes.to_vec();
+    match selection.sort {
+        ProcessSort::Cpu => processes.sort_by(|a, b| {
+            let usage = |p: &Process| p.cpu_usage.unwrap_or(0.0);
+            usage(b).total_cmp(&usage(a))
+        }),
+        ProcessSort::Memory => processes.sort_by_key(|p| Reverse(p.mem)),
+    }
+    processes.truncate(selection.top);
+    processes
+}
+
+/// Every section, in the order of the status
+pub fn all_sections() -> Vec<String> {
+    status::collectors()
+        .iter()
+        .map(|c| c.name.to_string())
+        .collect()
+}
+
+// Sensitive sections, like processes, are not part of the summary scope
+pub fn scope_sections(scope: Scope, sections: &[String]) -> Vec<String> {
+    sections
+        .iter()
+        .filter(|s| scope >= Scope::Full || status::collector(s).is_some_and(|c| !c.sensitive))
+        .cloned()
+        .collect()
+}
+
+/// The subscribed sections only, with the same keys as the full status
+pub fn render(status: &Status, subscription: &Subscription, sections: &[String]) -> Value {
+    let mut message = Map::new();
+
+    for info in status::collectors().iter() {
+        if !sections.iter().any(|s| s == info.name) {
+            continue;
+        }
+
+        let value = match (info.key, &subscription.processes) {
+            (proc::SECTION_KEY, Some(selection)) => status
+                .section::<Vec<Process>>(proc::SECTION_KEY)
+                .and_then(|p| serde_json::to_value(top_processes(&p, selection)).ok()),
+            (key, _) => status.get(key).cloned(),
+        };
+
+        message.insert(info.key.to_string(), value.unwrap_or(Value::Null));
+    }
+
+    let timed_out = status
+        .timed_out()
+        .filter(|name| sections.iter().any(|s| s == name))
+        .collect();
+    message.insert(String::from(status::TIMED_OUT_KEY), timed_out);
+
+    let errors = status
+        .errors()
+        .filter(|(name, _)| sections.iter().any(|s| s == name))
+        .map(|(name, e)| {
+            (
+                name.to_string(),
+                serde_json::to_value(e).unwrap_or_default(),
+            )
+        })
+        .collect();
+    message.insert(String::from(status::ERRORS_KEY), Value::Object(errors));
+
+    let timing = status
+        .timing()
+        .filter(|(name, _)| sections.iter().any(|s| s == name))
+        .map(|(name, t)| {
+            (
+                name.to_string(),
+                serde_json::to_value(t).unwrap_or_default(),
+            )
+        })
+        .collect();
+    message.insert(String::from(status::TIMING_KEY), Value::Object(timing));
+
+    Value::Object(message)
+}
@@ -0,0 +1,247 @@
+use crate::auth::Scope;
+use crate::history::{Point, HISTORY};
+use crate::status::{self, ACTIVE_CLIENTS, STATUS};
+
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix::{Actor, ActorContext, AsyncContext, SpawnHandle, StreamHandler};
+use actix_web::{web as ActixWeb, Error as ActixError, HttpRequest, HttpResponse};
+use actix_web_actors::ws::{self, Message, ProtocolError};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use super::consts::{CLIENT_TIMEOUT, HEARTBEAT_INTERVAL};
+use super::delta::DeltaEncoder;
+use super::encoding::{self, Payload, WireFormat};
+use super::login;
+use super::session::{self, SessionEntry, Transport};
+use super::subscription::{self, ClientMessage, Subscription};
+
+pub struct DataConfig {
+    pub update_interval: Duration,
+}
+
+#[derive(Deserialize)]
+pub struct WsDataQuery {
+    backfill: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct HistoryBackfill {
+    history: HashMap<String, Vec<Point>>,
+}
+
+#[derive(Serialize)]
+struct ErrorMessage {
+    error: String,
+}
+
+pub async fn serve_data(
+    req: HttpRequest,
+    stream: ActixWeb::Payload,
+    query: ActixWeb::Query<WsDataQuery>,
+    config: ActixWeb::Data<DataConfig>,
+) -> Result<HttpResponse, ActixError> {
+    let scope = login::scope(&req);
+    let protocol = encoding::negotiate(&req);
+    let format = protocol
+        .and_then(WireFormat::from_protocol)
+        .unwrap_or(WireFormat::JSON);
+
+    let session = WsDataSession {
+        scope,
+        format,
+        backfill: query.backfill,
+        interval: config.update_interval,
+        entry: session::register(&req, Transport::WebSocket),
+        last_heartbeat: Instant::now(),
+        subscription: None,
+        sections: subscription::scope_sections(scope, &subscription::all_sections()),
+        update_handle: None,
+        delta: None,
+    };
+
+    let protocols = protocol.map(|p| vec![p]).unwrap_or_default();
+    ws::WsResponseBuilder::new(session, &req, stream)
+        .protocols(&protocols)
+        .start()
+}
+
+struct WsDataSession {
+    scope: Scope,
+    format: WireFormat,
+    backfill: Option<u64>,
+    interval: Duration,
+    entry: Arc<SessionEntry>,
+    last_heartbeat: Instant,
+    // Clients that never subscribe get the whole status
+    subscription: Option<Subscription>,
+    sections: Vec<String>,
+    update_handle: Option<SpawnHandle>,
+    delta: Option<DeltaEncoder>,
+}
+
+impl WsDataSession {
+    fn send(&self, ctx: &mut ws::WebsocketContext<Self>, payload: anyhow::Result<Payload>) {
+        match payload {
+            Ok(Payload::Text(text)) => {
+                self.entry.add_sent(text.len());
+                ctx.text(text);
+            }
+            Ok(Payload::Binary(bytes)) => {
+                self.entry.add_sent(bytes.len());
+                ctx.binary(bytes);
+            }
+            Err(e) => error!("Could not encode WebSocket message: {}", e),
+        }
+    }
+
+    fn send_update(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let payload = match &self.subscription {
+            Some(s) => {
+                let value = subscription::render(&STATUS.read().unwrap(), s, &self.sections);
+                match &mut self.delta {
+                    Some(encoder) => self.format.encode(&encoder.encode(value)),
+                    None => self.format.encode(&value),
+                }
+            }
+            None => encoding::shared_status(self.format, self.scope < Scope::Full),
+        };
+
+        self.send(ctx, payload);
+    }
+
+    fn schedule_updates(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        if let Some(handle) = self.update_handle.take() {
+            ctx.cancel_future(handle);
+        }
+
+        // Updating faster than the data changes would only repeat it
+        let interval = self
+            .subscription
+            .as_ref()
+            .and_then(|s| s.interval())
+            .map_or(self.interval, |i| i.max(self.interval));
+
+        ctx.run_later(Duration::ZERO, |act, ctx| act.send_update(ctx));
+        self.update_handle = Some(ctx.run_interval(interval, |act, ctx| act.send_update(ctx)));
+    }
+
+    fn subscribe(&mut self, subscription: Subscription, ctx: &mut ws::WebsocketContext<Self>) {
+        let sections = match subscription.sections() {
+            Ok(s) => subscription::scope_sections(self.scope, &s),
+            Err(e) => {
+                let message = ErrorMessage {
+                    error: format!("Invalid message: {}", e),
+                };
+                self.send(ctx, self.format.encode(&message));
+                return;
+            }
+        };
+
+        status::unsubscribe(&self.sections);
+        status::subscribe(&sections);
+        self.sections = sections;
+        self.delta = subscription.delta.then(DeltaEncoder::new);
+        self.subscription = Some(subscription);
+
+        self.schedule_updates(ctx);
+    }
+
+    // Text messages are always JSON, binary ones use the session's format
+    fn handle_message(
+        &mut self,
+        message: anyhow::Result<ClientMessage>,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) {
+        match message {
+            Ok(ClientMessage::Subscribe(s)) => self.subscribe(s, ctx),
+            Ok(ClientMessage::Resync) => {
+                if let Some(encoder) = &mut self.delta {
+                    encoder.resync();
+                    self.send_update(ctx);
+                }
+            }
+            Err(e) => {
+                let message = ErrorMessage {
+                    error: format!("Invalid message: {}", e),
+                };
+                self.send(ctx, self.format.encode(&message));
+            }
+        }
+    }
+}
+
+impl StreamHandler<Result<Message, ProtocolError>> for WsDataSession {
+    fn handle(&mut self, msg: Result<Message, ProtocolError>, ctx: &mut Self::Context) {
+        // Anything coming from the client proves it's still there
+        self.last_heartbeat = Instant::now();
+
+        match msg {
+            Ok(m) => match m {
+                Message::Text(text) => {
+                    self.handle_message(serde_json::from_str(&text).map_err(Into::into), ctx)
+                }
+                Message::Binary(bytes) => self.handle_message(self.format.decode(&bytes), ctx),
+                Message::Ping(p) => ctx.pong(&p),
+                Message::Close(reason) => {
+                    ctx.close(reason);
+                    ctx.stop();
+                }
+                _ => (),
+            },
+            Err(e) => {
+                error!("Error occurred in WS receive operation: {}", e);
+                ctx.stop();
+            }
+        }
+    }
+
+    fn finished(&mut self, ctx: &mut Self::Context) {
+        ctx.stop();
+    }
+}
+
+impl Actor for WsDataSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    // Every way a session can end goes through here, including clients
+    // vanishing without closing the connection
+    fn stopped(&mut self, _: &mut Self::Context) {
+        ACTIVE_CLIENTS.fetch_sub(1, Ordering::Relaxed);
+        status::unsubscribe(&self.sections);
+        session::unregister(self.entry.id);
+    }
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ACTIVE_CLIENTS.fetch_add(1, Ordering::Relaxed);
+        status::subscribe(&self.sections);
+
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if act.last_heartbeat.elapsed() > CLIENT_TIMEOUT {
+                info!("Dropping unresponsive WebSocket session {}", act.entry.id);
+                ctx.stop();
+                return;
+            }
+
+            ctx.ping(b"");
+        });
+
+        if let Some(seconds) = self.backfill {
+            let history = HISTORY
+                .read()
+                .unwrap()
+                .as_ref()
+                .map(|h| h.backfill(status::now_secs(), seconds));
+
+            if let Some(history) = history {
+                self.send(ctx, self.format.encode(&HistoryBackfill { history }));
+            }
+        }
+
+        self.schedule_updates(ctx);
+    }
+}
@@ -0,0 +1,537 @@
+mod common;
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use pi_status::status;
+use pi_status::status::collector::Collector;
+use pi_status::status::cpu::CpuData;
+use pi_status::status::cpufreq::CpuFreqData;
+use pi_status::status::disk::FsData;
+use pi_status::status::host::HostData;
+use pi_status::status::load::LoadData;
+use pi_status::status::net::IfaStats;
+use pi_status::status::proc::Process;
+use pi_status::status::ram::RamData;
+use pi_status::status::source::{FsStats, Source};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use common::FixtureSource;
+
+const MACHINES: [&str; 5] = ["pi3", "pi4", "pi5", "x86", "container"];
+
+fn collector(source: &Arc<FixtureSource>, name: &str) -> Box<dyn Collector> {
+    let source: Arc<dyn Source> = source.clone();
+    let mut collector = status::builtin_collectors(source)
+        .into_iter()
+        .find(|c| c.name() == name)
+        .unwrap();
+
+    collector.init().unwrap();
+    collector
+}
+
+fn collect<T: DeserializeOwned>(collector: &mut Box<dyn Collector>) -> T {
+    serde_json::from_value(collector.collect().unwrap()).unwrap()
+}
+
+fn interface<'a>(stats: &'a [IfaStats], name: &str) -> &'a IfaStats {
+    stats
+        .iter()
+        .find(|s| s.interface.interface_name == name)
+        .unwrap()
+}
+
+fn process<'a>(processes: &'a [Process], name: &str) -> &'a Process {
+    processes.iter().find(|p| p.name == name).unwrap()
+}
+
+#[test]
+fn every_collector_reads_every_machine() {
+    for machine in MACHINES {
+        let source = FixtureSource::open(machine);
+        let source: Arc<dyn Source> = source;
+
+        for mut collector in status::builtin_collectors(source) {
+            collector.init().unwrap();
+            // Collectors computing deltas only report from their second run
+            let collected = collector.collect().and_then(|_| collector.collect());
+
+            // Containers without PST_HOST_ROOT have no thermal zone
+            if machine == "container" && collector.name() == "temp" {
+                let e = collected.unwrap_err();
+                let e = e.downcast_ref::<io::Error>().unwrap();
+                assert_eq!(e.kind(), io::ErrorKind::NotFound);
+                continue;
+            }
+
+            let data = collected.unwrap_or_else(|e| {
+                panic!("{} on {}: {}", collector.name(), machine, e);
+            });
+            assert!(!data.is_null(), "{} on {}", collector.name(), machine);
+        }
+    }
+}
+
+#[test]
+fn host() {
+    let source = FixtureSource::open("pi4");
+    let host: HostData = collect(&mut collector(&source, "host"));
+
+    assert_eq!(host.hostname, "raspberrypi");
+    assert_eq!(host.uptime, 81432);
+}
+
+#[test]
+fn ram() {
+    let source = FixtureSource::open("pi3");
+    let ram: RamData = collect(&mut collector(&source, "ram"));
+
+    assert_eq!(ram.total, 944268 * 1024);
+    assert_eq!(ram.free, 312440 * 1024);
+    assert_eq!(ram.available, 611232 * 1024);
+    assert_eq!(ram.cached, 302112 * 1024);
+    assert_eq!(ram.used, (944268 - 611232) * 1024);
+}
+
+#[test]
+fn temp() {
+    let source = FixtureSource::open("pi4");
+    let mut temp = collector(&source, "temp");
+
+    assert_eq!(temp.collect().unwrap(), Value::from(48.686));
+    source.advance();
+    assert_eq!(temp.collect().unwrap(), Value::from(49.173));
+}
+
+#[test]
+fn disk_skips_excluded_mounts() {
+    let source = FixtureSource::open("x86");
+    let filesystems: Vec<FsData> = collect(&mut collector(&source, "disk"));
+    let mountpoints = filesystems
+        .iter()
+        .map(|f| f.mountpoint.as_str())
+        .collect::<Vec<&str>>();
+
+    // Docker's overlays are under the excluded /var
+    assert_eq!(mountpoints, ["/", "/boot/efi", "/home"]);
+    assert_eq!(filesystems[2].filesystem, "/dev/sda1");
+    assert_eq!(filesystems[2].total, 1967317549056);
+    assert_eq!(filesystems[2].available, 1102223360000);
+}
+
+#[test]
+fn disk_reports_network_mounts() {
+    let source = FixtureSource::open("pi4");
+    let filesystems: Vec<FsData> = collect(&mut collector(&source, "disk"));
+
+    let media = filesystems
+        .iter()
+        .find(|f| f.mountpoint == "/mnt/media")
+        .unwrap();
+    assert_eq!(media.filesystem, "nas.lan:/export/media");
+    assert_eq!(media.total, 3985729650688);
+}
+
+#[test]
+fn cpu_usage_is_the_difference_between_snapshots() {
+    let source = FixtureSource::open("pi3");
+    let mut cpu = collector(&source, "cpu");
+
+    // Counters are since boot, so the first run is only a baseline
+    let first: Option<CpuData> = collect(&mut cpu);
+    assert!(first.is_none());
+
+    source.advance();
+    let data = cpu.collect().unwrap();
+    let second: CpuData = serde_json::from_value(data.clone()).unwrap();
+    let core_3 = &second.cores[&3];
+    assert_eq!(core_3.jiffies.user, 88);
+    assert_eq!(core_3.jiffies.system, 9);
+    assert_eq!(core_3.jiffies.idle, 3);
+    assert!((core_3.percent.busy - 97.0).abs() < 1e-9);
+    assert!((core_3.percent.user - 88.0).abs() < 1e-9);
+    assert_eq!(second.total.jiffies.user, 60 + 10 + 5 + 88);
+    // The fixture snapshots are a second apart
+    assert_eq!(second.context_switches_per_sec, Some(1489.0));
+    assert_eq!(second.forks_per_sec, Some(3.0));
+    assert_eq!(second.procs_running, 2);
+    assert_eq!(second.procs_blocked, 0);
+    assert_eq!(second.boot_time, 1699969888);
+
+    let samples = cpu.samples(&data);
+    let core_3 = samples.iter().find(|(n, _)| n == "cpu.3.usage").unwrap();
+    assert!((core_3.1 - 97.0).abs() < 1e-9);
+}
+
+// /proc/stat of a quad core with core 2 going offline and coming back, a
+// second apart
+const HOTPLUG_STATS: [&str; 4] = [
+    "cpu  400 0 100 4000 0 0 0 0 0 0
+cpu0 100 0 25 1000 0 0 0 0 0 0
+cpu1 100 0 25 1000 0 0 0 0 0 0
+cpu2 100 0 25 1000 0 0 0 0 0 0
+cpu3 100 0 25 1000 0 0 0 0 0 0
+ctxt 1000
+btime 1699969888
+processes 500
+",
+    // The idle time of core 3 going backwards
+    "cpu  500 0 100 4100 0 0 0 0 0 0
+cpu0 150 0 25 1050 0 0 0 0 0 0
+cpu1 150 0 25 1050 0 0 0 0 0 0
+cpu3 150 0 25 990 0 0 0 0 0 0
+ctxt 1500
+btime 1699969888
+processes 510
+",
+    "cpu  600 0 100 4200 0 0 0 0 0 0
+cpu0 160 0 25 1140 0 0 0 0 0 0
+cpu1 160 0 25 1140 0 0 0 0 0 0
+cpu2 120 0 25 1010 0 0 0 0 0 0
+cpu3 160 0 25 1080 0 0 0 0 0 0
+ctxt 2000
+btime 1699969888
+processes 520
+",
+    "cpu  700 0 100 4300 0 0 0 0 0 0
+cpu0 170 0 25 1230 0 0 0 0 0 0
+cpu1 170 0 25 1230 0 0 0 0 0 0
+cpu2 170 0 25 1060 0 0 0 0 0 0
+cpu3 170 0 25 1170 0 0 0 0 0 0
+ctxt 2500
+btime 1699969888
+processes 530
+",
+];
+
+struct Hotplug(AtomicUsize);
+
+impl Source for Hotplug {
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        match path {
+            "/proc/stat" => Ok(HOTPLUG_STATS[self.0.load(Ordering::Relaxed)].to_string()),
+            "/sys/devices/system/cpu/present" => Ok(String::from("0-3\n")),
+            _ => Err(io::Error::from(io::ErrorKind::NotFound)),
+        }
+    }
+
+    fn read_dir(&self, _: &str) -> io::Result<Vec<String>> {
+        Err(io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn statvfs(&self, _: &str) -> io::Result<FsStats> {
+        Err(io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn monotonic(&self) -> Duration {
+        Duration::from_secs(self.0.load(Ordering::Relaxed) as u64)
+    }
+}
+
+#[test]
+fn cpu_usage_follows_cores_going_offline() {
+    let source = Arc::new(Hotplug(AtomicUsize::new(0)));
+    let mut cpu = status::builtin_collectors(source.clone())
+        .into_iter()
+        .find(|c| c.name() == "cpu")
+        .unwrap();
+    let ids = |data: &CpuData| data.cores.keys().copied().collect::<Vec<u32>>();
+
+    let first: Option<CpuData> = collect(&mut cpu);
+    assert!(first.is_none());
+
+    source.0.store(1, Ordering::Relaxed);
+    let offline: CpuData = collect(&mut cpu);
+    assert_eq!(ids(&offline), [0, 1, 3]);
+    assert_eq!(offline.offline, [2]);
+    assert_eq!(offline.cores[&3].jiffies.idle, 0);
+    assert_eq!(offline.cores[&3].jiffies.user, 50);
+    assert_eq!(offline.context_switches_per_sec, Some(500.0));
+
+    // Back online, core 2 is re-baselined without reporting its load since
+    // boot
+    source.0.store(2, Ordering::Relaxed);
+    let back: CpuData = collect(&mut cpu);
+    assert_eq!(ids(&back), [0, 1, 3]);
+    assert!(back.offline.is_empty());
+    assert_eq!(back.cores[&3].jiffies.idle, 90);
+
+    source.0.store(3, Ordering::Relaxed);
+    let after: CpuData = collect(&mut cpu);
+    assert_eq!(ids(&after), [0, 1, 2, 3]);
+    assert_eq!(after.cores[&2].jiffies.user, 50);
+    assert_eq!(after.cores[&2].jiffies.idle, 50);
+    assert!((after.cores[&2].percent.busy - 50.0).abs() < 1e-9);
+}
+
+#[test]
+fn cpu_frequencies_and_throttling() {
+    let source = FixtureSource::open("pi4");
+    let mut cpufreq = collector(&source, "cpufreq");
+
+    let first: CpuFreqData = collect(&mut cpufreq);
+    assert_eq!(first.cores.len(), 4);
+    let core = &first.cores[&0];
+    assert_eq!(core.cur_khz, 1500000);
+    assert_eq!((core.min_khz, core.max_khz), (600000, 1800000));
+    assert_eq!(core.governor, "ondemand");
+    assert_eq!(core.available_governors.len(), 6);
+    // The times being since boot, the first run is only a baseline
+    assert!(core.time_in_state_ms.is_none());
+    let throttling = first.throttling.unwrap();
+    assert!(!throttling.now.throttled && !throttling.since_boot.throttled);
+
+    // Under-voltage making the firmware throttle
+    source.advance();
+    let second: CpuFreqData = collect(&mut cpufreq);
+    let times = second.cores[&3].time_in_state_ms.as_ref().unwrap();
+    assert_eq!(times[&600000], 200);
+    assert_eq!(times[&1800000], 800);
+    assert_eq!(times[&1000000], 0);
+    let throttling = second.throttling.unwrap();
+    assert_eq!(throttling.flags, 0x50005);
+    assert!(throttling.now.under_voltage && throttling.now.throttled);
+    assert!(!throttling.now.frequency_capped && !throttling.now.soft_temp_limit);
+    assert_eq!(throttling.now, throttling.since_boot);
+
+    // Over, but remembered until the next boot
+    source.advance();
+    let data = cpufreq.collect().unwrap();
+    let third: CpuFreqData = serde_json::from_value(data.clone()).unwrap();
+    assert_eq!(third.cores[&0].cur_khz, 600000);
+    let throttling = third.throttling.unwrap();
+    assert!(!throttling.now.under_voltage && !throttling.now.throttled);
+    assert!(throttling.since_boot.under_voltage && throttling.since_boot.throttled);
+
+    let samples = cpufreq.samples(&data);
+    assert!(samples.contains(&(String::from("cpufreq.2.khz"), 600000.0)));
+    assert!(samples.contains(&(String::from("cpufreq.throttled"), 0.0)));
+}
+
+#[test]
+fn cpu_frequencies_are_optional() {
+    let source = FixtureSource::open("x86");
+    let data: CpuFreqData = collect(&mut collector(&source, "cpufreq"));
+
+    assert!(data.cores.is_empty());
+    assert!(data.throttling.is_none());
+}
+
+#[test]
+fn load_and_pressure_stalls() {
+    let source = FixtureSource::open("pi4");
+    let mut load = collector(&source, "load");
+
+    let first: LoadData = collect(&mut load);
+    assert_eq!((first.load1, first.load5, first.load15), (0.41, 0.52, 0.48));
+    assert_eq!(
+        (first.runnable, first.tasks, first.last_pid),
+        (1, 287, 120330)
+    );
+    // Nothing to compare the totals since boot with on the first run
+    let pressure = first.pressure.unwrap();
+    assert_eq!(pressure.io.some.total_us, 4021877);
+    assert!(pressure.io.some.stalled_us.is_none());
+
+    source.advance();
+    source.advance();
+    let data = load.collect().unwrap();
+    let third: LoadData = serde_json::from_value(data.clone()).unwrap();
+    assert_eq!(third.runnable, 4);
+    // Stalling on the SD card rather than on the CPU
+    let pressure = third.pressure.unwrap();
+    assert_eq!(pressure.io.some.avg10, 42.17);
+    assert_eq!(pressure.io.some.total_us, 4521377);
+    assert_eq!(pressure.io.some.stalled_us, Some(4521377 - 4021877));
+    assert_eq!(
+        pressure.io.full.unwrap().stalled_us,
+        Some(3738102 - 3288102)
+    );
+    assert_eq!(pressure.cpu.some.stalled_us, Some(37000));
+    assert_eq!(pressure.cpu.full.unwrap().stalled_us, Some(0));
+    assert_eq!(pressure.memory.some.stalled_us, Some(0));
+
+    let samples = load.samples(&data);
+    assert!(samples.contains(&(String::from("load.1"), 1.93)));
+    assert!(samples.contains(&(String::from("load.pressure.io.full"), 35.02)));
+}
+
+// Booted with `psi=0`
+struct PsiDisabled(Arc<FixtureSource>);
+
+impl Source for PsiDisabled {
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        match path.starts_with("/proc/pressure/") {
+            true => Err(io::Error::from(io::ErrorKind::Unsupported)),
+            false => self.0.read_to_string(path),
+        }
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.0.read_dir(path)
+    }
+
+    fn statvfs(&self, mountpoint: &str) -> io::Result<FsStats> {
+        self.0.statvfs(mountpoint)
+    }
+}
+
+#[test]
+fn pressure_stalls_are_optional() {
+    // Before Linux 5.13, without a full line for the CPU
+    let source = FixtureSource::open("x86");
+    let load: LoadData = collect(&mut collector(&source, "load"));
+    let pressure = load.pressure.unwrap();
+    assert!(pressure.cpu.full.is_none());
+    assert!(pressure.io.full.is_some());
+
+    // Without PSI
+    let source = FixtureSource::open("pi3");
+    let load: LoadData = collect(&mut collector(&source, "load"));
+    assert_eq!(load.tasks, 121);
+    assert!(load.pressure.is_none());
+
+    let source: Arc<dyn Source> = Arc::new(PsiDisabled(FixtureSource::open("pi4")));
+    let mut load = status::builtin_collectors(source)
+        .into_iter()
+        .find(|c| c.name() == "load")
+        .unwrap();
+    let load: LoadData = collect(&mut load);
+    assert!(load.pressure.is_none());
+}
+
+#[test]
+fn net_speeds_are_computed_between_snapshots() {
+    let source = FixtureSource::open("pi4");
+    let mut net = collector(&source, "net");
+
+    let first: Vec<IfaStats> = collect(&mut net);
+    assert_eq!(interface(&first, "eth0").upload_speed, 0.0);
+
+    source.advance();
+    let second: Vec<IfaStats> = collect(&mut net);
+    let eth0 = interface(&second, "eth0");
+    assert_eq!(eth0.upload_total, 812745410);
+    assert_eq!(eth0.download_total, 5125382112);
+    // Bytes per millisecond, times 1024
+    assert_eq!(eth0.upload_speed, 512000.0 / 1000.0 * 1024.0);
+    assert_eq!(eth0.download_speed, 2048000.0 / 1000.0 * 1024.0);
+    assert_eq!(interface(&second, "wlan0").download_speed, 0.0);
+}
+
+// Fixture whose Unix time went back, like after an NTP correction
+struct ClockGoneBack(Arc<FixtureSource>);
+
+impl Source for ClockGoneBack {
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        self.0.read_to_string(path)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        self.0.read_dir(path)
+    }
+
+    fn statvfs(&self, mountpoint: &str) -> io::Result<FsStats> {
+        self.0.statvfs(mountpoint)
+    }
+
+    fn now_millis(&self) -> u128 {
+        self.0.now_millis() - self.0.monotonic().as_millis() * 3
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.0.monotonic()
+    }
+}
+
+#[test]
+fn net_speeds_use_the_monotonic_clock() {
+    let fixture = FixtureSource::open("pi4");
+    let source: Arc<dyn Source> = Arc::new(ClockGoneBack(fixture.clone()));
+    let mut net = status::builtin_collectors(source)
+        .into_iter()
+        .find(|c| c.name() == "net")
+        .unwrap();
+
+    net.collect().unwrap();
+    fixture.advance();
+    let stats: Vec<IfaStats> = collect(&mut net);
+    assert_eq!(
+        interface(&stats, "eth0").upload_speed,
+        512000.0 / 1000.0 * 1024.0
+    );
+}
+
+#[test]
+fn net_skips_entries_which_are_not_interfaces() {
+    let source = FixtureSource::open("x86");
+    let stats: Vec<IfaStats> = collect(&mut collector(&source, "net"));
+    let mut names = stats
+        .iter()
+        .map(|s| s.interface.interface_name.as_str())
+        .collect::<Vec<&str>>();
+    names.sort();
+
+    assert_eq!(names, ["docker0", "eno1", "lo"]);
+    assert_eq!(
+        interface(&stats, "eno1").interface.interface_path,
+        "/sys/class/net/eno1"
+    );
+}
+
+#[test]
+fn processes() {
+    let source = FixtureSource::open("x86");
+    let mut proc = collector(&source, "proc");
+
+    let first: Vec<Process> = collect(&mut proc);
+    assert_eq!(first.len(), 3);
+
+    // Names with spaces shift the following fields
+    let content = process(&first, "Web Content");
+    assert_eq!(content.pid, 30012);
+    assert_eq!(content.threads, 28);
+    assert_eq!(content.start_time, 9122034);
+    assert_eq!(content.mem, 90211 * 4096);
+    // Nothing to compare the times since the start with on the first run
+    assert!(content.cpu_usage.is_none());
+
+    // Jiffies are hundredths of a second, and the snapshots a second apart
+    source.advance();
+    let second: Vec<Process> = collect(&mut proc);
+    let content = process(&second, "Web Content");
+    let jiffies = (120410 + 8020) - (120334 + 8012);
+    assert!((content.cpu_usage.unwrap() - jiffies as f64).abs() < 1e-9);
+}
+
+#[test]
+fn processes_memory_uses_the_page_size() {
+    let source = FixtureSource::open("pi5");
+    let processes: Vec<Process> = collect(&mut collector(&source, "proc"));
+
+    assert_eq!(process(&processes, "node").mem, 6123 * 16384);
+}
+
+#[test]
+fn processes_starting_and_exiting() {
+    let source = FixtureSource::open("pi4");
+    let mut proc = collector(&source, "proc");
+
+    let first: Vec<Process> = collect(&mut proc);
+    assert!(first.iter().any(|p| p.name == "tmux: server"));
+
+    source.advance();
+    source.advance();
+    let third: Vec<Process> = collect(&mut proc);
+    assert!(!third.iter().any(|p| p.name == "tmux: server"));
+    // New processes report their whole CPU time, over the two seconds since
+    // the previous run
+    let make = process(&third, "make").cpu_usage.unwrap();
+    assert!((make - (40 + 12) as f64 / 2.0).abs() < 1e-9);
+    let pi_status = process(&third, "pi-status").cpu_usage.unwrap();
+    assert!((pi_status - ((8017 + 3303) - (8012 + 3301)) as f64 / 2.0).abs() < 1e-9);
+}
@@ -0,0 +1,108 @@
+// Source reading the fixture trees of `tests/fixtures`. Every machine has a
+// `fixture.toml` and numbered snapshots (`0`, `1`...), each a tree rooted at
+// the host's `/` holding the files the collectors read
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use pi_status::status::source::{FsStats, Source};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Filesystem {
+    mountpoint: String,
+    total: u64,
+    available: u64,
+}
+
+// What can't be read from files
+#[derive(Deserialize)]
+struct FixtureConfig {
+    page_size: u64,
+    // Milliseconds between two snapshots
+    interval: u64,
+    // Unix timestamp of the first snapshot, in milliseconds
+    start: u64,
+    #[serde(default)]
+    filesystems: Vec<Filesystem>,
+}
+
+pub struct FixtureSource {
+    dir: PathBuf,
+    config: FixtureConfig,
+    snapshots: usize,
+    current: AtomicUsize,
+}
+
+impl FixtureSource {
+    pub fn open(machine: &str) -> Arc<Self> {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(machine);
+        let config = fs::read_to_string(dir.join("fixture.toml")).unwrap();
+        let snapshots = (0..)
+            .take_while(|i| dir.join(i.to_string()).is_dir())
+            .count();
+        assert!(snapshots > 0, "{} has no snapshots", machine);
+
+        Arc::new(FixtureSource {
+            config: toml::from_str(&config).unwrap(),
+            dir,
+            snapshots,
+            current: AtomicUsize::new(0),
+        })
+    }
+
+    /// Moves to the next snapshot
+    pub fn advance(&self) {
+        let next = self.current.fetch_add(1, Ordering::Relaxed) + 1;
+        assert!(next < self.snapshots, "No snapshot {}", next);
+    }
+
+    fn path(&self, path: &str) -> PathBuf {
+        let snapshot = self.current.load(Ordering::Relaxed).to_string();
+        self.dir.join(snapshot).join(path.trim_start_matches('/'))
+    }
+}
+
+impl Source for FixtureSource {
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(self.path(path))
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        Ok(fs::read_dir(self.path(path))?
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .collect())
+    }
+
+    fn statvfs(&self, mountpoint: &str) -> io::Result<FsStats> {
+        self.config
+            .filesystems
+            .iter()
+            .find(|f| f.mountpoint == mountpoint)
+            .map(|f| FsStats {
+                total: f.total,
+                available: f.available,
+            })
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn page_size(&self) -> Result<u64> {
+        Ok(self.config.page_size)
+    }
+
+    fn now_millis(&self) -> u128 {
+        self.config.start as u128 + self.monotonic().as_millis()
+    }
+
+    fn monotonic(&self) -> Duration {
+        let snapshot = self.current.load(Ordering::Relaxed) as u64;
+        Duration::from_millis(snapshot * self.config.interval)
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pi_status::demo::{DemoConfig, DemoSource};
+use pi_status::status::collector::Collector;
+use pi_status::status::cpufreq::CpuFreqData;
+use pi_status::status::disk::FsData;
+use pi_status::status::load::LoadData;
+use pi_status::status::net::IfaStats;
+use pi_status::status::proc::Process;
+use pi_status::status::ram::RamData;
+use pi_status::status::{self, source::Source};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+fn demo(seed: u64) -> Arc<DemoSource> {
+    Arc::new(DemoSource::stopped(DemoConfig { seed }))
+}
+
+fn collectors(source: &Arc<DemoSource>) -> Vec<Box<dyn Collector>> {
+    let source: Arc<dyn Source> = source.clone();
+    let mut collectors = status::builtin_collectors(source);
+    for c in collectors.iter_mut() {
+        c.init().unwrap();
+    }
+
+    collectors
+}
+
+fn collect<T: DeserializeOwned>(collectors: &mut [Box<dyn Collector>], name: &str) -> T {
+    let collector = collectors.iter_mut().find(|c| c.name() == name).unwrap();
+    serde_json::from_value(collector.collect().unwrap()).unwrap()
+}
+
+// Every section after each second of a minute, the processes sorted since
+// they are read in parallel
+fn run(seed: u64) -> Vec<Value> {
+    let source = demo(seed);
+    let mut collectors = collectors(&source);
+
+    let mut collected = Vec::new();
+    for _ in 0..60 {
+        source.advance(Duration::from_secs(1));
+        for c in collectors.iter_mut() {
+            let mut data = c.collect().unwrap();
+            if let Value::Array(items) = &mut data {
+                items.sort_by_key(|v| v.to_string());
+            }
+            collected.push(data);
+        }
+    }
+
+    collected
+}
+
+#[test]
+fn the_same_seed_simulates_the_same_machine() {
+    assert_eq!(run(7), run(7));
+    assert_ne!(run(7), run(8));
+}
+
+#[test]
+fn every_collector_reads_the_demo() {
+    let source = demo(1);
+
+    for mut collector in collectors(&source) {
+        collector.collect().unwrap();
+        // Collectors computing deltas only report from their second run
+        source.advance(Duration::from_secs(1));
+        let data = collector.collect().unwrap();
+        assert!(!data.is_null(), "{}", collector.name());
+    }
+}
+
+#[test]
+fn the_simulation_stays_plausible() {
+    let source = demo(3);
+    let mut collectors = collectors(&source);
+
+    let mut pids = HashSet::new();
+    let mut exited = false;
+    let mut backup_used = HashSet::new();
+    let mut fastest_download: f64 = 0.0;
+    let mut temps = Vec::new();
+    let mut frequencies = HashSet::new();
+    let mut under_voltage = false;
+    let mut io_stalled: f64 = 0.0;
+
+    // Two simulated hours, checked every 5 seconds
+    for _ in 0..1440 {
+        source.advance(Duration::from_secs(5));
+
+        let processes: Vec<Process> = collect(&mut collectors, "proc");
+        let current = processes.iter().map(|p| p.pid).collect::<HashSet<u64>>();
+        exited |= pids.iter().any(|p| !current.contains(p));
+        pids.extend(current);
+
+        let ram: RamData = collect(&mut collectors, "ram");
+        assert!(ram.used < ram.total && ram.available <= ram.total);
+
+        let filesystems: Vec<FsData> = collect(&mut collectors, "disk");
+        let backup = filesystems
+            .iter()
+            .find(|f| f.mountpoint == "/mnt/backup")
+            .unwrap();
+        assert!(backup.available <= backup.total);
+        backup_used.insert(backup.total - backup.available);
+
+        let stats: Vec<IfaStats> = collect(&mut collectors, "net");
+        for s in &stats {
+            assert!(s.download_speed >= 0.0 && s.upload_speed >= 0.0);
+        }
+        let eth0 = stats
+            .iter()
+            .find(|s| s.interface.interface_name == "eth0")
+            .unwrap();
+        fastest_download = fastest_download.max(eth0.download_speed);
+
+        let temp: f64 = collect(&mut collectors, "temp");
+        assert!((30.0..85.0).contains(&temp), "{}", temp);
+        temps.push(temp);
+
+        let cpufreq: CpuFreqData = collect(&mut collectors, "cpufreq");
+        assert_eq!(cpufreq.cores.len(), 4);
+        frequencies.extend(cpufreq.cores.values().map(|c| c.cur_khz));
+        let throttling = cpufreq.throttling.unwrap();
+        under_voltage |= throttling.now.under_voltage;
+        // Remembered until the next boot
+        assert!(!under_voltage || throttling.since_boot.under_voltage);
+
+        let load: LoadData = collect(&mut collectors, "load");
+        assert!((0.0..8.0).contains(&load.load1), "{}", load.load1);
+        let pressure = load.pressure.unwrap();
+        assert!(pressure.io.full.unwrap().avg10 <= pressure.io.some.avg10);
+        io_stalled = io_stalled.max(pressure.io.some.avg10);
+    }
+
+    assert!(pids.len() > 20);
+    assert!(exited);
+    assert!(backup_used.len() > 1);
+    // Bytes per millisecond, times 1024: at least 1 MB/s
+    assert!(fastest_download > 1e3 * 1024.0);
+    let (min, max) = temps.iter().fold((f64::MAX, f64::MIN), |(min, max), t| {
+        (min.min(*t), max.max(*t))
+    });
+    assert!(max - min > 5.0);
+    assert!(frequencies.len() > 2);
+    assert!(under_voltage);
+    // The backups stall on the disk
+    assert!(io_stalled > 10.0);
+}
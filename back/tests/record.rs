@@ -0,0 +1,154 @@
+mod common;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use pi_status::record::{Recorder, Replay, ReplayConfig};
+use pi_status::status::{self, source::Source};
+use serde_json::Value;
+
+use common::FixtureSource;
+
+fn recording_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pi-status-{}-{}.gz", name, std::process::id()))
+}
+
+// Sections of every update, with the processes sorted since they are read in
+// parallel
+fn collect_all(source: Arc<dyn Source>, updates: usize, between: impl Fn()) -> Vec<Vec<Value>> {
+    let mut collectors = status::builtin_collectors(source);
+    for c in collectors.iter_mut() {
+        c.init().unwrap();
+    }
+
+    let mut collected = Vec::new();
+    for i in 0..updates {
+        if i > 0 {
+            between();
+        }
+
+        let mut sections = Vec::new();
+        for c in collectors.iter_mut() {
+            let mut data = c.collect().unwrap_or(Value::Null);
+            if let Value::Array(items) = &mut data {
+                items.sort_by_key(|v| v.to_string());
+            }
+            sections.push(data);
+        }
+        collected.push(sections);
+    }
+
+    collected
+}
+
+#[test]
+fn replays_what_was_recorded() {
+    let path = recording_path("replay");
+    let fixture = FixtureSource::open("pi4");
+    let recorder = Recorder::start(&path, fixture.clone()).unwrap();
+
+    let recorded = collect_all(recorder.clone(), 3, || {
+        recorder.commit().unwrap();
+        fixture.advance();
+    });
+    recorder.commit().unwrap();
+    drop(recorder);
+
+    // Slow enough for the replay clock not to move on its own
+    let replay = Arc::new(
+        Replay::open(ReplayConfig {
+            path: path.clone(),
+            speed: 1e-9,
+        })
+        .unwrap(),
+    );
+    let replayed = collect_all(replay.clone(), 3, || replay.skip(1));
+
+    assert_eq!(recorded, replayed);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn replay_keeps_the_recorded_time() {
+    let path = recording_path("time");
+    let fixture = FixtureSource::open("pi3");
+    let recorder = Recorder::start(&path, fixture.clone()).unwrap();
+
+    recorder.read_to_string("/proc/stat").unwrap();
+    recorder.commit().unwrap();
+    fixture.advance();
+    recorder.read_to_string("/proc/stat").unwrap();
+    recorder.commit().unwrap();
+    drop(recorder);
+
+    let replay = Replay::open(ReplayConfig {
+        path: path.clone(),
+        speed: 1e-9,
+    })
+    .unwrap();
+    assert_eq!(replay.now_millis(), 1700000000000);
+    assert_eq!(replay.page_size().unwrap(), 4096);
+    replay.skip(1);
+    assert_eq!(replay.now_millis(), 1700000001000);
+    // Files never read are not in the recording
+    assert!(replay.read_to_string("/proc/meminfo").is_err());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn rejects_files_which_are_not_recordings() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/pi4/fixture.toml");
+
+    assert!(Replay::open(ReplayConfig { path, speed: 1.0 }).is_err());
+}
+
+#[test]
+fn forgets_what_was_below_the_entries_gone() {
+    let path = recording_path("prune");
+    let fixture = FixtureSource::open("pi4");
+    let recorder = Recorder::start(&path, fixture.clone()).unwrap();
+
+    // tmux (pid 2001) exits before the third snapshot
+    recorder.read_dir("/proc").unwrap();
+    recorder.read_to_string("/proc/2001/stat").unwrap();
+    recorder.commit().unwrap();
+    fixture.advance();
+    fixture.advance();
+    recorder.read_dir("/proc").unwrap();
+    recorder.commit().unwrap();
+    drop(recorder);
+
+    let replay = Replay::open(ReplayConfig {
+        path: path.clone(),
+        speed: 1e-9,
+    })
+    .unwrap();
+    assert!(replay.read_to_string("/proc/2001/stat").is_ok());
+    replay.skip(1);
+    assert!(replay.read_to_string("/proc/2001/stat").is_err());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn does_not_cut_frames_during_a_run() {
+    let path = recording_path("run");
+    let fixture = FixtureSource::open("pi3");
+    let recorder = Recorder::start(&path, fixture.clone()).unwrap();
+
+    let run = recorder.run();
+    recorder.read_to_string("/proc/stat").unwrap();
+    recorder.commit().unwrap();
+    drop(run);
+    drop(recorder);
+
+    // Nothing was committed
+    assert!(Replay::open(ReplayConfig {
+        path: path.clone(),
+        speed: 1e-9,
+    })
+    .is_err());
+
+    std::fs::remove_file(path).unwrap();
+}
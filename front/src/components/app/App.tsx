@@ -1,6 +1,8 @@
 import { Tab, tabSchema } from "@/models/app";
-import { CoreData } from "@/models/cpu";
+import { CpuData } from "@/models/cpu";
+import { CpuFreqData } from "@/models/cpufreq";
 import { DiskData } from "@/models/disk";
+import { LoadData } from "@/models/load";
 import { NetValues } from "@/models/net";
 import { ProcessData } from "@/models/proc";
 import { RamData } from "@/models/ram";
@@ -15,7 +17,7 @@ import Net from "../net/Net";
 import Proc from "../procs/Procs";
 import "./App.css";
 import { ErrorBox } from "./ErrorBox";
-import { emptyCoreData, emptyNetValues, emptyRamData } from "./defaults";
+import { emptyNetValues, emptyRamData } from "./defaults";
 import { computeHostData } from "./updates/host";
 import {
     computeUpdatedNetStats,
@@ -38,7 +40,9 @@ export default function App() {
     const [selectedNetInterface, setSelectedNetInterface] = useState<
         string | null
     >(null);
-    const [cpuUsage, setCpuUsage] = useState<CoreData[]>([]);
+    const [cpuUsage, setCpuUsage] = useState<CpuData | null>(null);
+    const [cpuFreq, setCpuFreq] = useState<CpuFreqData | null>(null);
+    const [load, setLoad] = useState<LoadData | null>(null);
     const [ramData, setRamData] = useState<RamData>(emptyRamData);
     const [disks, setDisks] = useState<DiskData[]>([]);
     const [processes, setProcesses] = useState<ProcessData[]>([]);
@@ -149,6 +153,14 @@ export default function App() {
             setCpuUsage(newData.cpu_usage);
         }
 
+        if (newData.cpu_freq) {
+            setCpuFreq(newData.cpu_freq);
+        }
+
+        if (newData.load) {
+            setLoad(newData.load);
+        }
+
         if (newData.ram) {
             setRamData(newData.ram);
         }
@@ -222,7 +234,12 @@ export default function App() {
                     className="m-0 w-full px-2 md:px-4"
                     value={Tab["cpu-tab"]}
                 >
-                    <Cpu temp={temp} cpuUsage={cpuUsage} />
+                    <Cpu
+                        temp={temp}
+                        cpuUsage={cpuUsage}
+                        cpuFreq={cpuFreq}
+                        load={load}
+                    />
                 </Tabs.Content>
                 <Tabs.Content
                     className="m-0 w-full px-2 md:px-4"
@@ -268,12 +285,7 @@ export default function App() {
                     className="m-0 w-full px-2 md:px-4"
                     value={Tab["proc-tab"]}
                 >
-                    <Proc
-                        procs={processes}
-                        mainCpuUsage={
-                            cpuUsage.length > 0 ? cpuUsage[0] : emptyCoreData
-                        }
-                    />
+                    <Proc procs={processes} />
                 </Tabs.Content>
             </div>
         </Tabs.Root>
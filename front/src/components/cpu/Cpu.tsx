@@ -1,31 +1,134 @@
-import { CoreData } from "@/models/cpu";
+import { CpuData, CpuLoad } from "@/models/cpu";
+import { CpuFreqData, ThrottleFlags } from "@/models/cpufreq";
+import { LoadData } from "@/models/load";
 import CpuBar from "./CpuBar";
 
 type CpuProps = {
     temp: number;
-    cpuUsage: CoreData[];
+    cpuUsage: CpuData | null;
+    cpuFreq: CpuFreqData | null;
+    load: LoadData | null;
 };
 
+const throttleLabels: [keyof ThrottleFlags, string][] = [
+    ["under_voltage", "under-voltage"],
+    ["frequency_capped", "frequency capped"],
+    ["throttled", "throttled"],
+    ["soft_temp_limit", "soft temperature limit"],
+];
+
+function throttleSummary(flags: ThrottleFlags): string {
+    return throttleLabels
+        .filter(([flag]) => flags[flag])
+        .map(([, label]) => label)
+        .join(", ");
+}
+
+type ThrottlingProps = {
+    now: ThrottleFlags;
+    sinceBoot: ThrottleFlags;
+};
+
+// Shown once the firmware throttled, in red while it still does
+function Throttling(props: ThrottlingProps) {
+    const now = throttleSummary(props.now);
+    const sinceBoot = throttleSummary(props.sinceBoot);
+    if (!sinceBoot) {
+        return null;
+    }
+
+    return now ? (
+        <div className="text-sm text-ayu-red">{now}</div>
+    ) : (
+        <div className="text-sm text-ayu-yellow">Since boot: {sinceBoot}</div>
+    );
+}
+
+type LoadProps = {
+    load: LoadData;
+};
+
+// Load averages, and the share of the last 10 seconds tasks were stalled
+// waiting for each resource, telling CPU saturation from I/O stalls
+function Load(props: LoadProps) {
+    const { load1, load5, load15, pressure } = props.load;
+
+    return (
+        <div className="text-sm">
+            <p>
+                Load {load1.toFixed(2)} · {load5.toFixed(2)} ·{" "}
+                {load15.toFixed(2)}
+            </p>
+            {pressure && (
+                <p>
+                    Stalls: CPU {pressure.cpu.some.avg10.toFixed(1)}% · memory{" "}
+                    {pressure.memory.some.avg10.toFixed(1)}% · I/O{" "}
+                    {pressure.io.some.avg10.toFixed(1)}%
+                </p>
+            )}
+        </div>
+    );
+}
+
+// Highest frequency of the cores, in GHz
+function frequency(cpuFreq: CpuFreqData): string | null {
+    const frequencies = Object.values(cpuFreq.cores).map((c) => c.cur_khz);
+    if (frequencies.length === 0) {
+        return null;
+    }
+
+    return (Math.max(...frequencies) / 1e6).toFixed(2);
+}
+
+// Online and offline cores, by id
+function cores(cpuUsage: CpuData): [number, CpuLoad | null][] {
+    const cores: [number, CpuLoad | null][] = [
+        ...Object.entries(cpuUsage.cores).map(
+            ([id, load]): [number, CpuLoad | null] => [Number(id), load],
+        ),
+        ...cpuUsage.offline.map((id): [number, CpuLoad | null] => [id, null]),
+    ];
+
+    return cores.sort(([id1], [id2]) => id1 - id2);
+}
+
 export default function Cpu(props: CpuProps) {
     return (
         <div className="flex flex-col gap-3">
-            <div className="temp">{props.temp}°C</div>
+            <div className="temp">
+                {props.temp}°C
+                {props.cpuFreq && frequency(props.cpuFreq) && (
+                    <span> · {frequency(props.cpuFreq)} GHz</span>
+                )}
+            </div>
+            {props.load && <Load load={props.load} />}
+            {props.cpuFreq?.throttling && (
+                <Throttling
+                    now={props.cpuFreq.throttling.now}
+                    sinceBoot={props.cpuFreq.throttling.since_boot}
+                />
+            )}
             <div className="flex w-full flex-col items-center gap-[0.75rem]">
-                {props.cpuUsage.length !== 0 && (
+                {props.cpuUsage && (
                     <div className="mb-2 flex w-full max-w-[1000px] items-center gap-2 whitespace-pre px-1">
                         <p>All</p>
-                        <CpuBar coreData={props.cpuUsage[0]} />
+                        <CpuBar load={props.cpuUsage.total} />
                     </div>
                 )}
-                {props.cpuUsage.slice(1).map((coreUsage, index) => (
-                    <div
-                        className="flex w-full max-w-[1000px] items-center gap-2 whitespace-pre px-1"
-                        key={index}
-                    >
-                        <p>Core {index + 1}</p>
-                        <CpuBar coreData={coreUsage} />
-                    </div>
-                ))}
+                {props.cpuUsage &&
+                    cores(props.cpuUsage).map(([id, load]) => (
+                        <div
+                            className="flex w-full max-w-[1000px] items-center gap-2 whitespace-pre px-1"
+                            key={id}
+                        >
+                            <p>Core {id + 1}</p>
+                            {load ? (
+                                <CpuBar load={load} />
+                            ) : (
+                                <p className="opacity-50">offline</p>
+                            )}
+                        </div>
+                    ))}
             </div>
         </div>
     );
@@ -1,5 +1,4 @@
 import { formatBytes } from "@/lib/bytes";
-import { CoreData } from "@/models/cpu";
 import { ProcessData, ProcessOrder, ProcessProperty } from "@/models/proc";
 import { useOrderStore } from "@/store/order";
 import classNames from "classnames";
@@ -20,7 +19,7 @@ const orderingFromProperty = (
         case ProcessProperty.Memory:
             return (p1, p2) => p2.mem - p1.mem;
         case ProcessProperty.CPU:
-            return (p1, p2) => p2.cpu_usage - p1.cpu_usage;
+            return (p1, p2) => (p2.cpu_usage ?? 0) - (p1.cpu_usage ?? 0);
         default:
             console.error(`Unknown ordering value passed: ${processProperty}`);
             return (_p1, _p2) => 0;
@@ -29,7 +28,6 @@ const orderingFromProperty = (
 
 type ProcProps = {
     procs: ProcessData[];
-    mainCpuUsage: CoreData;
 };
 
 const pidLabelDefault = "PID";
@@ -42,18 +40,6 @@ export default function Procs(props: ProcProps) {
     const { order, setOrder } = useOrderStore();
     const [search, setSearch] = useState("");
 
-    const total =
-        props.mainCpuUsage.user +
-        props.mainCpuUsage.nice +
-        props.mainCpuUsage.system +
-        props.mainCpuUsage.idle +
-        props.mainCpuUsage.iowait +
-        props.mainCpuUsage.irq +
-        props.mainCpuUsage.softirq +
-        props.mainCpuUsage.steal +
-        props.mainCpuUsage.guest +
-        props.mainCpuUsage.guest_nice;
-
     const visibleProcs = props.procs
         .filter(
             (p) =>
@@ -246,9 +232,7 @@ export default function Procs(props: ProcProps) {
                     </button>
                     {visibleProcs.map((p, i) => (
                         <span className="col-content cpu-percs" key={i}>
-                            {((p.cpu_usage / total) * 100)
-                                .toFixed(1)
-                                .padStart(5, " ")}
+                            {(p.cpu_usage ?? 0).toFixed(1).padStart(5, " ")}
                             %
                         </span>
                     ))}
@@ -14,3 +14,38 @@ export const coreDataSchema = z.object({
 });
 
 export type CoreData = z.infer<typeof coreDataSchema>;
+
+// Share of the time spent in each mode, in percents
+export const cpuPercentagesSchema = z.object({
+    busy: z.number(),
+    user: z.number(),
+    nice: z.number(),
+    system: z.number(),
+    idle: z.number(),
+    iowait: z.number(),
+    irq: z.number(),
+    softirq: z.number(),
+    steal: z.number(),
+});
+
+export const cpuLoadSchema = z.object({
+    percent: cpuPercentagesSchema,
+    jiffies: coreDataSchema,
+});
+
+export const cpuDataSchema = z.object({
+    total: cpuLoadSchema,
+    // Online cores, by core id
+    cores: z.record(z.string(), cpuLoadSchema),
+    // Ids of the offline cores
+    offline: z.array(z.number()),
+    context_switches_per_sec: z.number().nullable(),
+    forks_per_sec: z.number().nullable(),
+    procs_running: z.number(),
+    procs_blocked: z.number(),
+    boot_time: z.number(),
+});
+
+export type CpuPercentages = z.infer<typeof cpuPercentagesSchema>;
+export type CpuLoad = z.infer<typeof cpuLoadSchema>;
+export type CpuData = z.infer<typeof cpuDataSchema>;
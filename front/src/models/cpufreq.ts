@@ -0,0 +1,33 @@
+import { z } from "zod";
+
+export const coreFreqSchema = z.object({
+    cur_khz: z.number(),
+    min_khz: z.number(),
+    max_khz: z.number(),
+    governor: z.string(),
+    available_governors: z.array(z.string()),
+    time_in_state_ms: z.record(z.string(), z.number()).nullable(),
+});
+
+export const throttleFlagsSchema = z.object({
+    under_voltage: z.boolean(),
+    frequency_capped: z.boolean(),
+    throttled: z.boolean(),
+    soft_temp_limit: z.boolean(),
+});
+
+export const cpuFreqDataSchema = z.object({
+    // By core id
+    cores: z.record(z.string(), coreFreqSchema),
+    throttling: z
+        .object({
+            flags: z.number(),
+            now: throttleFlagsSchema,
+            since_boot: throttleFlagsSchema,
+        })
+        .nullable(),
+});
+
+export type CoreFreq = z.infer<typeof coreFreqSchema>;
+export type ThrottleFlags = z.infer<typeof throttleFlagsSchema>;
+export type CpuFreqData = z.infer<typeof cpuFreqDataSchema>;
@@ -0,0 +1,34 @@
+import { z } from "zod";
+
+export const stallSchema = z.object({
+    avg10: z.number(),
+    avg60: z.number(),
+    avg300: z.number(),
+    total_us: z.number(),
+    stalled_us: z.number().nullable(),
+});
+
+export const resourcePressureSchema = z.object({
+    some: stallSchema,
+    full: stallSchema.nullable(),
+});
+
+export const loadDataSchema = z.object({
+    load1: z.number(),
+    load5: z.number(),
+    load15: z.number(),
+    runnable: z.number(),
+    tasks: z.number(),
+    last_pid: z.number(),
+    // Null when the kernel has PSI disabled
+    pressure: z
+        .object({
+            cpu: resourcePressureSchema,
+            memory: resourcePressureSchema,
+            io: resourcePressureSchema,
+        })
+        .nullable(),
+});
+
+export type Stall = z.infer<typeof stallSchema>;
+export type LoadData = z.infer<typeof loadDataSchema>;
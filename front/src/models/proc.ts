@@ -5,7 +5,8 @@ export const processDataSchema = z.object({
     name: z.string(),
     mem: z.number(),
     threads: z.number(),
-    cpu_usage: z.number(),
+    // Share of a CPU since the previous update, in percents
+    cpu_usage: z.number().nullable(),
     start_time: z.number(),
 });
 
@@ -1,7 +1,9 @@
 import { z } from "zod";
-import { coreDataSchema } from "./cpu";
+import { cpuDataSchema } from "./cpu";
+import { cpuFreqDataSchema } from "./cpufreq";
 import { diskDataSchema } from "./disk";
 import { hostDataSchema } from "./host";
+import { loadDataSchema } from "./load";
 import { netDataSchema } from "./net";
 import { processDataSchema } from "./proc";
 import { ramDataSchema } from "./ram";
@@ -10,7 +12,9 @@ export const statusDataSchema = z.object({
     host: hostDataSchema.nullable(),
     temp: z.number().nullable(),
     net_stats: z.array(netDataSchema).nullable(),
-    cpu_usage: z.array(coreDataSchema).nullable(),
+    cpu_usage: cpuDataSchema.nullable(),
+    cpu_freq: cpuFreqDataSchema.nullable(),
+    load: loadDataSchema.nullable(),
     ram: ramDataSchema.nullable(),
     disk: z.array(diskDataSchema).nullable(),
     proc: z.array(processDataSchema).nullable(),